@@ -0,0 +1,69 @@
+//! Renders a config for a fixed amount of frames without showing a window and reports timings.
+use super::{App, UserEvent};
+use crate::args::Args;
+use anyhow::Result;
+use glium::{draw_parameters::TimeElapsedQuery, glutin::event_loop::EventLoop, Surface};
+use serde_json::json;
+use std::time::Instant;
+
+/// Returns the min, average and max of a list of timings in milliseconds.
+fn summarize(times: &[f64]) -> serde_json::Value {
+    let min = times.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = times.iter().copied().fold(0.0, f64::max);
+    let avg = times.iter().sum::<f64>() / times.len().max(1) as f64;
+    json!({
+        "min": min,
+        "avg": avg,
+        "max": max,
+    })
+}
+
+pub fn run(event_loop: &EventLoop<UserEvent>, args: &Args) -> Result<()> {
+    ensure!(args.frames > 0, "Benchmark must render at least one frame");
+
+    let (display, config) = App::load_headless(event_loop, args)?;
+
+    let mut frame_times = Vec::with_capacity(args.frames as usize);
+    let mut pass_times = vec![Vec::with_capacity(args.frames as usize); config.passes.len()];
+
+    for _ in 0..args.frames {
+        let queries = config
+            .passes
+            .iter()
+            .map(|_| TimeElapsedQuery::new(&display))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow!("Could not create timing query: {}", e))?;
+
+        let start = Instant::now();
+        let mut target = display.draw();
+        target.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), config.clear_depth());
+        let res = config.render_timed(&mut target, Some(&queries));
+        target.finish()?;
+        res?;
+        display.finish();
+        frame_times.push(start.elapsed().as_secs_f64() * 1000.0);
+
+        for (times, query) in pass_times.iter_mut().zip(queries) {
+            times.push(query.get() as f64 / 1_000_000.0);
+        }
+    }
+
+    let passes: Vec<_> = pass_times
+        .iter()
+        .enumerate()
+        .map(|(idx, times)| {
+            json!({
+                "pass": idx,
+                "gpu_time_ms": summarize(times),
+            })
+        })
+        .collect();
+
+    let report = json!({
+        "frames": args.frames,
+        "frame_time_ms": summarize(&frame_times),
+        "passes": passes,
+    });
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
@@ -0,0 +1,86 @@
+//! Renders a single frame and tiles the outputs of all passes into one image.
+use super::{font, App, UserEvent};
+use crate::args::Args;
+use anyhow::{Context, Result};
+use glium::{glutin::event_loop::EventLoop, texture::RawImage2d, Surface};
+use image::{imageops, ImageFormat, Rgba, RgbaImage};
+use std::path::Path;
+
+/// The width and height of a single tile in the sheet.
+const TILE_SIZE: u32 = 256;
+/// Space between the tiles.
+const SPACING: u32 = 4;
+/// The size of a label font pixel in image pixels.
+const LABEL_SCALE: u32 = 2;
+/// The height of the label below each tile.
+const LABEL_HEIGHT: u32 = font::GLYPH_HEIGHT * LABEL_SCALE + SPACING;
+
+/// Scale an image to fit in a tile while keeping its aspect ratio.
+fn fit(image: &RgbaImage) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let scale = (TILE_SIZE as f32 / width as f32).min(TILE_SIZE as f32 / height as f32);
+    let width = ((width as f32 * scale) as u32).max(1);
+    let height = ((height as f32 * scale) as u32).max(1);
+    imageops::resize(image, width, height, imageops::FilterType::Triangle)
+}
+
+pub fn run(event_loop: &EventLoop<UserEvent>, args: &Args, path: &Path) -> Result<()> {
+    let (display, config) = App::load_headless(event_loop, args)?;
+
+    let mut target = display.draw();
+    target.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), config.clear_depth());
+    let res = config.render(&mut target);
+    target.finish()?;
+    res?;
+    display.finish();
+
+    let mut tiles = Vec::new();
+    for (pass_id, idx) in config.pass_outputs() {
+        let texture = &config.textures[idx];
+        if let Some(image) = texture.read_rgba() {
+            let label = format!("pass {}: {}", pass_id, texture.config.name);
+            tiles.push((label, image));
+        }
+    }
+    let frame: RawImage2d<u8> = display
+        .read_front_buffer()
+        .context("failed to read back frame")?;
+    let frame = RgbaImage::from_raw(frame.width, frame.height, frame.data.into_owned())
+        .context("frame has an invalid size")?;
+    tiles.push(("frame".to_string(), frame));
+
+    let columns = (tiles.len() as f32).sqrt().ceil() as u32;
+    let rows = (tiles.len() as u32).div_ceil(columns);
+    let mut sheet = RgbaImage::from_pixel(
+        columns * (TILE_SIZE + SPACING) + SPACING,
+        rows * (TILE_SIZE + LABEL_HEIGHT + SPACING) + SPACING,
+        Rgba([0, 0, 0, 255]),
+    );
+    // Labels longer than a tile are cut off.
+    let max_chars = ((TILE_SIZE / LABEL_SCALE + 1) / font::ADVANCE) as usize;
+    for (idx, (label, image)) in tiles.iter().enumerate() {
+        let (column, row) = (idx as u32 % columns, idx as u32 / columns);
+        // OpenGL images start at the bottom row.
+        let tile = fit(&imageops::flip_vertical(image));
+        let x = SPACING + column * (TILE_SIZE + SPACING) + (TILE_SIZE - tile.width()) / 2;
+        let top = SPACING + row * (TILE_SIZE + LABEL_HEIGHT + SPACING);
+        let y = top + (TILE_SIZE - tile.height()) / 2;
+        imageops::replace(&mut sheet, &tile, x, y);
+
+        let label: String = label.chars().take(max_chars).collect();
+        let x = SPACING + column * (TILE_SIZE + SPACING);
+        let x = x + (TILE_SIZE - font::text_width(&label, LABEL_SCALE)) / 2;
+        let y = top + TILE_SIZE + SPACING;
+        font::draw_text(
+            &mut sheet,
+            x,
+            y,
+            LABEL_SCALE,
+            Rgba([255, 255, 255, 255]),
+            &label,
+        );
+    }
+    sheet
+        .save_with_format(path, ImageFormat::Png)
+        .with_context(|| format!("failed to write contact sheet to `{}`", path.display()))
+}
@@ -0,0 +1,138 @@
+//! A tiny bitmap font for drawing ASCII labels into images.
+use image::{Rgba, RgbaImage};
+
+/// The width of a glyph in pixels before scaling.
+pub const GLYPH_WIDTH: u32 = 3;
+/// The height of a glyph in pixels before scaling.
+pub const GLYPH_HEIGHT: u32 = 5;
+/// The horizontal distance between the start of two glyphs in pixels before scaling.
+pub const ADVANCE: u32 = GLYPH_WIDTH + 1;
+
+/// Returns the rows of a glyph from top to bottom, the highest of the three bits is the left
+/// most pixel. Lowercase letters are drawn as uppercase and unknown characters as `?`.
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '(' => [0b010, 0b100, 0b100, 0b100, 0b010],
+        ')' => [0b010, 0b001, 0b001, 0b001, 0b010],
+        '[' => [0b110, 0b100, 0b100, 0b100, 0b110],
+        ']' => [0b011, 0b001, 0b001, 0b001, 0b011],
+        _ => [0b111, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+/// Returns the width in pixels `text` takes up when drawn at the given scale.
+pub fn text_width(text: &str, scale: u32) -> u32 {
+    (text.chars().count() as u32 * ADVANCE).saturating_sub(1) * scale
+}
+
+/// Draws `text` with its top left corner at `x`, `y`, each font pixel is drawn as a
+/// `scale` by `scale` square. Pixels outside the image are skipped.
+pub fn draw_text(image: &mut RgbaImage, x: u32, y: u32, scale: u32, color: Rgba<u8>, text: &str) {
+    for (idx, c) in text.chars().enumerate() {
+        let glyph_x = x + idx as u32 * ADVANCE * scale;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+                let px = glyph_x + column * scale;
+                let py = y + row as u32 * scale;
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        if px + dx < image.width() && py + dy < image.height() {
+                            image.put_pixel(px + dx, py + dy, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    fn lit(image: &RgbaImage) -> Vec<(u32, u32)> {
+        image
+            .enumerate_pixels()
+            .filter(|(_, _, p)| p[0] != 0)
+            .map(|(x, y, _)| (x, y))
+            .collect()
+    }
+
+    #[test]
+    fn draws_within_text_width() {
+        let scale = 2;
+        let text = "pass 0: a_b";
+        let mut image = RgbaImage::new(100, 20);
+        draw_text(&mut image, 1, 1, scale, WHITE, text);
+        let pixels = lit(&image);
+        assert!(!pixels.is_empty());
+        let right = pixels.iter().map(|(x, _)| *x).max().unwrap();
+        let bottom = pixels.iter().map(|(_, y)| *y).max().unwrap();
+        assert_eq!(right, text_width(text, scale));
+        assert_eq!(bottom, GLYPH_HEIGHT * scale);
+    }
+
+    #[test]
+    fn lowercase_matches_uppercase() {
+        let mut lower = RgbaImage::new(20, 10);
+        let mut upper = RgbaImage::new(20, 10);
+        draw_text(&mut lower, 0, 0, 1, WHITE, "frame");
+        draw_text(&mut upper, 0, 0, 1, WHITE, "FRAME");
+        assert_eq!(lower, upper);
+    }
+
+    #[test]
+    fn clips_at_image_edge() {
+        let mut image = RgbaImage::new(4, 4);
+        draw_text(&mut image, 2, 2, 3, WHITE, "88");
+        assert!(!lit(&image).is_empty());
+    }
+}
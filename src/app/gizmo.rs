@@ -0,0 +1,142 @@
+//! A transform gizmo for moving, rotating and scaling the selected object.
+use crate::config::Config;
+use egui::{Area, Color32, Id, LayerId, Order, Pos2, Rect, Sense, Stroke, Vec2};
+use glam::f32::{Mat4, Vec3, Vec4};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl GizmoMode {
+    pub fn label(&self) -> &'static str {
+        match *self {
+            GizmoMode::Translate => "Translate",
+            GizmoMode::Rotate => "Rotate",
+            GizmoMode::Scale => "Scale",
+        }
+    }
+}
+
+/// Size of the gizmo handles on screen in points.
+const HANDLE_RADIUS: f32 = 7.0;
+/// Length of the gizmo axes relative to the distance to the camera.
+const AXIS_SCALE: f32 = 0.15;
+
+const AXES: [(Vec3, Color32); 3] = [
+    (Vec3::X, Color32::RED),
+    (Vec3::Y, Color32::GREEN),
+    (Vec3::Z, Color32::BLUE),
+];
+
+fn dot(a: Vec2, b: Vec2) -> f32 {
+    a.x * b.x + a.y * b.y
+}
+
+/// Project a point from world space to screen space, returns `None` if the point is behind the
+/// camera.
+fn project(view_projection: Mat4, screen: Rect, point: Vec3) -> Option<(Pos2, f32)> {
+    let clip = view_projection * Vec4::new(point.x, point.y, point.z, 1.0);
+    if clip.w <= 0.0 {
+        return None;
+    }
+    let ndc = clip / clip.w;
+    let pos = Pos2::new(
+        screen.left() + (ndc.x + 1.0) * 0.5 * screen.width(),
+        screen.top() + (1.0 - ndc.y) * 0.5 * screen.height(),
+    );
+    Some((pos, clip.w))
+}
+
+/// Draw the gizmo for the selected object and apply any drags on its handles.
+pub fn show(ctx: &egui::Context, config: &mut Config, mode: GizmoMode) {
+    let selected = match config.selected_object {
+        Some(x) => x,
+        None => return,
+    };
+    let mut transform = config.objects[selected].transform();
+    let view_projection = config.get_perspective_matrix() * config.get_camera_matrix();
+    let screen = ctx.input().screen_rect();
+
+    let origin = config.objects[selected]
+        .matrix()
+        .transform_point3(Vec3::ZERO);
+    let (origin_screen, depth) = match project(view_projection, screen, origin) {
+        Some(x) => x,
+        None => return,
+    };
+    let length = depth * AXIS_SCALE;
+
+    let painter = ctx.layer_painter(LayerId::new(Order::Background, Id::new("gizmo")));
+
+    for (idx, (axis, color)) in AXES.iter().copied().enumerate() {
+        let (tip_screen, _) = match project(view_projection, screen, origin + axis * length) {
+            Some(x) => x,
+            None => continue,
+        };
+        let direction = tip_screen - origin_screen;
+        if direction.length_sq() < 1.0 {
+            continue;
+        }
+        painter.line_segment([origin_screen, tip_screen], Stroke::new(2.0, color));
+
+        let drag = Area::new(("gizmo_handle", idx))
+            .order(Order::Foreground)
+            .fixed_pos(tip_screen - Vec2::splat(HANDLE_RADIUS))
+            .show(ctx, |ui| {
+                let (rect, response) =
+                    ui.allocate_exact_size(Vec2::splat(HANDLE_RADIUS * 2.0), Sense::drag());
+                let color = if response.hovered() || response.dragged() {
+                    Color32::WHITE
+                } else {
+                    color
+                };
+                match mode {
+                    GizmoMode::Translate => {
+                        ui.painter()
+                            .circle_filled(rect.center(), HANDLE_RADIUS, color);
+                    }
+                    GizmoMode::Rotate => {
+                        ui.painter().circle_stroke(
+                            rect.center(),
+                            HANDLE_RADIUS - 1.0,
+                            Stroke::new(2.0, color),
+                        );
+                    }
+                    GizmoMode::Scale => {
+                        ui.painter().rect_filled(rect, 0.0, color);
+                    }
+                }
+                response.drag_delta()
+            })
+            .inner;
+        if drag == Vec2::ZERO {
+            continue;
+        }
+
+        // Amount of the drag along the handle, where 1.0 is the length of the axis.
+        let along = dot(drag, direction) / direction.length_sq();
+        match mode {
+            GizmoMode::Translate => {
+                // The position is applied before rotation and scale so convert the world space
+                // movement back into object space.
+                let inverse = transform.rotation_scale_matrix().inverse();
+                let delta = inverse.transform_vector3(axis * along * length);
+                if delta.is_finite() {
+                    transform.position += delta;
+                }
+            }
+            GizmoMode::Rotate => {
+                let perpendicular = Vec2::new(-direction.y, direction.x).normalized();
+                transform.rotation[idx] += dot(drag, perpendicular);
+            }
+            GizmoMode::Scale => {
+                transform.scale[idx] = (transform.scale[idx] * (1.0 + along)).max(0.001);
+            }
+        }
+    }
+
+    config.objects[selected].set_transform(transform);
+}
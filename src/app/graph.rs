@@ -0,0 +1,18 @@
+//! Writes the render graph of the config as a Graphviz diagram.
+use super::{App, UserEvent};
+use crate::args::Args;
+use anyhow::{Context, Result};
+use glium::glutin::event_loop::EventLoop;
+use std::fs;
+
+pub fn run(event_loop: &EventLoop<UserEvent>, args: &Args) -> Result<()> {
+    let (_display, config) = App::load_headless(event_loop, args)?;
+
+    let graph = config.render_graph();
+    match args.render_graph_output {
+        Some(ref path) => fs::write(path, graph)
+            .with_context(|| format!("Failed to write render graph to `{}`", path.display()))?,
+        None => print!("{}", graph),
+    }
+    Ok(())
+}
@@ -1,24 +1,60 @@
-use crate::config::{BuiltinUniform, CustomUniform, LoadedPass, UniformBinding, UniformData};
+use crate::{
+    config::{
+        component_count, BuiltinUniform, Config, CustomUniform, LoadedPass, LoadedTexture, Tonemap,
+        UniformBinding, UniformData, UniformRange,
+    },
+    util::Rng,
+};
 
-use super::{App, State};
-use egui::{self, menu, Color32, ComboBox, DragValue, RichText, Ui, Window};
-use glium::program::Uniform;
+use super::{
+    gizmo::{self, GizmoMode},
+    App, State,
+};
+use anyhow::{Context, Result};
+use egui::{
+    self, emath::Numeric, menu, CollapsingHeader, Color32, ComboBox, DragValue, RichText, Slider,
+    Ui, Window,
+};
+use std::{
+    collections::BTreeMap,
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
 pub struct Gui {
     show_uniforms: bool,
+    show_objects: bool,
+    /// Whether the dialog confirming that the config file is rewritten is open.
+    confirm_save_uniforms: bool,
+    gizmo_mode: GizmoMode,
+    /// Seed used the next time the uniforms are randomized.
+    random_seed: u64,
+    /// Pixels per point of the interface, follows the scale of the window if not set.
+    ui_scale: Option<f32>,
 }
 
 impl Gui {
     pub fn new() -> Self {
         Gui {
             show_uniforms: false,
+            show_objects: false,
+            confirm_save_uniforms: false,
+            gizmo_mode: GizmoMode::Translate,
+            random_seed: 0,
+            ui_scale: None,
         }
     }
 }
 
 impl App {
     pub fn draw_gui(&mut self) -> bool {
+        let native_scale = self.display.gl_window().window().scale_factor() as f32;
         self.egui.run(&self.display, |ctx| {
+            if let Some(scale) = self.gui.ui_scale {
+                ctx.set_pixels_per_point(scale);
+            }
+
             egui::TopBottomPanel::top("menu").show(ctx, |ui| {
                 menu::bar(ui, |ui| {
                     ui.menu_button("Shader Tool", |ui| {
@@ -26,10 +62,66 @@ impl App {
                             self.should_run = false;
                         }
                     });
+                    ui.menu_button("Settings", |ui| {
+                        let mut scale = self.gui.ui_scale.unwrap_or(native_scale);
+                        if ui
+                            .add(Slider::new(&mut scale, 0.5..=4.0).text("UI scale"))
+                            .changed()
+                        {
+                            self.gui.ui_scale = Some(scale);
+                        }
+                        if ui.button("Reset UI scale").clicked() {
+                            self.gui.ui_scale = None;
+                            ctx.set_pixels_per_point(native_scale);
+                        }
+                    });
                     ui.menu_button("Scene", |ui| {
                         if ui.button("Toggle Uniforms").clicked() {
                             self.gui.show_uniforms = !self.gui.show_uniforms;
                         }
+                        if ui.button("Toggle Objects").clicked() {
+                            self.gui.show_objects = !self.gui.show_objects;
+                        }
+                        if self.state.active_config().is_some()
+                            && ui.button("Save uniforms").clicked()
+                        {
+                            self.gui.confirm_save_uniforms = true;
+                            ui.close_menu();
+                        }
+                        if let Some(config) = self.state.active_config_mut() {
+                            ui.checkbox(&mut config.camera_locked, "Lock camera");
+                            ui.checkbox(&mut config.fxaa.enabled, "FXAA");
+                            ComboBox::from_label("Tone mapping")
+                                .selected_text(config.tonemap.operator.label())
+                                .show_ui(ui, |ui| {
+                                    for x in Tonemap::ALL {
+                                        ui.selectable_value(
+                                            &mut config.tonemap.operator,
+                                            x,
+                                            x.label(),
+                                        );
+                                    }
+                                });
+                            if config.tonemap.enabled() {
+                                ui.add(
+                                    Slider::new(&mut config.tonemap.exposure, 0.0..=10.0)
+                                        .logarithmic(true)
+                                        .text("Exposure"),
+                                );
+                            }
+                            if let Some(grading) = config.grading.as_mut() {
+                                ui.checkbox(&mut grading.enabled, "Color grading");
+                                ui.add(
+                                    Slider::new(&mut grading.intensity, 0.0..=1.0)
+                                        .text("Grading intensity"),
+                                );
+                            }
+                            if !config.buffers.is_empty() && ui.button("Dump Buffers").clicked() {
+                                if let Err(e) = config.dump_buffers() {
+                                    error!("{:?}", e);
+                                }
+                            }
+                        }
                     });
                 });
             });
@@ -37,7 +129,19 @@ impl App {
             match self.state {
                 State::NotLoaded { ref error } | State::ReloadError { ref error, .. } => {
                     egui::TopBottomPanel::bottom("error_panel").show(ctx, |ui| {
-                        ui.heading(RichText::new("！  Error").strong().color(Color32::RED));
+                        ui.horizontal(|ui| {
+                            ui.heading(RichText::new("！  Error").strong().color(Color32::RED));
+                            if ui.button("Copy error").clicked() {
+                                ui.output().copied_text = error.clone();
+                            }
+                            if let Some((path, line)) = error_location(error) {
+                                if ui.button("Open in editor").clicked() {
+                                    if let Err(e) = open_in_editor(&path, line) {
+                                        error!("{:?}", e);
+                                    }
+                                }
+                            }
+                        });
                         ui.separator();
                         ui.monospace(error);
                     });
@@ -45,51 +149,326 @@ impl App {
                 _ => {}
             }
 
+            if self.gui.confirm_save_uniforms {
+                Window::new("Save uniforms")
+                    .collapsible(false)
+                    .resizable(false)
+                    .show(ctx, |ui| match self.state.config_path() {
+                        Some(path) => {
+                            ui.label(format!("Write the current uniform values into `{}`?", path));
+                            ui.label("Only the uniforms of the passes are rewritten.");
+                            ui.horizontal(|ui| {
+                                if ui.button("Save").clicked() {
+                                    let config = self.state.active_config().unwrap();
+                                    if let Err(e) = config.save_uniforms(Path::new(path)) {
+                                        error!("{:?}", e);
+                                    }
+                                    self.gui.confirm_save_uniforms = false;
+                                }
+                                if ui.button("Cancel").clicked() {
+                                    self.gui.confirm_save_uniforms = false;
+                                }
+                            });
+                        }
+                        None => self.gui.confirm_save_uniforms = false,
+                    });
+            }
+
             Window::new("Uniforms")
                 .open(&mut self.gui.show_uniforms)
                 .show(ctx, |ui| {
                     if let Some(config) = self.state.active_config_mut() {
+                        ui.horizontal(|ui| {
+                            if ui.button("Randomize uniforms").clicked() {
+                                let mut rng = Rng::new(self.gui.random_seed);
+                                Self::randomize_uniforms(config, &mut rng);
+                                self.gui.random_seed += 1;
+                            }
+                            ui.label("Seed:");
+                            ui.add(DragValue::new(&mut self.gui.random_seed));
+                        });
+                        if ui
+                            .button("Reroll seed")
+                            .on_hover_text("Pick a new value for the `seed` uniform")
+                            .clicked()
+                        {
+                            config.reroll_seed();
+                        }
+                        if config.is_recording() {
+                            if ui.button("Stop recording").clicked() {
+                                if let Err(e) = config.stop_recording() {
+                                    error!("{:?}", e);
+                                }
+                            }
+                        } else if ui.button("Start recording").clicked() {
+                            match config.start_recording() {
+                                Ok(()) => {
+                                    let path = config.recording_path().unwrap();
+                                    Self::ignore_changes(&mut self.written_files, &path);
+                                }
+                                Err(e) => error!("{:?}", e),
+                            }
+                        }
                         if config.passes.is_empty() {
                             ui.label("Config does not contain any render passes!");
                         } else {
                             for (pass_id, pass) in config.passes.iter_mut().enumerate() {
                                 ui.collapsing(format!("pass: {}", pass_id), |ui| {
-                                    Self::render_uniforms(ui, pass, pass_id);
+                                    Self::render_uniforms(ui, pass, pass_id, &config.textures);
                                 });
                             }
                         }
+                        if !config.clocks.is_empty() {
+                            ui.collapsing("Clocks", |ui| {
+                                Self::render_clocks(ui, config);
+                            });
+                        }
+                        ui.collapsing("Objects", |ui| {
+                            Self::render_object_counts(ui, config);
+                        });
+                    } else {
+                        ui.label("Config not loaded");
+                    }
+                });
+
+            Window::new("Objects")
+                .open(&mut self.gui.show_objects)
+                .show(ctx, |ui| {
+                    if let Some(config) = self.state.active_config_mut() {
+                        if config.objects.is_empty() {
+                            ui.label("Config does not contain any objects!");
+                        } else {
+                            for (idx, object) in config.objects.iter().enumerate() {
+                                let selected = config.selected_object == Some(idx);
+                                if ui.selectable_label(selected, &object.name).clicked() {
+                                    config.selected_object = (!selected).then_some(idx);
+                                }
+                            }
+                            if let Some(idx) = config.selected_object {
+                                ui.separator();
+                                Self::render_transform(ui, config, idx, &mut self.gui.gizmo_mode);
+                            }
+                        }
                     } else {
                         ui.label("Config not loaded");
                     }
                 });
+
+            if self.gui.show_objects {
+                if let Some(config) = self.state.active_config_mut() {
+                    gizmo::show(ctx, config, self.gui.gizmo_mode);
+                }
+            }
         })
     }
 
-    pub fn render_uniforms(ui: &mut Ui, pass: &mut LoadedPass, pass_id: usize) {
-        if pass.uniforms.is_empty() {
-            ui.label("Pass does not contain any uniforms");
+    pub fn render_transform(ui: &mut Ui, config: &mut Config, object: usize, mode: &mut GizmoMode) {
+        ui.horizontal(|ui| {
+            for m in [GizmoMode::Translate, GizmoMode::Rotate, GizmoMode::Scale] {
+                ui.selectable_value(mode, m, m.label());
+            }
+        });
+
+        let mut transform = config.objects[object].transform();
+        egui::Grid::new("transform_grid").show(ui, |ui| {
+            let rows = [
+                ("Position", &mut transform.position, 0.05),
+                ("Rotation", &mut transform.rotation, 1.0),
+                ("Scale", &mut transform.scale, 0.05),
+            ];
+            for (label, value, speed) in rows {
+                ui.label(label);
+                let mut array: [f32; 3] = (*value).into();
+                ui.horizontal(|ui| {
+                    for (v, n) in array.iter_mut().zip(["x:", "y:", "z:"].iter()) {
+                        ui.label(*n);
+                        ui.add(DragValue::new(v).speed(speed));
+                    }
+                });
+                *value = array.into();
+                ui.end_row();
+            }
+        });
+        if transform != config.objects[object].transform() {
+            config.objects[object].set_transform(transform);
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Save Transform").clicked() {
+                config.save_object_transform(object);
+            }
+            if ui.button("Reset Transform").clicked() {
+                config.reset_object_transform(object);
+            }
+        });
+    }
+
+    pub fn render_object_counts(ui: &mut Ui, config: &Config) {
+        if config.objects.is_empty() {
+            ui.label("Config does not contain any objects!");
         } else {
-            egui::Grid::new(("uniforms_grid", pass_id)).show(ui, |ui| {
+            egui::Grid::new("object_counts_grid").show(ui, |ui| {
                 ui.label(RichText::new("Name").italics());
-                ui.label(RichText::new("Binding").italics());
-                ui.label(RichText::new("Value").italics());
+                ui.label(RichText::new("Vertices").italics());
+                ui.label(RichText::new("Indices").italics());
                 ui.end_row();
-                ui.separator();
-                ui.separator();
-                ui.separator();
+                for object in config.objects.iter() {
+                    ui.monospace(&object.name);
+                    ui.label(object.vertex_count().to_string());
+                    ui.label(object.index_count().to_string());
+                    ui.end_row();
+                }
+            });
+        }
+    }
+
+    pub fn render_clocks(ui: &mut Ui, config: &mut Config) {
+        egui::Grid::new("clocks_grid").show(ui, |ui| {
+            ui.label(RichText::new("Name").italics());
+            ui.label(RichText::new("Time").italics());
+            ui.label(RichText::new("Scale").italics());
+            ui.end_row();
+            for clock in config.clocks.iter_mut() {
+                ui.monospace(&clock.name);
+                let mut time = clock.time();
+                if ui.add(DragValue::new(&mut time).speed(0.05)).changed() {
+                    clock.set_time(time);
+                }
+                let mut scale = clock.scale();
+                if ui.add(DragValue::new(&mut scale).speed(0.01)).changed() {
+                    clock.set_scale(scale);
+                }
+                let label = if clock.is_paused() { "Play" } else { "Pause" };
+                if ui.button(label).clicked() {
+                    clock.set_paused(!clock.is_paused());
+                }
                 ui.end_row();
-                pass.uniforms
-                    .iter_mut()
-                    .enumerate()
-                    .for_each(|(idx, (name, value))| {
-                        ui.monospace(name);
-                        Self::render_uniform_data(ui, value, idx, pass_id);
+            }
+        });
+    }
+
+    /// Give all custom uniforms of all passes a random value.
+    pub fn randomize_uniforms(config: &mut Config, rng: &mut Rng) {
+        for pass in config.passes.iter_mut() {
+            // Sort the uniforms so the same seed always gives the same values.
+            let mut uniforms: Vec<_> = pass.uniforms.iter_mut().collect();
+            uniforms.sort_by(|a, b| a.0.cmp(b.0));
+            for (_, data) in uniforms {
+                if let UniformBinding::Custom(ref mut x) = data.binding {
+                    x.randomize(rng);
+                }
+            }
+        }
+    }
+
+    pub fn render_uniforms(
+        ui: &mut Ui,
+        pass: &mut LoadedPass,
+        pass_id: usize,
+        textures: &[LoadedTexture],
+    ) {
+        // Textures the pass renders to can't be sampled at the same time unless double buffered.
+        let sampleable: Vec<_> = (0..textures.len())
+            .filter(|x| !pass.renders_to(*x) || textures[*x].is_double_buffered())
+            .collect();
+        if pass.uniforms.is_empty() {
+            ui.label("Pass does not contain any uniforms");
+            return;
+        }
+
+        // Uniforms without a group are shown first under "General".
+        let mut groups: BTreeMap<Option<String>, Vec<_>> = BTreeMap::new();
+        for (idx, (name, value)) in pass.uniforms.iter_mut().enumerate() {
+            groups
+                .entry(value.group.clone())
+                .or_default()
+                .push((idx, name, value));
+        }
+        for (group, uniforms) in groups {
+            let group = group.as_deref().unwrap_or("General");
+            CollapsingHeader::new(group)
+                .id_source(("uniform_group", group, pass_id))
+                .default_open(true)
+                .show(ui, |ui| {
+                    egui::Grid::new(("uniforms_grid", group, pass_id)).show(ui, |ui| {
+                        ui.label(RichText::new("Name").italics());
+                        ui.label(RichText::new("Binding").italics());
+                        ui.label(RichText::new("Value").italics());
+                        ui.label(RichText::new("Record").italics());
+                        ui.end_row();
+                        ui.separator();
+                        ui.separator();
+                        ui.separator();
+                        ui.separator();
                         ui.end_row();
+                        for (idx, name, value) in uniforms {
+                            let name_label = match value.label {
+                                Some(ref label) => ui.label(label),
+                                None => ui.monospace(name),
+                            };
+                            name_label.on_hover_text(uniform_tooltip(name, value));
+                            if LoadedTexture::is_sampler(value.kind.ty) {
+                                Self::render_sampler_binding(
+                                    ui,
+                                    value,
+                                    textures,
+                                    &sampleable,
+                                    idx,
+                                    pass_id,
+                                );
+                            } else {
+                                Self::render_uniform_data(ui, value, idx, pass_id);
+                            }
+                            if value.kind.size.is_none() && component_count(value.kind.ty) > 0 {
+                                ui.checkbox(&mut value.record, "");
+                            }
+                            ui.end_row();
+                        }
                     });
-            });
+                });
         }
     }
 
+    /// Select the texture bound to a sampler, by default the texture the sampler is named after.
+    fn render_sampler_binding(
+        ui: &mut Ui,
+        data: &mut UniformData,
+        textures: &[LoadedTexture],
+        sampleable: &[usize],
+        idx: usize,
+        pass_id: usize,
+    ) {
+        let selected = match data.binding {
+            UniformBinding::Texture(x) => textures[x].config.name.as_str(),
+            _ => "Default",
+        };
+        ComboBox::from_id_source(("uniform_texture", idx, pass_id))
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                if ui
+                    .selectable_label(
+                        !matches!(data.binding, UniformBinding::Texture(_)),
+                        "Default",
+                    )
+                    .clicked()
+                {
+                    data.binding = UniformBinding::Unbound;
+                }
+                for x in sampleable.iter().copied() {
+                    if !textures[x].matches_sampler(data.kind.ty) {
+                        continue;
+                    }
+                    let is_selected = matches!(data.binding, UniformBinding::Texture(y) if y == x);
+                    if ui
+                        .selectable_label(is_selected, &textures[x].config.name)
+                        .clicked()
+                    {
+                        data.binding = UniformBinding::Texture(x);
+                    }
+                }
+            });
+    }
+
     pub fn render_uniform_data(ui: &mut Ui, data: &mut UniformData, idx: usize, pass_id: usize) {
         #[derive(Clone, Copy, Eq, PartialEq)]
         enum BindChoice {
@@ -111,18 +490,16 @@ impl App {
                 match *binding {
                     UniformBinding::Custom(_) => BindChoice::Custom,
                     UniformBinding::Builtin(_) => BindChoice::Builtin,
-                    UniformBinding::Unbound => BindChoice::Unbound,
+                    UniformBinding::Unbound | UniformBinding::Texture(_) => BindChoice::Unbound,
                 }
             }
 
-            fn into_binding(self, uniform: &Uniform) -> UniformBinding {
+            fn into_binding(self, data: &UniformData) -> UniformBinding {
                 match self {
-                    Self::Custom => UniformBinding::Custom(
-                        CustomUniform::from_uniform_type(uniform.ty).unwrap(),
-                    ),
-                    Self::Builtin => UniformBinding::Builtin(
-                        BuiltinUniform::valid_for_uniform_type(uniform.ty)[0],
-                    ),
+                    Self::Custom => UniformBinding::Custom(data.default_custom().unwrap()),
+                    Self::Builtin => {
+                        UniformBinding::Builtin(BuiltinUniform::valid_for_uniform(&data.kind)[0])
+                    }
                     Self::Unbound => UniformBinding::Unbound,
                 }
             }
@@ -138,14 +515,16 @@ impl App {
                     BindChoice::Unbound,
                     BindChoice::Unbound.label(),
                 );
-                if CustomUniform::from_uniform_type(data.kind.ty).is_some() {
+                if data.kind.size.is_none()
+                    && CustomUniform::from_uniform_type(data.kind.ty).is_some()
+                {
                     ui.selectable_value(
                         &mut choice,
                         BindChoice::Custom,
                         BindChoice::Custom.label(),
                     );
                 }
-                if !BuiltinUniform::valid_for_uniform_type(data.kind.ty).is_empty() {
+                if !BuiltinUniform::valid_for_uniform(&data.kind).is_empty() {
                     ui.selectable_value(
                         &mut choice,
                         BindChoice::Builtin,
@@ -153,9 +532,10 @@ impl App {
                     );
                 }
             });
+        let range = data.range;
         match data.binding {
             UniformBinding::Builtin(ref mut x) => {
-                let valid = BuiltinUniform::valid_for_uniform_type(data.kind.ty);
+                let valid = BuiltinUniform::valid_for_uniform(&data.kind);
                 ComboBox::from_id_source(("uniform_builtin", idx, pass_id))
                     .selected_text(x.label())
                     .show_ui(ui, |ui| {
@@ -164,13 +544,23 @@ impl App {
                         }
                     });
             }
+            UniformBinding::Custom(CustomUniform::Vec4(ref mut x)) if data.color => {
+                let mut array: [f32; 4] = (*x).into();
+                ui.color_edit_button_rgba_unmultiplied(&mut array);
+                *x = array.into();
+            }
+            UniformBinding::Custom(CustomUniform::Vec3(ref mut x)) if data.color => {
+                let mut array: [f32; 3] = (*x).into();
+                ui.color_edit_button_rgb(&mut array);
+                *x = array.into();
+            }
             UniformBinding::Custom(CustomUniform::Vec4(ref mut x)) => {
                 let mut array: [f32; 4] = (*x).into();
                 let names = ["x:", "y:", "z:", "w:"];
                 ui.horizontal(|ui| {
                     for (v, n) in array.iter_mut().zip(names.iter()) {
                         ui.label(*n);
-                        ui.add(DragValue::new(v).speed(0.05));
+                        ui.add(ranged_drag(v, 0.05, &range));
                     }
                 });
                 *x = array.into();
@@ -181,7 +571,7 @@ impl App {
                 ui.horizontal(|ui| {
                     for (v, n) in array.iter_mut().zip(names.iter()) {
                         ui.label(*n);
-                        ui.add(DragValue::new(v).speed(0.05));
+                        ui.add(ranged_drag(v, 0.05, &range));
                     }
                 });
                 *x = array.into();
@@ -192,19 +582,136 @@ impl App {
                 ui.horizontal(|ui| {
                     for (v, n) in array.iter_mut().zip(names.iter()) {
                         ui.label(*n);
-                        ui.add(DragValue::new(v).speed(0.05));
+                        ui.add(ranged_drag(v, 0.05, &range));
                     }
                 });
                 *x = array.into();
             }
             UniformBinding::Custom(CustomUniform::Float(ref mut x)) => {
-                ui.add(DragValue::new(x).speed(0.05));
+                ranged_scalar(ui, x, 0.05, &range);
+            }
+            UniformBinding::Custom(CustomUniform::Double(ref mut x)) => {
+                ranged_scalar(ui, x, 0.05, &range);
+            }
+            UniformBinding::Custom(CustomUniform::Int(ref mut x)) => {
+                ranged_scalar(ui, x, 0.1, &range);
+            }
+            UniformBinding::Custom(CustomUniform::UInt(ref mut x)) => {
+                ranged_scalar(ui, x, 0.1, &range);
+            }
+            UniformBinding::Custom(CustomUniform::Bool(ref mut x)) => {
+                ui.checkbox(x, "");
             }
             _ => {}
         }
+        if let UniformBinding::Custom(x) = data.binding {
+            let changed = data.default_custom() != Some(x);
+            if ui
+                .add_enabled(changed, egui::Button::new("Reset").small())
+                .on_hover_text("Reset to the value from the config")
+                .clicked()
+            {
+                data.reset();
+            }
+        }
 
         if choice != before {
-            data.binding = choice.into_binding(&data.kind);
+            data.binding = choice.into_binding(data);
         }
     }
 }
+
+/// A drag value for a component of a uniform, clamped to the range of the uniform if it has one.
+fn ranged_drag<'a, N: Numeric>(
+    value: &'a mut N,
+    speed: f64,
+    range: &UniformRange,
+) -> DragValue<'a> {
+    let drag = DragValue::new(value).speed(range.step.map_or(speed, f64::from));
+    if range.min.is_none() && range.max.is_none() {
+        return drag;
+    }
+    drag.clamp_range(
+        range.min.map_or(f64::NEG_INFINITY, f64::from)..=range.max.map_or(f64::INFINITY, f64::from),
+    )
+}
+
+/// Describes the type of a uniform and whether the shader gets a value for it.
+fn uniform_tooltip(name: &str, data: &UniformData) -> String {
+    let mut res = match data.kind.size {
+        Some(size) => format!("{}: {:?}[{}]", name, data.kind.ty, size),
+        None => format!("{}: {:?}", name, data.kind.ty),
+    };
+    // Samplers are bound to textures instead.
+    if LoadedTexture::is_sampler(data.kind.ty) {
+        return res;
+    }
+    if let UniformBinding::Unbound = data.binding {
+        res.push_str("\nUnbound, the shader reads zero or an undefined value.");
+    }
+    if data.kind.size.is_some() || CustomUniform::from_uniform_type(data.kind.ty).is_none() {
+        res.push_str("\nValues of this type can't be edited.");
+    }
+    res
+}
+
+/// Edit a scalar uniform with a slider if both bounds of its range are given.
+fn ranged_scalar<N: Numeric>(ui: &mut Ui, value: &mut N, speed: f64, range: &UniformRange) {
+    match (range.min, range.max) {
+        (Some(min), Some(max)) => {
+            let mut slider = Slider::new(value, N::from_f64(min.into())..=N::from_f64(max.into()));
+            if let Some(step) = range.step {
+                slider = slider.step_by(step.into());
+            }
+            ui.add(slider);
+        }
+        _ => {
+            ui.add(ranged_drag(value, speed, range));
+        }
+    }
+}
+
+/// Find the file an error refers to by looking for an existing path in backticks, together with
+/// the line from an `Error on line` context if there is one.
+fn error_location(error: &str) -> Option<(PathBuf, Option<usize>)> {
+    let path = error
+        .split('`')
+        .skip(1)
+        .step_by(2)
+        .map(Path::new)
+        .find(|x| x.is_file())?;
+    let line = error
+        .split("Error on line ")
+        .nth(1)
+        .and_then(|x| x.split(|c: char| !c.is_ascii_digit()).next())
+        .and_then(|x| x.parse().ok());
+    Some((path.to_path_buf(), line))
+}
+
+/// Open a file in `$EDITOR`, or the default application for the file if it is not set.
+fn open_in_editor(path: &Path, line: Option<usize>) -> Result<()> {
+    let mut command = match env::var("EDITOR") {
+        Ok(editor) if !editor.is_empty() => {
+            let mut args = editor.split_whitespace();
+            let mut command = Command::new(args.next().unwrap());
+            command.args(args);
+            // Most terminal editors accept `+line` to jump to a line.
+            if let Some(line) = line {
+                command.arg(format!("+{}", line));
+            }
+            command
+        }
+        _ if cfg!(windows) => {
+            let mut command = Command::new("cmd");
+            command.args(["/C", "start", ""]);
+            command
+        }
+        _ if cfg!(target_os = "macos") => Command::new("open"),
+        _ => Command::new("xdg-open"),
+    };
+    command
+        .arg(path)
+        .spawn()
+        .with_context(|| format!("failed to open `{}` in an editor", path.display()))?;
+    Ok(())
+}
@@ -0,0 +1,179 @@
+//! A secondary window showing a copy of the rendered frame.
+use crate::{args::Args, geom::Geometry, render::Vertex};
+use anyhow::{Context, Result};
+use glium::{
+    framebuffer::SimpleFrameBuffer,
+    glutin::{
+        self,
+        event_loop::EventLoop,
+        window::{Fullscreen, WindowBuilder, WindowId},
+    },
+    texture::{MipmapsOption, RawImage2d, SrgbFormat, SrgbTexture2d},
+    uniform,
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler},
+    BlitTarget, Display, DrawParameters, Frame, IndexBuffer, Program, Rect, Surface, VertexBuffer,
+};
+
+use super::UserEvent;
+
+const VERTEX_SHADER: &str = r#"
+#version 330
+
+in vec3 position;
+
+out vec2 uv;
+
+void main(){
+    uv = position.xy * 0.5 + 0.5;
+    gl_Position = vec4(position.xy, 0.0, 1.0);
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+#version 330
+
+in vec2 uv;
+
+uniform sampler2D frame;
+
+out vec4 color;
+
+void main(){
+    color = texture(frame, uv);
+}
+"#;
+
+pub struct Mirror {
+    display: Display,
+    program: Program,
+    quad: (VertexBuffer<Vertex>, IndexBuffer<u32>),
+    /// Copy of the frame in the context of the main window.
+    capture: Option<SrgbTexture2d>,
+    /// Copy of the frame in the context of the mirror window.
+    texture: Option<SrgbTexture2d>,
+}
+
+impl Mirror {
+    pub fn new(event_loop: &EventLoop<UserEvent>, args: &Args) -> Result<Self> {
+        let mut window_builder = WindowBuilder::new()
+            .with_resizable(true)
+            .with_title("Shader tool mirror");
+        if let Some(idx) = args.mirror_monitor {
+            let monitor = event_loop
+                .available_monitors()
+                .nth(idx)
+                .with_context(|| format!("Could not find monitor {}", idx))?;
+            window_builder =
+                window_builder.with_fullscreen(Some(Fullscreen::Borderless(Some(monitor))));
+        }
+        let context_builder = glutin::ContextBuilder::new()
+            .with_srgb(true)
+            .with_vsync(false);
+        let display = Display::new(window_builder, context_builder, event_loop)?;
+        let program = Program::from_source(&display, VERTEX_SHADER, FRAGMENT_SHADER, None)
+            .context("failed to compile mirror program")?;
+
+        let quad = Geometry::ScreenQuad.to_buffers(&display)?;
+
+        Ok(Mirror {
+            display,
+            program,
+            quad,
+            capture: None,
+            texture: None,
+        })
+    }
+
+    pub fn window_id(&self) -> WindowId {
+        self.display.gl_window().window().id()
+    }
+
+    /// Copy the frame of the main window and show it in the mirror window.
+    ///
+    /// The frame is scaled to fit the mirror window while keeping its aspect ratio.
+    pub fn present(&mut self, main: &Display, frame: &Frame) -> Result<()> {
+        let (width, height) = frame.get_dimensions();
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        // The two windows have separate contexts so the frame has to go through main memory.
+        let capture = match self.capture {
+            Some(ref x) if x.dimensions() == (width, height) => x,
+            _ => self.capture.insert(
+                SrgbTexture2d::empty_with_format(
+                    main,
+                    SrgbFormat::U8U8U8U8,
+                    MipmapsOption::NoMipmap,
+                    width,
+                    height,
+                )
+                .context("failed to create mirror texture")?,
+            ),
+        };
+        let full = BlitTarget {
+            left: 0,
+            bottom: 0,
+            width: width as i32,
+            height: height as i32,
+        };
+        let capture_target = SimpleFrameBuffer::new(main, capture)
+            .context("could not create mirror frame buffer")?;
+        frame.blit_whole_color_to(&capture_target, &full, MagnifySamplerFilter::Nearest);
+        let image: RawImage2d<u8> = capture.read();
+        let texture = match self.texture {
+            Some(ref x) if x.dimensions() == (width, height) => x,
+            _ => self.texture.insert(
+                SrgbTexture2d::empty_with_format(
+                    &self.display,
+                    SrgbFormat::U8U8U8U8,
+                    MipmapsOption::NoMipmap,
+                    width,
+                    height,
+                )
+                .context("failed to create mirror texture")?,
+            ),
+        };
+        texture.write(
+            Rect {
+                left: 0,
+                bottom: 0,
+                width,
+                height,
+            },
+            image,
+        );
+
+        let mut target = self.display.draw();
+        target.clear_color(0.0, 0.0, 0.0, 1.0);
+        let (target_width, target_height) = target.get_dimensions();
+        let scale = (target_width as f32 / width as f32).min(target_height as f32 / height as f32);
+        let viewport_width = (width as f32 * scale) as u32;
+        let viewport_height = (height as f32 * scale) as u32;
+        let draw_parameters = DrawParameters {
+            viewport: Some(Rect {
+                left: (target_width - viewport_width) / 2,
+                bottom: (target_height - viewport_height) / 2,
+                width: viewport_width,
+                height: viewport_height,
+            }),
+            ..Default::default()
+        };
+        let uniforms = uniform! {
+            frame: Sampler::new(texture)
+                .minify_filter(MinifySamplerFilter::Linear)
+                .magnify_filter(MagnifySamplerFilter::Linear),
+        };
+        let result = target
+            .draw(
+                &self.quad.0,
+                &self.quad.1,
+                &self.program,
+                &uniforms,
+                &draw_parameters,
+            )
+            .context("could not draw mirror");
+        target.finish()?;
+        result
+    }
+}
@@ -1,22 +1,35 @@
-use crate::config::Config;
+use crate::{args::Args, config::Config};
 use anyhow::{Context, Result};
 use egui_glium::EguiGlium;
 use glium::{
+    debug::{DebugCallbackBehavior, MessageType, Severity, Source},
     glutin::{
         self,
-        event::{Event, WindowEvent},
+        event::{Event, StartCause, WindowEvent},
         event_loop::{ControlFlow, EventLoop, EventLoopProxy},
         window::WindowBuilder,
     },
-    Display,
+    texture::RawImage2d,
+    CapabilitiesSource, Display, Frame,
 };
+use image::{ImageFormat, RgbaImage};
 use notify::{
     event::{AccessKind, AccessMode, Event as NotifyEvent},
     EventKind, RecommendedWatcher, Result as NotifyResult, Watcher,
 };
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
+pub mod benchmark;
+pub mod contact_sheet;
+mod font;
+mod gizmo;
+pub mod graph;
 mod gui;
+mod mirror;
+pub mod reflect;
 
 #[derive(Debug)]
 pub enum UserEvent {
@@ -28,6 +41,15 @@ pub enum ConfigKind {
     Json,
 }
 
+impl ConfigKind {
+    pub fn path(&self) -> &'static str {
+        match *self {
+            ConfigKind::Ron => "./ShaderTool.ron",
+            ConfigKind::Json => "./ShaderTool.json",
+        }
+    }
+}
+
 pub enum State {
     /// No config could be found
     NotLoaded { error: String },
@@ -69,6 +91,16 @@ impl State {
         }
     }
 
+    /// Returns the path of the file the active config was loaded from.
+    pub fn config_path(&self) -> Option<&'static str> {
+        match *self {
+            State::Loaded { ref kind, .. }
+            | State::FirstFrame { ref kind, .. }
+            | State::ReloadError { ref kind, .. } => Some(kind.path()),
+            _ => None,
+        }
+    }
+
     pub fn take(&mut self) -> Self {
         std::mem::replace(
             self,
@@ -86,18 +118,44 @@ pub struct App {
     _watcher: RecommendedWatcher,
     should_run: bool,
     gui: gui::Gui,
+    /// The last time input was received or the config was reloaded.
+    last_activity: Instant,
+    idle_timeout: Option<Duration>,
+    idle_frame_time: Duration,
+    /// Path to write a screenshot to once the config renders successfully.
+    thumbnail: Option<PathBuf>,
+    thumbnail_exit: bool,
+    /// The minimum amount of time between two reloads.
+    min_reload_interval: Duration,
+    last_reload: Option<Instant>,
+    /// Files which changed but were not yet reloaded.
+    pending_reload: Vec<PathBuf>,
+    /// Files written by the app itself, like recordings and thumbnails, which are not reloaded
+    /// when they change.
+    written_files: Vec<PathBuf>,
+    mirror: Option<mirror::Mirror>,
 }
 
 impl App {
-    pub fn new(event_loop: &EventLoop<UserEvent>) -> Result<Self> {
+    pub fn new(event_loop: &EventLoop<UserEvent>, args: &Args) -> Result<Self> {
         // Load the config file
 
         // Create display and setup egui
-        let display = Self::create_display(event_loop).context("failed to create an window")?;
+        let display =
+            Self::create_display(event_loop, args).context("failed to create an window")?;
         let egui = EguiGlium::new(&display);
         let _watcher = Self::create_watcher(event_loop.create_proxy())
             .context("could not create a file watcher")?;
 
+        let mirror = if args.mirror {
+            Some(
+                mirror::Mirror::new(event_loop, args)
+                    .context("failed to create the mirror window")?,
+            )
+        } else {
+            None
+        };
+
         let state = Self::initial_load_config(&display);
 
         Ok(App {
@@ -107,6 +165,16 @@ impl App {
             state,
             should_run: true,
             gui: gui::Gui::new(),
+            last_activity: Instant::now(),
+            idle_timeout: (args.idle_timeout > 0).then(|| Duration::from_secs(args.idle_timeout)),
+            idle_frame_time: Duration::from_secs(1) / args.idle_fps,
+            thumbnail: args.thumbnail.clone(),
+            thumbnail_exit: args.thumbnail_exit,
+            min_reload_interval: Duration::from_millis(args.min_reload_interval),
+            last_reload: None,
+            pending_reload: Vec::new(),
+            written_files: Vec::new(),
+            mirror,
         })
     }
 
@@ -119,7 +187,7 @@ impl App {
             );
         }
         if ron_exists {
-            match Config::load("./ShaderTool.ron", &display) {
+            match Config::load("./ShaderTool.ron", &display, None) {
                 Ok(x) => State::FirstFrame {
                     old_config: None,
                     config: Box::new(x),
@@ -130,7 +198,7 @@ impl App {
                 },
             }
         } else if json_exists {
-            match Config::load("./ShaderTool.json", &display) {
+            match Config::load("./ShaderTool.json", &display, None) {
                 Ok(x) => State::FirstFrame {
                     old_config: None,
                     config: Box::new(x),
@@ -148,18 +216,87 @@ impl App {
         }
     }
 
-    fn create_display(event_loop: &EventLoop<UserEvent>) -> Result<Display> {
+    /// Create a hidden display and load the config for the modes which exit without running the
+    /// app.
+    fn load_headless(
+        event_loop: &EventLoop<UserEvent>,
+        args: &Args,
+    ) -> Result<(Display, Box<Config>)> {
+        let display =
+            Self::create_display(event_loop, args).context("failed to create an window")?;
+        match Self::initial_load_config(&display) {
+            State::FirstFrame { config, .. } => Ok((display, config)),
+            State::NotLoaded { error } => bail!("Failed to load config: {}", error),
+            _ => unreachable!(),
+        }
+    }
+
+    fn create_display(event_loop: &EventLoop<UserEvent>, args: &Args) -> Result<Display> {
         let window_builder = WindowBuilder::new()
+            .with_visible(
+                !args.benchmark
+                    && !args.reflect
+                    && !args.render_graph
+                    && args.contact_sheet.is_none(),
+            )
             .with_resizable(true)
             .with_title("Shader tool");
 
         let context_builder = glutin::ContextBuilder::new()
-            .with_depth_buffer(8)
+            .with_depth_buffer(args.depth_bits)
             .with_srgb(true)
-            .with_stencil_buffer(0)
-            .with_vsync(true);
+            .with_stencil_buffer(args.stencil_bits)
+            .with_vsync(true)
+            .with_gl_debug_flag(args.gl_debug);
+
+        let display = if args.gl_debug {
+            let gl_window = context_builder.build_windowed(window_builder, event_loop)?;
+            let behavior = DebugCallbackBehavior::Custom {
+                callback: Box::new(Self::log_gl_message),
+                synchronous: true,
+            };
+            Display::with_debug(gl_window, behavior)?
+        } else {
+            Display::new(window_builder, context_builder, event_loop)?
+        };
+
+        // The context is allowed to give us different buffers than requested so check what we
+        // actually got.
+        let capabilities = display.get_capabilities();
+        let depth_bits = capabilities.depth_bits.unwrap_or(0);
+        if depth_bits != args.depth_bits as u16 {
+            warn!(
+                "Requested a {} bit depth buffer but the context provided {} bits",
+                args.depth_bits, depth_bits
+            );
+        }
+        let stencil_bits = capabilities.stencil_bits.unwrap_or(0);
+        if stencil_bits != args.stencil_bits as u16 {
+            warn!(
+                "Requested a {} bit stencil buffer but the context provided {} bits",
+                args.stencil_bits, stencil_bits
+            );
+        }
+
+        Ok(display)
+    }
 
-        Ok(Display::new(window_builder, context_builder, event_loop)?)
+    /// Write a message from the debug context to the log at a level matching its severity.
+    fn log_gl_message(
+        source: Source,
+        ty: MessageType,
+        severity: Severity,
+        id: u32,
+        _report_debug_output_errors: bool,
+        message: &str,
+    ) {
+        let level = match severity {
+            Severity::High => log::Level::Error,
+            Severity::Medium => log::Level::Warn,
+            Severity::Low => log::Level::Info,
+            Severity::Notification => log::Level::Debug,
+        };
+        log!(level, "GL {:?} {:?} ({}): {}", source, ty, id, message);
     }
 
     fn create_watcher(proxy: EventLoopProxy<UserEvent>) -> Result<RecommendedWatcher> {
@@ -179,14 +316,55 @@ impl App {
         Ok(watcher)
     }
 
+    /// Stop changes to a file written by the app from reloading the config.
+    ///
+    /// The path is stored canonicalized like the paths of file change events.
+    fn ignore_changes(written_files: &mut Vec<PathBuf>, path: &Path) {
+        if let Ok(x) = path.canonicalize() {
+            if !written_files.contains(&x) {
+                written_files.push(x);
+            }
+        }
+    }
+
+    /// Read back the last presented frame and write it to a png file.
+    pub fn save_screenshot(display: &Display, path: &Path) -> Result<()> {
+        let image: RawImage2d<u8> = display
+            .read_front_buffer()
+            .context("failed to read back frame")?;
+        let image = RgbaImage::from_raw(image.width, image.height, image.data.into_owned())
+            .context("frame has an invalid size")?;
+        // OpenGL images start at the bottom row.
+        image::imageops::flip_vertical(&image)
+            .save_with_format(path, ImageFormat::Png)
+            .with_context(|| format!("failed to write screenshot to `{}`", path.display()))
+    }
+
+    /// Returns whether no activity happened for longer than the idle timeout.
+    fn is_idle(&self) -> bool {
+        self.idle_timeout
+            .map(|x| self.last_activity.elapsed() > x)
+            .unwrap_or(false)
+    }
+
     fn redraw(&mut self, control_flow: &mut ControlFlow) {
-        let mut needs_repaint = self.draw_gui();
+        let needs_repaint = self.draw_gui();
+        let mut should_poll = false;
 
         {
             use glium::Surface as _;
             let mut target = self.display.draw();
 
-            let clear_color = egui::Rgba::from_rgb(0.1, 0.3, 0.2);
+            let clear_color = self
+                .state
+                .active_config()
+                .map(Config::clear_color)
+                .unwrap_or_else(|| egui::Rgba::from_rgb(0.1, 0.3, 0.2).to_array());
+            let clear_depth = self
+                .state
+                .active_config()
+                .map(Config::clear_depth)
+                .unwrap_or(1.0);
             target.clear_color_and_depth(
                 (
                     clear_color[0],
@@ -194,14 +372,15 @@ impl App {
                     clear_color[2],
                     clear_color[3],
                 ),
-                1.0,
+                clear_depth,
             );
 
             match self.state {
                 State::Loaded { ref config, .. } | State::ReloadError { ref config, .. } => {
                     // Unwrap because at this point we verified that the current config should run
                     // without problem.
-                    needs_repaint |= config.render(&mut target).unwrap();
+                    should_poll = config.render(&mut target).unwrap();
+                    self.present_mirror(&target);
                     self.egui.paint(&self.display, &mut target);
                     target.finish().unwrap()
                 }
@@ -215,14 +394,31 @@ impl App {
                         kind,
                     } = self.state.take()
                     {
+                        // Leave the gui out of the frame used as a thumbnail.
+                        let capture = self.thumbnail.is_some();
                         match config.render(&mut target).and_then(|x| {
-                            self.egui.paint(&self.display, &mut target);
+                            self.present_mirror(&target);
+                            if !capture {
+                                self.egui.paint(&self.display, &mut target);
+                            }
                             target.finish()?;
                             Ok(x)
                         }) {
-                            Ok(should_poll) => {
+                            Ok(x) => {
                                 self.state = State::Loaded { config, kind };
-                                needs_repaint |= should_poll;
+                                should_poll = x;
+                                if let Some(path) = self.thumbnail.take() {
+                                    match Self::save_screenshot(&self.display, &path) {
+                                        Ok(()) => {
+                                            info!("wrote thumbnail to `{}`", path.display());
+                                            Self::ignore_changes(&mut self.written_files, &path);
+                                        }
+                                        Err(e) => error!("{:?}", e),
+                                    }
+                                    if self.thumbnail_exit {
+                                        self.should_run = false;
+                                    }
+                                }
                             }
                             Err(e) => {
                                 *control_flow = glutin::event_loop::ControlFlow::Poll;
@@ -253,12 +449,26 @@ impl App {
 
         *control_flow = if !self.should_run {
             glutin::event_loop::ControlFlow::Exit
-        } else if needs_repaint {
+        } else if needs_repaint || (should_poll && !self.is_idle()) {
             self.display.gl_window().window().request_redraw();
             glutin::event_loop::ControlFlow::Poll
+        } else if should_poll {
+            // Keep animating at a lower rate to save power while nobody is interacting.
+            glutin::event_loop::ControlFlow::WaitUntil(Instant::now() + self.idle_frame_time)
         } else {
             glutin::event_loop::ControlFlow::Wait
         };
+        self.wait_for_reload(control_flow);
+    }
+
+    /// Show the frame, without the gui, in the mirror window if there is one.
+    fn present_mirror(&mut self, frame: &Frame) {
+        if let Some(ref mut mirror) = self.mirror {
+            if let Err(e) = mirror.present(&self.display, frame) {
+                error!("{:?}", e);
+                self.mirror = None;
+            }
+        }
     }
 
     pub fn handle_event(&mut self, event: Event<UserEvent>, control_flow: &mut ControlFlow) {
@@ -269,11 +479,32 @@ impl App {
             Event::RedrawEventsCleared if cfg!(windows) => {
                 self.redraw(control_flow);
             }
-            Event::RedrawRequested(_) if !cfg!(windows) => {
+            // The mirror window is drawn together with the main window.
+            Event::RedrawRequested(id)
+                if !cfg!(windows) && self.display.gl_window().window().id() == id =>
+            {
                 self.redraw(control_flow);
             }
 
-            Event::WindowEvent { event, .. } => {
+            Event::NewEvents(cause) => {
+                if let Some(deadline) = self.reload_deadline() {
+                    if deadline <= Instant::now() {
+                        self.reload(control_flow);
+                    }
+                }
+                if let StartCause::ResumeTimeReached { .. } = cause {
+                    self.display.gl_window().window().request_redraw();
+                }
+            }
+
+            Event::WindowEvent { event, window_id } => {
+                if self.mirror.as_ref().map(|x| x.window_id()) == Some(window_id) {
+                    if event == WindowEvent::CloseRequested {
+                        self.mirror = None;
+                    }
+                    return;
+                }
+                self.last_activity = Instant::now();
                 if !self.egui.on_event(&event) {
                     if let Some(x) = self.state.active_config_mut() {
                         x.handle_window_event(&event)
@@ -289,91 +520,144 @@ impl App {
                 self.display.gl_window().window().request_redraw(); // TODO: ask egui if the events warrants a repaint instead
             }
             Event::DeviceEvent { event, .. } => {
+                self.last_activity = Instant::now();
                 if let Some(x) = self.state.active_config_mut() {
                     x.handle_device_event(&event)
                 }
             }
-            Event::UserEvent(UserEvent::FileChanged(_)) => {
-                match self.state {
-                    State::NotLoaded { .. } => {
-                        self.state = Self::initial_load_config(&self.display);
+            Event::UserEvent(UserEvent::FileChanged(path)) => {
+                if self.written_files.contains(&path) {
+                    return;
+                }
+                self.last_activity = Instant::now();
+                if !self.pending_reload.contains(&path) {
+                    self.pending_reload.push(path);
+                }
+                // Bound the amount of reloads, changes arriving too soon are applied together
+                // once the interval has passed.
+                match self.reload_deadline() {
+                    Some(deadline) if deadline > Instant::now() => {
+                        self.wait_for_reload(control_flow);
                     }
-                    State::FirstFrame { .. } => {
-                        if let State::FirstFrame {
-                            old_config, kind, ..
-                        } = self.state.take()
-                        {
-                            let new_config = match kind {
-                                ConfigKind::Ron => Config::load("./ShaderTool.ron", &self.display),
-                                ConfigKind::Json => {
-                                    Config::load("./ShaderTool.json", &self.display)
-                                }
-                            };
-                            match new_config {
-                                Ok(mut x) => {
-                                    old_config.as_deref().map(|old| {
-                                        x.copy_state(old);
-                                    });
-                                    self.state = State::FirstFrame {
-                                        old_config,
-                                        config: Box::new(x),
-                                        kind,
-                                    }
+                    _ => self.reload(control_flow),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns when the pending file changes can be applied.
+    fn reload_deadline(&self) -> Option<Instant> {
+        if self.pending_reload.is_empty() {
+            return None;
+        }
+        Some(
+            self.last_reload
+                .map(|x| x + self.min_reload_interval)
+                .unwrap_or_else(Instant::now),
+        )
+    }
+
+    /// Make sure the event loop wakes up in time to apply pending file changes.
+    fn wait_for_reload(&self, control_flow: &mut ControlFlow) {
+        if let Some(deadline) = self.reload_deadline() {
+            *control_flow = match *control_flow {
+                ControlFlow::Wait => ControlFlow::WaitUntil(deadline),
+                ControlFlow::WaitUntil(x) => ControlFlow::WaitUntil(x.min(deadline)),
+                x => x,
+            };
+        }
+    }
+
+    /// Apply all pending file changes.
+    fn reload(&mut self, control_flow: &mut ControlFlow) {
+        let paths = std::mem::take(&mut self.pending_reload);
+        self.last_reload = Some(Instant::now());
+
+        // Changes to only the camera can be applied without reloading everything.
+        if let State::Loaded {
+            ref mut config,
+            ref kind,
+        } = self.state
+        {
+            let is_config = Path::new(kind.path())
+                .canonicalize()
+                .map(|x| paths.iter().all(|path| *path == x))
+                .unwrap_or(false);
+            if is_config && config.reload_camera(kind.path()).unwrap_or(false) {
+                self.display.gl_window().window().request_redraw();
+                return;
+            }
+        }
+
+        match self.state {
+            State::NotLoaded { .. } => {
+                self.state = Self::initial_load_config(&self.display);
+            }
+            State::FirstFrame { .. } => {
+                if let State::FirstFrame {
+                    old_config, kind, ..
+                } = self.state.take()
+                {
+                    let new_config =
+                        Config::load(kind.path(), &self.display, old_config.as_deref());
+                    match new_config {
+                        Ok(mut x) => {
+                            old_config.as_deref().map(|old| {
+                                x.copy_state(old);
+                            });
+                            self.state = State::FirstFrame {
+                                old_config,
+                                config: Box::new(x),
+                                kind,
+                            }
+                        }
+                        Err(e) => {
+                            if let Some(config) = old_config {
+                                self.state = State::ReloadError {
+                                    config,
+                                    kind,
+                                    error: format!("{:?}", e),
                                 }
-                                Err(e) => {
-                                    if let Some(config) = old_config {
-                                        self.state = State::ReloadError {
-                                            config,
-                                            kind,
-                                            error: format!("{:?}", e),
-                                        }
-                                    } else {
-                                        self.state = State::NotLoaded {
-                                            error: format!("{:?}", e),
-                                        }
-                                    }
+                            } else {
+                                self.state = State::NotLoaded {
+                                    error: format!("{:?}", e),
                                 }
                             }
-                        } else {
-                            unreachable!()
                         }
                     }
-                    State::ReloadError { .. } | State::Loaded { .. } => {
-                        if let State::ReloadError { config, kind, .. }
-                        | State::Loaded { config, kind } = self.state.take()
-                        {
-                            let new_config = match kind {
-                                ConfigKind::Ron => Config::load("./ShaderTool.ron", &self.display),
-                                ConfigKind::Json => {
-                                    Config::load("./ShaderTool.json", &self.display)
-                                }
-                            };
-                            match new_config {
-                                Ok(mut x) => {
-                                    x.copy_state(&*config);
-                                    self.state = State::FirstFrame {
-                                        old_config: Some(config),
-                                        config: Box::new(x),
-                                        kind,
-                                    }
-                                }
-                                Err(e) => {
-                                    self.state = State::ReloadError {
-                                        config,
-                                        kind,
-                                        error: format!("{:?}", e),
-                                    }
-                                }
+                } else {
+                    unreachable!()
+                }
+            }
+            State::ReloadError { .. } | State::Loaded { .. } => {
+                if let State::ReloadError { config, kind, .. } | State::Loaded { config, kind } =
+                    self.state.take()
+                {
+                    let new_config = Config::load(kind.path(), &self.display, Some(&*config));
+                    match new_config {
+                        Ok(mut x) => {
+                            x.copy_state(&*config);
+                            self.state = State::FirstFrame {
+                                old_config: Some(config),
+                                config: Box::new(x),
+                                kind,
+                            }
+                        }
+                        Err(e) => {
+                            self.state = State::ReloadError {
+                                config,
+                                kind,
+                                error: format!("{:?}", e),
                             }
-                        } else {
-                            unreachable!()
                         }
                     }
+                } else {
+                    unreachable!()
                 }
-                *control_flow = glutin::event_loop::ControlFlow::Poll;
-                self.display.gl_window().window().request_redraw();
             }
-            _ => {}
         }
+        *control_flow = glutin::event_loop::ControlFlow::Poll;
+        self.display.gl_window().window().request_redraw();
     }
 }
@@ -0,0 +1,278 @@
+//! Prints the interface of every pass in the config as JSON for use by other tools.
+use super::{App, UserEvent};
+use crate::{
+    args::Args,
+    config::{CustomUniform, LoadedPass, LoadedTexture, UniformBinding},
+};
+use anyhow::Result;
+use glium::{glutin::event_loop::EventLoop, uniforms::UniformType, vertex::AttributeType};
+use serde_json::{json, Value};
+
+fn custom_value(value: &CustomUniform) -> Value {
+    match *value {
+        CustomUniform::Mat4(x) => json!(x.to_cols_array()),
+        CustomUniform::Vec4(x) => json!(<[f32; 4]>::from(x)),
+        CustomUniform::Vec3(x) => json!(<[f32; 3]>::from(x)),
+        CustomUniform::Vec2(x) => json!([x.x, x.y]),
+        CustomUniform::Int(x) => json!(x),
+        CustomUniform::UInt(x) => json!(x),
+        CustomUniform::Double(x) => json!(x),
+        CustomUniform::Float(x) => json!(x),
+        CustomUniform::Bool(x) => json!(x),
+    }
+}
+
+/// Returns the GLSL name of a uniform type.
+fn uniform_type_name(ty: UniformType) -> &'static str {
+    match ty {
+        UniformType::Float => "float",
+        UniformType::FloatVec2 => "vec2",
+        UniformType::FloatVec3 => "vec3",
+        UniformType::FloatVec4 => "vec4",
+        UniformType::Double => "double",
+        UniformType::DoubleVec2 => "dvec2",
+        UniformType::DoubleVec3 => "dvec3",
+        UniformType::DoubleVec4 => "dvec4",
+        UniformType::Int => "int",
+        UniformType::IntVec2 => "ivec2",
+        UniformType::IntVec3 => "ivec3",
+        UniformType::IntVec4 => "ivec4",
+        UniformType::UnsignedInt => "uint",
+        UniformType::UnsignedIntVec2 => "uvec2",
+        UniformType::UnsignedIntVec3 => "uvec3",
+        UniformType::UnsignedIntVec4 => "uvec4",
+        UniformType::Int64 => "int64_t",
+        UniformType::Int64Vec2 => "i64vec2",
+        UniformType::Int64Vec3 => "i64vec3",
+        UniformType::Int64Vec4 => "i64vec4",
+        UniformType::UnsignedInt64 => "uint64_t",
+        UniformType::UnsignedInt64Vec2 => "u64vec2",
+        UniformType::UnsignedInt64Vec3 => "u64vec3",
+        UniformType::UnsignedInt64Vec4 => "u64vec4",
+        UniformType::Bool => "bool",
+        UniformType::BoolVec2 => "bvec2",
+        UniformType::BoolVec3 => "bvec3",
+        UniformType::BoolVec4 => "bvec4",
+        UniformType::FloatMat2 => "mat2",
+        UniformType::FloatMat3 => "mat3",
+        UniformType::FloatMat4 => "mat4",
+        UniformType::FloatMat2x3 => "mat2x3",
+        UniformType::FloatMat2x4 => "mat2x4",
+        UniformType::FloatMat3x2 => "mat3x2",
+        UniformType::FloatMat3x4 => "mat3x4",
+        UniformType::FloatMat4x2 => "mat4x2",
+        UniformType::FloatMat4x3 => "mat4x3",
+        UniformType::DoubleMat2 => "dmat2",
+        UniformType::DoubleMat3 => "dmat3",
+        UniformType::DoubleMat4 => "dmat4",
+        UniformType::DoubleMat2x3 => "dmat2x3",
+        UniformType::DoubleMat2x4 => "dmat2x4",
+        UniformType::DoubleMat3x2 => "dmat3x2",
+        UniformType::DoubleMat3x4 => "dmat3x4",
+        UniformType::DoubleMat4x2 => "dmat4x2",
+        UniformType::DoubleMat4x3 => "dmat4x3",
+        UniformType::Sampler1d => "sampler1D",
+        UniformType::ISampler1d => "isampler1D",
+        UniformType::USampler1d => "usampler1D",
+        UniformType::Sampler2d => "sampler2D",
+        UniformType::ISampler2d => "isampler2D",
+        UniformType::USampler2d => "usampler2D",
+        UniformType::Sampler3d => "sampler3D",
+        UniformType::ISampler3d => "isampler3D",
+        UniformType::USampler3d => "usampler3D",
+        UniformType::Sampler1dArray => "sampler1DArray",
+        UniformType::ISampler1dArray => "isampler1DArray",
+        UniformType::USampler1dArray => "usampler1DArray",
+        UniformType::Sampler2dArray => "sampler2DArray",
+        UniformType::ISampler2dArray => "isampler2DArray",
+        UniformType::USampler2dArray => "usampler2DArray",
+        UniformType::SamplerCube => "samplerCube",
+        UniformType::ISamplerCube => "isamplerCube",
+        UniformType::USamplerCube => "usamplerCube",
+        UniformType::Sampler2dRect => "sampler2DRect",
+        UniformType::ISampler2dRect => "isampler2DRect",
+        UniformType::USampler2dRect => "usampler2DRect",
+        UniformType::Sampler2dRectShadow => "sampler2DRectShadow",
+        UniformType::SamplerCubeArray => "samplerCubeArray",
+        UniformType::ISamplerCubeArray => "isamplerCubeArray",
+        UniformType::USamplerCubeArray => "usamplerCubeArray",
+        UniformType::SamplerBuffer => "samplerBuffer",
+        UniformType::ISamplerBuffer => "isamplerBuffer",
+        UniformType::USamplerBuffer => "usamplerBuffer",
+        UniformType::Sampler2dMultisample => "sampler2DMS",
+        UniformType::ISampler2dMultisample => "isampler2DMS",
+        UniformType::USampler2dMultisample => "usampler2DMS",
+        UniformType::Sampler2dMultisampleArray => "sampler2DMSArray",
+        UniformType::ISampler2dMultisampleArray => "isampler2DMSArray",
+        UniformType::USampler2dMultisampleArray => "usampler2DMSArray",
+        UniformType::Sampler1dShadow => "sampler1DShadow",
+        UniformType::Sampler2dShadow => "sampler2DShadow",
+        UniformType::SamplerCubeShadow => "samplerCubeShadow",
+        UniformType::Sampler1dArrayShadow => "sampler1DArrayShadow",
+        UniformType::Sampler2dArrayShadow => "sampler2DArrayShadow",
+        UniformType::SamplerCubeArrayShadow => "samplerCubeArrayShadow",
+        UniformType::Image1d => "image1D",
+        UniformType::IImage1d => "iimage1D",
+        UniformType::UImage1d => "uimage1D",
+        UniformType::Image2d => "image2D",
+        UniformType::IImage2d => "iimage2D",
+        UniformType::UImage2d => "uimage2D",
+        UniformType::Image3d => "image3D",
+        UniformType::IImage3d => "iimage3D",
+        UniformType::UImage3d => "uimage3D",
+        UniformType::Image2dRect => "image2DRect",
+        UniformType::IImage2dRect => "iimage2DRect",
+        UniformType::UImage2dRect => "uimage2DRect",
+        UniformType::ImageCube => "imageCube",
+        UniformType::IImageCube => "iimageCube",
+        UniformType::UImageCube => "uimageCube",
+        UniformType::ImageBuffer => "imageBuffer",
+        UniformType::IImageBuffer => "iimageBuffer",
+        UniformType::UImageBuffer => "uimageBuffer",
+        UniformType::Image1dArray => "image1DArray",
+        UniformType::IImage1dArray => "iimage1DArray",
+        UniformType::UImage1dArray => "uimage1DArray",
+        UniformType::Image2dArray => "image2DArray",
+        UniformType::IImage2dArray => "iimage2DArray",
+        UniformType::UImage2dArray => "uimage2DArray",
+        UniformType::Image2dMultisample => "image2DMS",
+        UniformType::IImage2dMultisample => "iimage2DMS",
+        UniformType::UImage2dMultisample => "uimage2DMS",
+        UniformType::Image2dMultisampleArray => "image2DMSArray",
+        UniformType::IImage2dMultisampleArray => "iimage2DMSArray",
+        UniformType::UImage2dMultisampleArray => "uimage2DMSArray",
+        UniformType::AtomicCounterUint => "atomic_uint",
+    }
+}
+
+/// Returns the GLSL name of an attribute type.
+fn attribute_type_name(ty: AttributeType) -> &'static str {
+    match ty {
+        AttributeType::I32 => "int",
+        AttributeType::I32I32 => "ivec2",
+        AttributeType::I32I32I32 => "ivec3",
+        AttributeType::I32I32I32I32 => "ivec4",
+        AttributeType::U32 => "uint",
+        AttributeType::U32U32 => "uvec2",
+        AttributeType::U32U32U32 => "uvec3",
+        AttributeType::U32U32U32U32 => "uvec4",
+        AttributeType::I64 => "int64_t",
+        AttributeType::I64I64 => "i64vec2",
+        AttributeType::I64I64I64 => "i64vec3",
+        AttributeType::I64I64I64I64 => "i64vec4",
+        AttributeType::U64 => "uint64_t",
+        AttributeType::U64U64 => "u64vec2",
+        AttributeType::U64U64U64 => "u64vec3",
+        AttributeType::U64U64U64U64 => "u64vec4",
+        AttributeType::F32 => "float",
+        AttributeType::F32F32 => "vec2",
+        AttributeType::F32F32F32 => "vec3",
+        AttributeType::F32F32F32F32 => "vec4",
+        AttributeType::F32x2x2 => "mat2",
+        AttributeType::F32x2x3 => "mat2x3",
+        AttributeType::F32x2x4 => "mat2x4",
+        AttributeType::F32x3x2 => "mat3x2",
+        AttributeType::F32x3x3 => "mat3",
+        AttributeType::F32x3x4 => "mat3x4",
+        AttributeType::F32x4x2 => "mat4x2",
+        AttributeType::F32x4x3 => "mat4x3",
+        AttributeType::F32x4x4 => "mat4",
+        AttributeType::F64 => "double",
+        AttributeType::F64F64 => "dvec2",
+        AttributeType::F64F64F64 => "dvec3",
+        AttributeType::F64F64F64F64 => "dvec4",
+        AttributeType::F64x2x2 => "dmat2",
+        AttributeType::F64x2x3 => "dmat2x3",
+        AttributeType::F64x2x4 => "dmat2x4",
+        AttributeType::F64x3x2 => "dmat3x2",
+        AttributeType::F64x3x3 => "dmat3",
+        AttributeType::F64x3x4 => "dmat3x4",
+        AttributeType::F64x4x2 => "dmat4x2",
+        AttributeType::F64x4x3 => "dmat4x3",
+        AttributeType::F64x4x4 => "dmat4",
+        // Vertex formats which can't be declared as an attribute in GLSL.
+        _ => "unknown",
+    }
+}
+
+fn binding(binding: &UniformBinding, textures: &[LoadedTexture]) -> Value {
+    match binding {
+        UniformBinding::Builtin(x) => json!({
+            "kind": "builtin",
+            "builtin": x.name(),
+        }),
+        UniformBinding::Custom(x) => json!({
+            "kind": "custom",
+            "value": custom_value(x),
+        }),
+        UniformBinding::Texture(x) => json!({
+            "kind": "texture",
+            "texture": textures[*x].config.name,
+        }),
+        UniformBinding::Unbound => json!({ "kind": "unbound" }),
+    }
+}
+
+/// Entries are sorted by name so the output is stable between runs.
+fn reflect_pass(idx: usize, pass: &LoadedPass, textures: &[LoadedTexture]) -> Value {
+    let mut uniforms: Vec<_> = pass.uniforms.iter().collect();
+    uniforms.sort_by(|a, b| a.0.cmp(b.0));
+    let uniforms: Vec<_> = uniforms
+        .into_iter()
+        .map(|(name, data)| {
+            json!({
+                "name": name,
+                "type": uniform_type_name(data.kind.ty),
+                "size": data.kind.size,
+                "binding": binding(&data.binding, textures),
+            })
+        })
+        .collect();
+
+    let mut attributes: Vec<_> = pass.program.attributes().collect();
+    attributes.sort_by(|a, b| a.0.cmp(b.0));
+    let attributes: Vec<_> = attributes
+        .into_iter()
+        .map(|(name, attribute)| {
+            json!({
+                "name": name,
+                "type": attribute_type_name(attribute.ty),
+                "size": attribute.size,
+            })
+        })
+        .collect();
+
+    let mut outputs = pass.fragment.outputs();
+    outputs.sort_by(|a, b| a.1.cmp(b.1));
+    let outputs: Vec<_> = outputs
+        .into_iter()
+        .map(|(ty, name)| {
+            json!({
+                "name": name,
+                "type": ty,
+                "location": pass.program.get_frag_data_location(name),
+            })
+        })
+        .collect();
+
+    json!({
+        "pass": idx,
+        "uniforms": uniforms,
+        "attributes": attributes,
+        "outputs": outputs,
+    })
+}
+
+pub fn run(event_loop: &EventLoop<UserEvent>, args: &Args) -> Result<()> {
+    let (_display, config) = App::load_headless(event_loop, args)?;
+
+    let passes: Vec<_> = config
+        .passes
+        .iter()
+        .enumerate()
+        .map(|(idx, pass)| reflect_pass(idx, pass, &config.textures))
+        .collect();
+    let report = json!({ "passes": passes });
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
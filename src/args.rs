@@ -0,0 +1,117 @@
+//! Command line argument parsing.
+use anyhow::{Context, Result};
+use std::{env, error::Error, path::PathBuf, str::FromStr};
+
+#[derive(Debug)]
+pub struct Args {
+    /// Amount of bits requested for the default frame depth buffer.
+    pub depth_bits: u8,
+    /// Amount of bits requested for the default frame stencil buffer.
+    pub stencil_bits: u8,
+    /// Directory of a Shadertoy project to convert into a config.
+    pub import_shadertoy: Option<PathBuf>,
+    /// Render the config without showing a window and print timing information.
+    pub benchmark: bool,
+    /// The amount of frames rendered when benchmarking.
+    pub frames: u32,
+    /// Print the uniforms, attributes and outputs of every pass as JSON and exit.
+    pub reflect: bool,
+    /// Print which passes read and write which textures as a Graphviz graph and exit.
+    pub render_graph: bool,
+    /// File to write the render graph to instead of printing it.
+    pub render_graph_output: Option<PathBuf>,
+    /// Render a single frame and write the outputs of all passes tiled into one image.
+    pub contact_sheet: Option<PathBuf>,
+    /// Seconds without input after which animated configs are rendered at `idle_fps` to save
+    /// power. Zero, the default, keeps animated configs at full rate.
+    pub idle_timeout: u64,
+    /// The frame rate used for animated configs while idle.
+    pub idle_fps: u32,
+    /// Path to write a screenshot to after the first successfully rendered frame.
+    pub thumbnail: Option<PathBuf>,
+    /// Exit after the thumbnail has been written.
+    pub thumbnail_exit: bool,
+    /// Minimum amount of milliseconds between two reloads of the config.
+    pub min_reload_interval: u64,
+    /// Request a debug context and write the messages of the driver to the log.
+    pub gl_debug: bool,
+    /// Open a second window which shows a copy of the rendered frame.
+    pub mirror: bool,
+    /// Index of the monitor to show the mirror window fullscreen on.
+    pub mirror_monitor: Option<usize>,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Args {
+            depth_bits: 24,
+            stencil_bits: 8,
+            import_shadertoy: None,
+            benchmark: false,
+            frames: 100,
+            reflect: false,
+            render_graph: false,
+            render_graph_output: None,
+            contact_sheet: None,
+            idle_timeout: 0,
+            idle_fps: 5,
+            thumbnail: None,
+            thumbnail_exit: false,
+            min_reload_interval: 250,
+            gl_debug: false,
+            mirror: false,
+            mirror_monitor: None,
+        }
+    }
+}
+
+impl Args {
+    pub fn parse() -> Result<Self> {
+        let mut res = Args::default();
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--depth-bits" => res.depth_bits = Self::value(&mut args, &arg)?,
+                "--stencil-bits" => res.stencil_bits = Self::value(&mut args, &arg)?,
+                "--import-shadertoy" => res.import_shadertoy = Some(Self::value(&mut args, &arg)?),
+                "--benchmark" => res.benchmark = true,
+                "--frames" => res.frames = Self::value(&mut args, &arg)?,
+                "--reflect" => res.reflect = true,
+                "--render-graph" => res.render_graph = true,
+                "--render-graph-output" => {
+                    res.render_graph = true;
+                    res.render_graph_output = Some(Self::value(&mut args, &arg)?);
+                }
+                "--contact-sheet" => res.contact_sheet = Some(Self::value(&mut args, &arg)?),
+                "--idle-timeout" => res.idle_timeout = Self::value(&mut args, &arg)?,
+                "--idle-fps" => res.idle_fps = Self::value(&mut args, &arg)?,
+                "--thumbnail" => res.thumbnail = Some(Self::value(&mut args, &arg)?),
+                "--thumbnail-exit" => res.thumbnail_exit = true,
+                "--min-reload-interval" => res.min_reload_interval = Self::value(&mut args, &arg)?,
+                "--gl-debug" => res.gl_debug = true,
+                "--mirror" => res.mirror = true,
+                "--mirror-monitor" => {
+                    res.mirror = true;
+                    res.mirror_monitor = Some(Self::value(&mut args, &arg)?);
+                }
+                x => bail!("Unknown argument `{}`", x),
+            }
+        }
+        ensure!(
+            res.idle_fps > 0,
+            "Argument `--idle-fps` must be larger than zero"
+        );
+        Ok(res)
+    }
+
+    fn value<T>(args: &mut impl Iterator<Item = String>, name: &str) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: Error + Send + Sync + 'static,
+    {
+        args.next()
+            .with_context(|| format!("Missing value for argument `{}`", name))?
+            .parse()
+            .with_context(|| format!("Invalid value for argument `{}`", name))
+    }
+}
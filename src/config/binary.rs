@@ -0,0 +1,100 @@
+//! Loading of meshes from binary files of little endian vertex and index data.
+use super::ser::VertexAttribute;
+use crate::{
+    geom::{Mesh, Shading},
+    render::Vertex,
+};
+use anyhow::{Context, Result};
+use std::{fs, path::Path};
+
+impl VertexAttribute {
+    fn components(&self) -> usize {
+        match *self {
+            VertexAttribute::Position | VertexAttribute::Normal => 3,
+            VertexAttribute::TexCoord => 2,
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>> {
+    fs::read(path).with_context(|| format!("Failed to read `{}`", path.display()))
+}
+
+/// Load a mesh from a file of interleaved `f32` vertex attributes laid out as described by the
+/// format and a file of `u32` triangle indices.
+///
+/// Normals are calculated from the faces if the format does not contain them.
+pub fn load(vertices: &Path, indices: &Path, format: &[VertexAttribute]) -> Result<Mesh> {
+    ensure!(
+        format.contains(&VertexAttribute::Position),
+        "Vertex format must contain `position`"
+    );
+    for (idx, x) in format.iter().enumerate() {
+        ensure!(
+            !format[..idx].contains(x),
+            "Vertex format contains `{:?}` more than once",
+            x
+        );
+    }
+    let stride = format.iter().map(|x| x.components()).sum::<usize>() * 4;
+
+    let vertex_data = read(vertices)?;
+    ensure!(
+        vertex_data.len() % stride == 0,
+        "Size of vertex file `{}` is {} bytes which is not a multiple of the vertex size of {} bytes",
+        vertices.display(),
+        vertex_data.len(),
+        stride
+    );
+    let index_data = read(indices)?;
+    ensure!(
+        index_data.len() % 12 == 0,
+        "Size of index file `{}` is {} bytes which is not a multiple of the triangle size of 12 bytes",
+        indices.display(),
+        index_data.len()
+    );
+
+    let verticies: Vec<Vertex> = vertex_data
+        .chunks_exact(stride)
+        .map(|chunk| {
+            let mut floats = chunk
+                .chunks_exact(4)
+                .map(|x| f32::from_le_bytes([x[0], x[1], x[2], x[3]]));
+            let mut vertex = Vertex {
+                position: [0.0; 3],
+                normal: [0.0; 3],
+                tex_coord: [0.0; 2],
+            };
+            for attribute in format {
+                let target: &mut [f32] = match attribute {
+                    VertexAttribute::Position => &mut vertex.position,
+                    VertexAttribute::Normal => &mut vertex.normal,
+                    VertexAttribute::TexCoord => &mut vertex.tex_coord,
+                };
+                for x in target.iter_mut() {
+                    *x = floats.next().unwrap();
+                }
+            }
+            vertex
+        })
+        .collect();
+
+    let index: Vec<u32> = index_data
+        .chunks_exact(4)
+        .map(|x| u32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+        .collect();
+    if let Some(x) = index.iter().find(|x| **x as usize >= verticies.len()) {
+        bail!(
+            "Index {} out of range, the vertex file contains {} vertices",
+            x,
+            verticies.len()
+        );
+    }
+
+    let mesh = (verticies, index);
+    if format.contains(&VertexAttribute::Normal) {
+        Ok(mesh)
+    } else {
+        Ok(Shading::Smooth.apply(mesh))
+    }
+}
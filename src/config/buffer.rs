@@ -0,0 +1,47 @@
+use super::ser;
+use anyhow::{Context, Result};
+use glium::{
+    buffer::{Buffer, BufferMode, BufferType},
+    CapabilitiesSource, Display,
+};
+
+/// A shader storage buffer which can be written by shaders and read back on the CPU.
+#[derive(Debug)]
+pub struct LoadedBuffer {
+    pub buffer: Buffer<[f32]>,
+    pub config: ser::StorageBuffer,
+}
+
+impl LoadedBuffer {
+    pub fn load(config: &ser::StorageBuffer, display: &Display) -> Result<Self> {
+        ensure!(
+            display.get_capabilities().max_indexed_shader_storage_buffer > 0,
+            "Storage buffer `{}` requires OpenGL 4.3 or ARB_shader_storage_buffer_object which is not supported by the current context",
+            config.name
+        );
+        ensure!(
+            config.size > 0,
+            "Storage buffer `{}` must have a size larger than zero",
+            config.name
+        );
+        let data = vec![0.0f32; config.size];
+        let buffer = Buffer::new(
+            display,
+            &data[..],
+            BufferType::ShaderStorageBuffer,
+            BufferMode::Default,
+        )
+        .with_context(|| format!("failed to create storage buffer `{}`", config.name))?;
+        Ok(LoadedBuffer {
+            buffer,
+            config: config.clone(),
+        })
+    }
+
+    /// Read the contents of the buffer back from the GPU.
+    pub fn read(&self) -> Result<Vec<f32>> {
+        self.buffer
+            .read()
+            .with_context(|| format!("failed to read storage buffer `{}`", self.config.name))
+    }
+}
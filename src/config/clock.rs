@@ -0,0 +1,64 @@
+//! Named clocks which can be paused and scaled independently of each other.
+use super::ser;
+use std::time::Instant;
+
+#[derive(Debug, Clone)]
+pub struct LoadedClock {
+    pub name: String,
+    scale: f32,
+    paused: bool,
+    /// The time of the clock at the last change to its speed.
+    base: f32,
+    /// Time of the last change, the clock has advanced at a constant speed since.
+    last_update: Instant,
+}
+
+impl LoadedClock {
+    pub fn new(name: String, config: &ser::Clock) -> Self {
+        LoadedClock {
+            name,
+            scale: config.scale,
+            paused: config.paused,
+            base: 0.0,
+            last_update: Instant::now(),
+        }
+    }
+
+    /// Returns the current time of the clock in seconds.
+    pub fn time(&self) -> f32 {
+        if self.paused {
+            self.base
+        } else {
+            self.base + self.last_update.elapsed().as_secs_f32() * self.scale
+        }
+    }
+
+    /// Store the current time so the speed can change from here.
+    fn update(&mut self) {
+        self.base = self.time();
+        self.last_update = Instant::now();
+    }
+
+    pub fn set_time(&mut self, time: f32) {
+        self.base = time;
+        self.last_update = Instant::now();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.update();
+        self.paused = paused;
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn set_scale(&mut self, scale: f32) {
+        self.update();
+        self.scale = scale;
+    }
+}
@@ -0,0 +1,120 @@
+//! A free flying camera controlled with the keyboard and mouse.
+use glam::f32::{Mat4, Vec3};
+use glium::glutin::event::VirtualKeyCode;
+use std::time::Instant;
+
+/// The pitch is kept away from straight up or down so the view can't flip over.
+const MAX_PITCH: f32 = 89.0;
+
+/// Keys moving the camera along with the direction they move in, relative to the camera.
+const MOVEMENT_KEYS: [(VirtualKeyCode, [f32; 3]); 6] = [
+    (VirtualKeyCode::W, [0.0, 0.0, 1.0]),
+    (VirtualKeyCode::S, [0.0, 0.0, -1.0]),
+    (VirtualKeyCode::D, [1.0, 0.0, 0.0]),
+    (VirtualKeyCode::A, [-1.0, 0.0, 0.0]),
+    (VirtualKeyCode::E, [0.0, 1.0, 0.0]),
+    (VirtualKeyCode::Q, [0.0, -1.0, 0.0]),
+];
+
+#[derive(Debug, Clone, Copy)]
+pub struct FlyingCamera {
+    /// The position at the time of the last change in movement.
+    position: Vec3,
+    /// Rotation around the up axis in degrees.
+    yaw: f32,
+    /// Rotation above the horizon in degrees.
+    pitch: f32,
+    /// Movement speed in units per second.
+    speed: f32,
+    /// Which of the movement keys are held.
+    pressed: [bool; 6],
+    /// Time the position was last updated, the camera moves in a straight line since.
+    last_update: Instant,
+}
+
+impl FlyingCamera {
+    pub fn new(position: Vec3, yaw: f32, pitch: f32, speed: f32) -> Self {
+        FlyingCamera {
+            position,
+            yaw,
+            pitch: pitch.clamp(-MAX_PITCH, MAX_PITCH),
+            speed,
+            pressed: [false; 6],
+            last_update: Instant::now(),
+        }
+    }
+
+    /// Returns the direction the camera is looking in.
+    pub fn forward(&self) -> Vec3 {
+        let (yaw, pitch) = (self.yaw.to_radians(), self.pitch.to_radians());
+        Vec3::new(
+            yaw.sin() * pitch.cos(),
+            pitch.sin(),
+            yaw.cos() * pitch.cos(),
+        )
+    }
+
+    fn velocity(&self) -> Vec3 {
+        let forward = self.forward();
+        let right = Vec3::Y.cross(forward).normalize_or_zero();
+        let direction = MOVEMENT_KEYS
+            .iter()
+            .zip(self.pressed.iter())
+            .filter(|(_, pressed)| **pressed)
+            .map(|((_, x), _)| Vec3::from(*x))
+            .fold(Vec3::ZERO, |a, b| a + b);
+        (right * direction.x + Vec3::Y * direction.y + forward * direction.z).normalize_or_zero()
+            * self.speed
+    }
+
+    /// Returns the current position of the camera.
+    pub fn position(&self) -> Vec3 {
+        self.position + self.velocity() * self.last_update.elapsed().as_secs_f32()
+    }
+
+    /// Store the current position so the movement can change direction from here.
+    fn update(&mut self) {
+        self.position = self.position();
+        self.last_update = Instant::now();
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.velocity() != Vec3::ZERO
+    }
+
+    pub fn view_matrix(&self) -> Mat4 {
+        let position = self.position();
+        Mat4::look_at_lh(position, position + self.forward(), Vec3::Y)
+    }
+
+    /// Update the held movement keys, returns whether the key moves the camera.
+    pub fn set_key(&mut self, key: VirtualKeyCode, pressed: bool) -> bool {
+        match MOVEMENT_KEYS.iter().position(|(x, _)| *x == key) {
+            Some(idx) => {
+                self.update();
+                self.pressed[idx] = pressed;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stop all movement, for example when the window loses focus and key releases are missed.
+    pub fn release_keys(&mut self) {
+        self.update();
+        self.pressed = [false; 6];
+    }
+
+    /// Rotate the camera by the given amount of degrees.
+    pub fn look(&mut self, yaw: f32, pitch: f32) {
+        self.update();
+        self.yaw = (self.yaw + yaw) % 360.0;
+        self.pitch = (self.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Scale the movement speed, positive steps speed up and negative steps slow down.
+    pub fn scale_speed(&mut self, steps: f32) {
+        self.update();
+        self.speed = (self.speed * 1.1f32.powf(steps)).max(0.01);
+    }
+}
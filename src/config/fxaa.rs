@@ -0,0 +1,174 @@
+//! Fast approximate antialiasing of the final frame.
+use crate::{geom::Geometry, render::Vertex};
+use anyhow::{Context, Result};
+use glium::{
+    framebuffer::SimpleFrameBuffer,
+    program::ProgramCreationInput,
+    texture::{DepthFormat, DepthTexture2d, MipmapsOption, SrgbFormat, SrgbTexture2d},
+    uniform,
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction},
+    Display, DrawParameters, IndexBuffer, Program, Surface, VertexBuffer,
+};
+
+const VERTEX_SHADER: &str = r#"
+#version 330
+
+in vec3 position;
+
+out vec2 uv;
+
+void main(){
+    uv = position.xy * 0.5 + 0.5;
+    gl_Position = vec4(position.xy, 0.0, 1.0);
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+#version 330
+
+#define REDUCE_MIN (1.0 / 128.0)
+#define REDUCE_MUL (1.0 / 8.0)
+#define SPAN_MAX 8.0
+
+in vec2 uv;
+
+uniform sampler2D frame;
+uniform vec2 texel_size;
+
+out vec4 color;
+
+// Edges are detected on perceptual luminance, the square root is close enough to gamma encoding.
+float luma(vec3 rgb){
+    return dot(sqrt(max(rgb, 0.0)), vec3(0.299, 0.587, 0.114));
+}
+
+void main(){
+    vec4 center = texture(frame, uv);
+    float luma_nw = luma(texture(frame, uv + vec2(-1.0, 1.0) * texel_size).rgb);
+    float luma_ne = luma(texture(frame, uv + vec2(1.0, 1.0) * texel_size).rgb);
+    float luma_sw = luma(texture(frame, uv + vec2(-1.0, -1.0) * texel_size).rgb);
+    float luma_se = luma(texture(frame, uv + vec2(1.0, -1.0) * texel_size).rgb);
+    float luma_m = luma(center.rgb);
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    // Blur along the edge, which is perpendicular to the luminance gradient.
+    vec2 dir = vec2(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se)
+    );
+    float dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * (0.25 * REDUCE_MUL), REDUCE_MIN);
+    float dir_scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * dir_scale, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * texel_size;
+
+    vec3 rgb_a = 0.5 * (
+        texture(frame, uv + dir * (1.0 / 3.0 - 0.5)).rgb +
+        texture(frame, uv + dir * (2.0 / 3.0 - 0.5)).rgb
+    );
+    vec3 rgb_b = rgb_a * 0.5 + 0.25 * (
+        texture(frame, uv - dir * 0.5).rgb +
+        texture(frame, uv + dir * 0.5).rgb
+    );
+    // The wider blur crossed another edge, fall back to the narrow one.
+    float luma_b = luma(rgb_b);
+    vec3 rgb = (luma_b < luma_min || luma_b > luma_max) ? rgb_a : rgb_b;
+    color = vec4(rgb, center.a);
+}
+"#;
+
+#[derive(Debug)]
+pub struct Fxaa {
+    /// Whether antialiasing is applied to the frame.
+    pub enabled: bool,
+    program: Program,
+    quad: (VertexBuffer<Vertex>, IndexBuffer<u32>),
+    /// An sRGB texture so both linear and already encoded output end up stored the same way.
+    color: SrgbTexture2d,
+    depth: DepthTexture2d,
+}
+
+impl Fxaa {
+    pub fn load(enabled: bool, display: &Display) -> Result<Self> {
+        let program = Program::new(
+            display,
+            ProgramCreationInput::SourceCode {
+                vertex_shader: VERTEX_SHADER,
+                tessellation_control_shader: None,
+                tessellation_evaluation_shader: None,
+                geometry_shader: None,
+                fragment_shader: FRAGMENT_SHADER,
+                transform_feedback_varyings: None,
+                outputs_srgb: false,
+                uses_point_size: false,
+            },
+        )
+        .context("failed to compile FXAA program")?;
+        let quad = Geometry::ScreenQuad.to_buffers(display)?;
+        let (color, depth) = Self::create_textures(display, display.get_framebuffer_dimensions())?;
+
+        Ok(Fxaa {
+            enabled,
+            program,
+            quad,
+            color,
+            depth,
+        })
+    }
+
+    fn create_textures(
+        display: &Display,
+        (width, height): (u32, u32),
+    ) -> Result<(SrgbTexture2d, DepthTexture2d)> {
+        let color = SrgbTexture2d::empty_with_format(
+            display,
+            SrgbFormat::U8U8U8U8,
+            MipmapsOption::NoMipmap,
+            width.max(1),
+            height.max(1),
+        )
+        .context("failed to create FXAA texture")?;
+        let depth = DepthTexture2d::empty_with_format(
+            display,
+            DepthFormat::I24,
+            MipmapsOption::NoMipmap,
+            width.max(1),
+            height.max(1),
+        )
+        .context("failed to create FXAA depth texture")?;
+        Ok((color, depth))
+    }
+
+    pub fn resize(&mut self, dimensions: (u32, u32), display: &Display) -> Result<()> {
+        let (color, depth) = Self::create_textures(display, dimensions)?;
+        self.color = color;
+        self.depth = depth;
+        Ok(())
+    }
+
+    /// Returns the surface the frame should be rendered to instead of the frame.
+    pub fn target<'a>(&'a self, display: &Display) -> Result<SimpleFrameBuffer<'a>> {
+        SimpleFrameBuffer::with_depth_buffer(display, &self.color, &self.depth)
+            .context("could not create FXAA frame buffer")
+    }
+
+    /// Draw the antialiased image to the given surface.
+    pub fn apply<S: Surface>(&self, surface: &mut S) -> Result<()> {
+        let (width, height) = self.color.dimensions();
+        let uniforms = uniform! {
+            frame: Sampler::new(&self.color)
+                .wrap_function(SamplerWrapFunction::Clamp)
+                .minify_filter(MinifySamplerFilter::Linear)
+                .magnify_filter(MagnifySamplerFilter::Linear),
+            texel_size: [1.0 / width as f32, 1.0 / height as f32],
+        };
+        surface
+            .draw(
+                &self.quad.0,
+                &self.quad.1,
+                &self.program,
+                &uniforms,
+                &DrawParameters::default(),
+            )
+            .context("could not apply FXAA")
+    }
+}
@@ -0,0 +1,410 @@
+//! Loading of meshes from glTF 2.0 files.
+use crate::{
+    geom::{Mesh, Shading},
+    render::Vertex,
+};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{collections::HashMap, fs, path::Path};
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_CHUNK_JSON: u32 = 0x4E4F534A;
+const GLB_CHUNK_BIN: u32 = 0x004E4942;
+
+const COMPONENT_BYTE: u32 = 5120;
+const COMPONENT_UNSIGNED_BYTE: u32 = 5121;
+const COMPONENT_SHORT: u32 = 5122;
+const COMPONENT_UNSIGNED_SHORT: u32 = 5123;
+const COMPONENT_UNSIGNED_INT: u32 = 5125;
+const COMPONENT_FLOAT: u32 = 5126;
+
+const MODE_TRIANGLES: u32 = 4;
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Document {
+    #[serde(default)]
+    buffers: Vec<Buffer>,
+    #[serde(default)]
+    buffer_views: Vec<BufferView>,
+    #[serde(default)]
+    accessors: Vec<Accessor>,
+    #[serde(default)]
+    meshes: Vec<GltfMesh>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Buffer {
+    uri: Option<String>,
+    byte_length: usize,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct BufferView {
+    buffer: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Accessor {
+    buffer_view: Option<usize>,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+    #[serde(default)]
+    normalized: bool,
+    count: usize,
+    r#type: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct GltfMesh {
+    name: Option<String>,
+    primitives: Vec<Primitive>,
+}
+
+fn default_mode() -> u32 {
+    MODE_TRIANGLES
+}
+
+#[derive(Deserialize, Debug)]
+struct Primitive {
+    attributes: HashMap<String, usize>,
+    indices: Option<usize>,
+    #[serde(default = "default_mode")]
+    mode: u32,
+}
+
+fn mode_name(mode: u32) -> &'static str {
+    match mode {
+        0 => "points",
+        1 => "lines",
+        2 => "line loop",
+        3 => "line strip",
+        4 => "triangles",
+        5 => "triangle strip",
+        6 => "triangle fan",
+        _ => "unknown",
+    }
+}
+
+fn component_size(component_type: u32) -> Result<usize> {
+    Ok(match component_type {
+        COMPONENT_BYTE | COMPONENT_UNSIGNED_BYTE => 1,
+        COMPONENT_SHORT | COMPONENT_UNSIGNED_SHORT => 2,
+        COMPONENT_UNSIGNED_INT | COMPONENT_FLOAT => 4,
+        x => bail!("Unknown component type `{}`", x),
+    })
+}
+
+fn type_components(ty: &str) -> Result<usize> {
+    Ok(match ty {
+        "SCALAR" => 1,
+        "VEC2" => 2,
+        "VEC3" => 3,
+        "VEC4" => 4,
+        "MAT2" => 4,
+        "MAT3" => 9,
+        "MAT4" => 16,
+        x => bail!("Unknown accessor type `{}`", x),
+    })
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .context("Unexpected end of file")?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Split a binary glTF file into its JSON and binary chunk.
+fn parse_glb(data: &[u8]) -> Result<(&[u8], Option<&[u8]>)> {
+    let version = read_u32(data, 4)?;
+    ensure!(
+        version == 2,
+        "Only glTF version 2 is supported, found version {}",
+        version
+    );
+    let length = (read_u32(data, 8)? as usize).min(data.len());
+
+    let mut json = None;
+    let mut bin = None;
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let chunk_length = read_u32(data, offset)? as usize;
+        let chunk_type = read_u32(data, offset + 4)?;
+        let chunk = data
+            .get(offset + 8..offset + 8 + chunk_length)
+            .context("Chunk extends past the end of the file")?;
+        match chunk_type {
+            GLB_CHUNK_JSON if json.is_none() => json = Some(chunk),
+            GLB_CHUNK_BIN if bin.is_none() => bin = Some(chunk),
+            // Unknown chunks must be ignored.
+            _ => {}
+        }
+        offset += 8 + chunk_length;
+    }
+    Ok((json.context("Missing JSON chunk")?, bin))
+}
+
+fn load_buffer(buffer: &Buffer, base: &Path, bin: Option<&[u8]>) -> Result<Vec<u8>> {
+    let data = match buffer.uri {
+        None => bin
+            .context("Buffer without an uri but the file has no binary chunk")?
+            .to_vec(),
+        Some(ref uri) if uri.starts_with("data:") => {
+            let (header, data) = uri.split_once(',').context("Invalid data uri")?;
+            ensure!(
+                header.ends_with(";base64"),
+                "Only base64 encoded data uris are supported"
+            );
+            base64::decode(data).context("Invalid base64 in data uri")?
+        }
+        Some(ref uri) => {
+            let path = base.join(uri);
+            fs::read(&path)
+                .with_context(|| format!("Failed to read buffer `{}`", path.display()))?
+        }
+    };
+    ensure!(
+        data.len() >= buffer.byte_length,
+        "Buffer is {} bytes long but should be {} bytes",
+        data.len(),
+        buffer.byte_length
+    );
+    Ok(data)
+}
+
+struct Loader<'a> {
+    document: &'a Document,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl<'a> Loader<'a> {
+    /// Read the components of every element of an accessor converted to floats.
+    ///
+    /// Doubles are used so that 32 bit indices are represented exactly.
+    fn read(&self, accessor: usize) -> Result<Vec<Vec<f64>>> {
+        let accessor = self
+            .document
+            .accessors
+            .get(accessor)
+            .with_context(|| format!("Accessor `{}` does not exist", accessor))?;
+        let components = type_components(&accessor.r#type)?;
+        let size = component_size(accessor.component_type)?;
+
+        // Accessors without a buffer view are initialized with zeros.
+        let view = match accessor.buffer_view {
+            Some(x) => self
+                .document
+                .buffer_views
+                .get(x)
+                .with_context(|| format!("Buffer view `{}` does not exist", x))?,
+            None => return Ok(vec![vec![0.0; components]; accessor.count]),
+        };
+        let buffer = self
+            .buffers
+            .get(view.buffer)
+            .with_context(|| format!("Buffer `{}` does not exist", view.buffer))?;
+        let data = buffer
+            .get(view.byte_offset..view.byte_offset + view.byte_length)
+            .context("Buffer view extends past the end of the buffer")?;
+        let stride = view.byte_stride.unwrap_or(components * size);
+
+        let mut res = Vec::with_capacity(accessor.count);
+        for idx in 0..accessor.count {
+            let start = accessor.byte_offset + idx * stride;
+            let element = data
+                .get(start..start + components * size)
+                .context("Accessor extends past the end of the buffer view")?;
+            let element = element
+                .chunks_exact(size)
+                .map(|x| match (accessor.component_type, accessor.normalized) {
+                    (COMPONENT_FLOAT, _) => f32::from_le_bytes([x[0], x[1], x[2], x[3]]) as f64,
+                    (COMPONENT_UNSIGNED_BYTE, true) => x[0] as f64 / u8::MAX as f64,
+                    (COMPONENT_UNSIGNED_SHORT, true) => {
+                        u16::from_le_bytes([x[0], x[1]]) as f64 / u16::MAX as f64
+                    }
+                    (COMPONENT_BYTE, true) => (x[0] as i8 as f64 / i8::MAX as f64).max(-1.0),
+                    (COMPONENT_SHORT, true) => {
+                        (i16::from_le_bytes([x[0], x[1]]) as f64 / i16::MAX as f64).max(-1.0)
+                    }
+                    (COMPONENT_UNSIGNED_BYTE, false) => x[0] as f64,
+                    (COMPONENT_UNSIGNED_SHORT, false) => u16::from_le_bytes([x[0], x[1]]) as f64,
+                    (COMPONENT_UNSIGNED_INT, _) => {
+                        u32::from_le_bytes([x[0], x[1], x[2], x[3]]) as f64
+                    }
+                    (COMPONENT_BYTE, false) => x[0] as i8 as f64,
+                    _ => i16::from_le_bytes([x[0], x[1]]) as f64,
+                })
+                .collect();
+            res.push(element);
+        }
+        Ok(res)
+    }
+
+    fn read_indices(&self, accessor: usize) -> Result<Vec<u32>> {
+        let component_type = self
+            .document
+            .accessors
+            .get(accessor)
+            .with_context(|| format!("Accessor `{}` does not exist", accessor))?
+            .component_type;
+        ensure!(
+            matches!(
+                component_type,
+                COMPONENT_UNSIGNED_BYTE | COMPONENT_UNSIGNED_SHORT | COMPONENT_UNSIGNED_INT
+            ),
+            "Indices must be unsigned integers, found component type `{}`",
+            component_type
+        );
+        Ok(self
+            .read(accessor)?
+            .into_iter()
+            .map(|x| x[0] as u32)
+            .collect())
+    }
+
+    fn read_attribute<const N: usize>(
+        &self,
+        primitive: &Primitive,
+        name: &str,
+    ) -> Result<Option<Vec<[f32; N]>>> {
+        let accessor = match primitive.attributes.get(name) {
+            Some(x) => *x,
+            None => return Ok(None),
+        };
+        self.read(accessor)
+            .and_then(|data| {
+                data.into_iter()
+                    .map(|x| {
+                        ensure!(x.len() == N, "Expected {} components found {}", N, x.len());
+                        let mut res = [0.0; N];
+                        for (res, x) in res.iter_mut().zip(x) {
+                            *res = x as f32;
+                        }
+                        Ok(res)
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .map(Some)
+            .with_context(|| format!("Failed to read attribute `{}`", name))
+    }
+
+    fn primitive(&self, primitive: &Primitive) -> Result<Mesh> {
+        ensure!(
+            primitive.mode == MODE_TRIANGLES,
+            "Primitives with topology `{}` are not supported, only triangles are",
+            mode_name(primitive.mode)
+        );
+        let positions = self
+            .read_attribute::<3>(primitive, "POSITION")?
+            .context("Primitive is missing the `POSITION` attribute")?;
+        let normals = self.read_attribute::<3>(primitive, "NORMAL")?;
+        let tex_coords = self.read_attribute::<2>(primitive, "TEXCOORD_0")?;
+
+        let verticies = positions
+            .iter()
+            .enumerate()
+            .map(|(idx, position)| Vertex {
+                position: *position,
+                normal: normals
+                    .as_ref()
+                    .and_then(|x| x.get(idx).copied())
+                    .unwrap_or([0.0; 3]),
+                tex_coord: tex_coords
+                    .as_ref()
+                    .and_then(|x| x.get(idx).copied())
+                    .unwrap_or([0.0; 2]),
+            })
+            .collect::<Vec<_>>();
+
+        let index = match primitive.indices {
+            Some(x) => self.read_indices(x).context("Failed to read indices")?,
+            None => (0..verticies.len() as u32).collect(),
+        };
+        ensure!(
+            index.len().is_multiple_of(3),
+            "Amount of indices must be a multiple of 3, found {}",
+            index.len()
+        );
+        if let Some(x) = index.iter().find(|x| **x as usize >= verticies.len()) {
+            bail!(
+                "Index `{}` is out of bounds for {} vertices",
+                x,
+                verticies.len()
+            );
+        }
+
+        let mesh = (verticies, index);
+        if normals.is_none() {
+            Ok(Shading::Smooth.apply(mesh))
+        } else {
+            Ok(mesh)
+        }
+    }
+}
+
+fn load_mesh(data: &[u8], base: &Path, mesh: Option<&str>) -> Result<Mesh> {
+    let (json, bin) = if data.starts_with(GLB_MAGIC) {
+        parse_glb(data)?
+    } else {
+        (data, None)
+    };
+    let document: Document = serde_json::from_slice(json).context("Invalid glTF document")?;
+    let buffers = document
+        .buffers
+        .iter()
+        .map(|x| load_buffer(x, base, bin))
+        .collect::<Result<Vec<_>>>()?;
+
+    let gltf_mesh = match mesh {
+        Some(name) => document
+            .meshes
+            .iter()
+            .find(|x| x.name.as_deref() == Some(name))
+            .with_context(|| {
+                let mut names = document.meshes.iter().filter_map(|x| x.name.as_deref());
+                let mut error = format!("Could not find mesh `{}`.", name);
+                if let Some(first) = names.next() {
+                    error.push_str(&format!(" Expected one of `{}`", first));
+                    for x in names {
+                        error.push_str(&format!(", `{}`", x));
+                    }
+                }
+                error
+            })?,
+        None => document
+            .meshes
+            .first()
+            .context("File does not contain a mesh")?,
+    };
+    let primitive = gltf_mesh
+        .primitives
+        .first()
+        .context("Mesh does not have any primitives")?;
+
+    Loader {
+        document: &document,
+        buffers,
+    }
+    .primitive(primitive)
+}
+
+/// Load the first primitive of a mesh from a `.gltf` or `.glb` file.
+///
+/// Loads the first mesh in the file if no mesh name is given. Normals are calculated from the
+/// faces if the primitive does not have any.
+pub fn load(path: &Path, mesh: Option<&str>) -> Result<Mesh> {
+    let data =
+        fs::read(path).with_context(|| format!("Failed to read model `{}`", path.display()))?;
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    load_mesh(&data, base, mesh)
+        .with_context(|| format!("Failed to load model `{}`", path.display()))
+}
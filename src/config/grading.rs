@@ -0,0 +1,188 @@
+//! Color grading of the final frame with a LUT texture.
+use super::{
+    ser,
+    texture::{LoadedTexture, LoadedTextureKind},
+};
+use crate::{geom::Geometry, render::Vertex};
+use anyhow::{Context, Result};
+use glium::{
+    framebuffer::SimpleFrameBuffer,
+    program::ProgramCreationInput,
+    texture::{DepthFormat, DepthTexture2d, MipmapsOption, Texture2d, UncompressedFloatFormat},
+    uniform,
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction},
+    Display, DrawParameters, IndexBuffer, Program, Surface, VertexBuffer,
+};
+use std::collections::HashMap;
+
+const VERTEX_SHADER: &str = r#"
+#version 330
+
+in vec3 position;
+
+out vec2 uv;
+
+void main(){
+    uv = position.xy * 0.5 + 0.5;
+    gl_Position = vec4(position.xy, 0.0, 1.0);
+}
+"#;
+
+// The program outputs sRGB directly as LUTs are generally authored for gamma encoded colors.
+const FRAGMENT_SHADER: &str = r#"
+#version 330
+
+in vec2 uv;
+
+uniform sampler2D frame;
+uniform sampler3D lut;
+uniform vec3 domain_min;
+uniform vec3 domain_max;
+uniform float intensity;
+
+out vec4 color;
+
+vec3 to_srgb(vec3 linear){
+    vec3 low = linear * 12.92;
+    vec3 high = 1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055;
+    return mix(high, low, vec3(lessThanEqual(linear, vec3(0.0031308))));
+}
+
+void main(){
+    vec4 source = texture(frame, uv);
+    vec3 srgb = to_srgb(clamp(source.rgb, 0.0, 1.0));
+    vec3 coord = clamp((srgb - domain_min) / (domain_max - domain_min), 0.0, 1.0);
+    // Sample at texel centers so the ends of the domain map to the first and last entries.
+    vec3 size = vec3(textureSize(lut, 0));
+    vec3 graded = texture(lut, coord * (size - 1.0) / size + 0.5 / size).rgb;
+    color = vec4(mix(srgb, graded, intensity), source.a);
+}
+"#;
+
+#[derive(Debug)]
+pub struct ColorGrading {
+    /// Whether grading is applied to the frame.
+    pub enabled: bool,
+    /// How much of the graded color is mixed into the frame.
+    pub intensity: f32,
+    lut: usize,
+    program: Program,
+    quad: (VertexBuffer<Vertex>, IndexBuffer<u32>),
+    color: Texture2d,
+    depth: DepthTexture2d,
+}
+
+impl ColorGrading {
+    pub fn load(
+        config: &ser::Grading,
+        texture_name_match: &HashMap<String, usize>,
+        textures: &[LoadedTexture],
+        display: &Display,
+    ) -> Result<Self> {
+        let lut = texture_name_match
+            .get(&config.lut)
+            .copied()
+            .with_context(|| format!("Could not find texture `{}`", config.lut))?;
+        ensure!(
+            matches!(textures[lut].kind, LoadedTextureKind::Lut { .. }),
+            "Texture `{}` used for color grading is not a LUT",
+            config.lut
+        );
+
+        let program = Program::new(
+            display,
+            ProgramCreationInput::SourceCode {
+                vertex_shader: VERTEX_SHADER,
+                tessellation_control_shader: None,
+                tessellation_evaluation_shader: None,
+                geometry_shader: None,
+                fragment_shader: FRAGMENT_SHADER,
+                transform_feedback_varyings: None,
+                outputs_srgb: true,
+                uses_point_size: false,
+            },
+        )
+        .context("failed to compile color grading program")?;
+        let quad = Geometry::ScreenQuad.to_buffers(display)?;
+        let (color, depth) = Self::create_textures(display, display.get_framebuffer_dimensions())?;
+
+        Ok(ColorGrading {
+            enabled: true,
+            intensity: config.intensity,
+            lut,
+            program,
+            quad,
+            color,
+            depth,
+        })
+    }
+
+    fn create_textures(
+        display: &Display,
+        (width, height): (u32, u32),
+    ) -> Result<(Texture2d, DepthTexture2d)> {
+        let color = Texture2d::empty_with_format(
+            display,
+            UncompressedFloatFormat::F16F16F16F16,
+            MipmapsOption::NoMipmap,
+            width.max(1),
+            height.max(1),
+        )
+        .context("failed to create color grading texture")?;
+        let depth = DepthTexture2d::empty_with_format(
+            display,
+            DepthFormat::I24,
+            MipmapsOption::NoMipmap,
+            width.max(1),
+            height.max(1),
+        )
+        .context("failed to create color grading depth texture")?;
+        Ok((color, depth))
+    }
+
+    pub fn resize(&mut self, dimensions: (u32, u32), display: &Display) -> Result<()> {
+        let (color, depth) = Self::create_textures(display, dimensions)?;
+        self.color = color;
+        self.depth = depth;
+        Ok(())
+    }
+
+    /// Returns the surface passes should render to instead of the frame.
+    pub fn target<'a>(&'a self, display: &Display) -> Result<SimpleFrameBuffer<'a>> {
+        SimpleFrameBuffer::with_depth_buffer(display, &self.color, &self.depth)
+            .context("could not create color grading frame buffer")
+    }
+
+    /// Draw the graded image to the given surface.
+    pub fn apply<S: Surface>(&self, surface: &mut S, textures: &[LoadedTexture]) -> Result<()> {
+        let (domain_min, domain_max, lut) = match textures[self.lut].kind {
+            LoadedTextureKind::Lut {
+                domain_min,
+                domain_max,
+                ref texture,
+            } => (domain_min, domain_max, &**texture),
+            _ => unreachable!(),
+        };
+        let uniforms = uniform! {
+            frame: Sampler::new(&self.color)
+                .minify_filter(MinifySamplerFilter::Nearest)
+                .magnify_filter(MagnifySamplerFilter::Nearest),
+            lut: Sampler::new(lut)
+                .wrap_function(SamplerWrapFunction::Clamp)
+                .minify_filter(MinifySamplerFilter::Linear)
+                .magnify_filter(MagnifySamplerFilter::Linear),
+            domain_min: domain_min,
+            domain_max: domain_max,
+            intensity: self.intensity,
+        };
+        surface
+            .draw(
+                &self.quad.0,
+                &self.quad.1,
+                &self.program,
+                &uniforms,
+                &DrawParameters::default(),
+            )
+            .context("could not apply color grading")
+    }
+}
@@ -0,0 +1,84 @@
+//! Describes which passes read and write which textures as a Graphviz graph.
+use super::Config;
+use std::fmt::Write;
+
+impl Config {
+    /// Returns the render graph in the Graphviz dot format.
+    ///
+    /// Passes and textures are nodes, an edge from a texture to a pass means the pass samples the
+    /// texture and an edge from a pass to a texture means the pass renders to it.
+    pub fn render_graph(&self) -> String {
+        let mut res = String::new();
+        writeln!(res, "digraph render_graph {{").unwrap();
+        writeln!(res, "    rankdir=LR;").unwrap();
+        writeln!(res, "    frame [label=\"frame\", shape=doublecircle];").unwrap();
+        for (idx, texture) in self.textures.iter().enumerate() {
+            writeln!(
+                res,
+                "    texture{} [label={:?}, shape=ellipse];",
+                idx, texture.config.name
+            )
+            .unwrap();
+        }
+
+        for (pass_id, pass) in self.passes.iter().enumerate() {
+            writeln!(
+                res,
+                "    pass{} [label=\"pass {}\", shape=box];",
+                pass_id, pass_id
+            )
+            .unwrap();
+            for (idx, name) in pass.textures.iter() {
+                if *name == self.textures[*idx].config.name {
+                    writeln!(res, "    texture{} -> pass{};", idx, pass_id).unwrap();
+                } else {
+                    writeln!(
+                        res,
+                        "    texture{} -> pass{} [label={:?}];",
+                        idx, pass_id, name
+                    )
+                    .unwrap();
+                }
+            }
+            match pass.target {
+                Some(ref target) => {
+                    for (idx, name) in target.color.iter() {
+                        writeln!(
+                            res,
+                            "    pass{} -> texture{} [label={:?}];",
+                            pass_id, idx, name
+                        )
+                        .unwrap();
+                    }
+                    if let Some(idx) = target.depth {
+                        writeln!(
+                            res,
+                            "    pass{} -> texture{} [label=\"depth\", style=dashed];",
+                            pass_id, idx
+                        )
+                        .unwrap();
+                    }
+                }
+                None => writeln!(res, "    pass{} -> frame;", pass_id).unwrap(),
+            }
+        }
+        writeln!(res, "}}").unwrap();
+        res
+    }
+
+    /// Returns the color textures rendered to by the passes in the order they are first written,
+    /// together with the pass writing them.
+    pub fn pass_outputs(&self) -> Vec<(usize, usize)> {
+        let mut res: Vec<(usize, usize)> = Vec::new();
+        for (pass_id, pass) in self.passes.iter().enumerate() {
+            if let Some(ref target) = pass.target {
+                for (idx, _) in target.color.iter() {
+                    if !res.iter().any(|x| x.1 == *idx) {
+                        res.push((pass_id, *idx));
+                    }
+                }
+            }
+        }
+        res
+    }
+}
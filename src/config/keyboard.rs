@@ -0,0 +1,71 @@
+//! Key state available to shaders as the `keys` uniform array.
+use glium::glutin::event::VirtualKeyCode;
+
+/// The amount of keys in the state, the length of the `keys` array.
+pub const KEY_COUNT: usize = 256;
+
+/// Returns the index of a key in the state.
+///
+/// Indices follow the JavaScript key codes used by Shadertoy, so letters and digits are at their
+/// uppercase ASCII value. Returns `None` for keys without a code.
+pub fn key_code(key: VirtualKeyCode) -> Option<usize> {
+    use VirtualKeyCode::*;
+
+    const LETTERS: [VirtualKeyCode; 26] = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    const DIGITS: [VirtualKeyCode; 10] =
+        [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+    const NUMPAD: [VirtualKeyCode; 10] = [
+        Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    ];
+    const FUNCTION: [VirtualKeyCode; 12] = [F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12];
+
+    if let Some(idx) = LETTERS.iter().position(|x| *x == key) {
+        return Some(65 + idx);
+    }
+    if let Some(idx) = DIGITS.iter().position(|x| *x == key) {
+        return Some(48 + idx);
+    }
+    if let Some(idx) = NUMPAD.iter().position(|x| *x == key) {
+        return Some(96 + idx);
+    }
+    if let Some(idx) = FUNCTION.iter().position(|x| *x == key) {
+        return Some(112 + idx);
+    }
+    let code = match key {
+        Back => 8,
+        Tab => 9,
+        Return | NumpadEnter => 13,
+        LShift | RShift => 16,
+        LControl | RControl => 17,
+        LAlt | RAlt => 18,
+        Pause => 19,
+        Capital => 20,
+        Escape => 27,
+        Space => 32,
+        PageUp => 33,
+        PageDown => 34,
+        End => 35,
+        Home => 36,
+        Left => 37,
+        Up => 38,
+        Right => 39,
+        Down => 40,
+        Insert => 45,
+        Delete => 46,
+        Semicolon => 186,
+        Equals => 187,
+        Comma => 188,
+        Minus => 189,
+        Period => 190,
+        Slash => 191,
+        Grave => 192,
+        LBracket => 219,
+        Backslash => 220,
+        RBracket => 221,
+        Apostrophe => 222,
+        _ => return None,
+    };
+    Some(code)
+}
@@ -0,0 +1,119 @@
+//! Loading of Adobe `.cube` color lookup tables.
+use anyhow::{Context, Result};
+use glium::{
+    texture::{MipmapsOption, Texture3d, UncompressedFloatFormat},
+    Display,
+};
+use std::{fs, path::Path};
+
+/// The largest LUT size allowed by the `.cube` specification.
+const MAX_SIZE: usize = 256;
+
+#[derive(Debug)]
+pub struct Lut {
+    pub size: usize,
+    pub domain_min: [f32; 3],
+    pub domain_max: [f32; 3],
+    /// The table with red changing fastest and blue slowest.
+    pub data: Vec<(f32, f32, f32)>,
+}
+
+fn parse_triple<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<[f32; 3]> {
+    let mut res = [0.0; 3];
+    for x in res.iter_mut() {
+        let token = tokens.next().context("Expected 3 numbers")?;
+        *x = token
+            .parse()
+            .with_context(|| format!("Invalid number `{}`", token))?;
+    }
+    ensure!(tokens.next().is_none(), "Expected 3 numbers");
+    Ok(res)
+}
+
+impl Lut {
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut data = Vec::new();
+
+        for (line_idx, line) in source.lines().enumerate() {
+            let res: Result<()> = (|| {
+                let line = line.split('#').next().unwrap_or("").trim();
+                let mut tokens = line.split_whitespace();
+                match tokens.next() {
+                    None | Some("TITLE") => {}
+                    Some("LUT_1D_SIZE") => bail!("1D LUTs are not supported"),
+                    Some("LUT_3D_SIZE") => {
+                        let token = tokens.next().context("Missing LUT size")?;
+                        let x: usize = token
+                            .parse()
+                            .with_context(|| format!("Invalid LUT size `{}`", token))?;
+                        ensure!(
+                            (2..=MAX_SIZE).contains(&x),
+                            "LUT size must be between 2 and {} found {}",
+                            MAX_SIZE,
+                            x
+                        );
+                        size = Some(x);
+                    }
+                    Some("DOMAIN_MIN") => domain_min = parse_triple(tokens)?,
+                    Some("DOMAIN_MAX") => domain_max = parse_triple(tokens)?,
+                    Some(x)
+                        if x.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.') =>
+                    {
+                        let [r, g, b] = parse_triple(line.split_whitespace())?;
+                        data.push((r, g, b));
+                    }
+                    Some(x) => bail!("Unknown keyword `{}`", x),
+                }
+                Ok(())
+            })();
+            res.with_context(|| format!("Error on line {}", line_idx + 1))?;
+        }
+
+        let size = size.context("Missing `LUT_3D_SIZE`")?;
+        for i in 0..3 {
+            ensure!(
+                domain_min[i] < domain_max[i],
+                "`DOMAIN_MIN` must be smaller than `DOMAIN_MAX`"
+            );
+        }
+        ensure!(
+            data.len() == size * size * size,
+            "Expected {} table entries for a LUT of size {} found {}",
+            size * size * size,
+            size,
+            data.len()
+        );
+
+        Ok(Lut {
+            size,
+            domain_min,
+            domain_max,
+            data,
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read LUT `{}`", path.display()))?;
+        Self::parse(&source).with_context(|| format!("Failed to parse LUT `{}`", path.display()))
+    }
+
+    pub fn to_texture(&self, display: &Display) -> Result<Texture3d> {
+        let data: Vec<Vec<Vec<(f32, f32, f32)>>> = self
+            .data
+            .chunks(self.size * self.size)
+            .map(|layer| layer.chunks(self.size).map(|row| row.to_vec()).collect())
+            .collect();
+        Texture3d::with_format(
+            display,
+            data,
+            UncompressedFloatFormat::F32F32F32,
+            MipmapsOption::NoMipmap,
+        )
+        .context("failed to create LUT texture")
+    }
+}
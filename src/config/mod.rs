@@ -1,80 +1,343 @@
-use crate::render::Vertex;
+use crate::{
+    geom,
+    render::{Tangent, Vertex},
+    util::Rng,
+};
 use anyhow::{Context, Result};
 use glam::f32::{Mat4, Quat, Vec2, Vec3};
 use glium::glutin::event::DeviceEvent;
 use glium::{
-    glutin::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent},
-    Display, IndexBuffer, VertexBuffer,
+    glutin::event::{
+        ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+    },
+    index::PrimitiveType,
+    texture::Texture2d,
+    Display, IndexBuffer, Rect, VertexBuffer,
+};
+use std::{
+    cell::{Cell, RefCell},
+    path::PathBuf,
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
-use std::time::Instant;
-use std::{collections::HashMap, ffi::OsStr, fmt::Write, fs::File, io::Read, path::Path};
+use std::{collections::HashMap, fmt::Write, fs::File, io::Read, path::Path};
 
 use self::ser::CameraKind;
+pub use self::ser::Tonemap;
 
 mod ser;
 mod texture;
-use texture::LoadedTexture;
+pub use texture::LoadedTexture;
+use texture::TexturePool;
+mod binary;
+mod buffer;
+mod flying;
+use flying::FlyingCamera;
+mod gltf;
+mod grading;
+mod keyboard;
+use keyboard::KEY_COUNT;
+mod graph;
+use grading::ColorGrading;
+mod fxaa;
+use fxaa::Fxaa;
+mod tonemap;
+use tonemap::Tonemapping;
+mod clock;
+pub use clock::LoadedClock;
+mod lut;
+mod model;
+use buffer::LoadedBuffer;
 mod pass;
-pub use pass::{BuiltinUniform, CustomUniform, LoadedPass, UniformBinding, UniformData};
+pub use pass::{
+    BuiltinUniform, CustomUniform, LoadedPass, UniformBinding, UniformData, UniformRange,
+};
+mod picking;
+mod pragma;
+use pragma::UniformPragma;
+mod recorder;
+mod save;
+pub use recorder::component_count;
+use recorder::Recorder;
 mod render;
+use picking::Picker;
 
 #[derive(Debug)]
 pub struct Shader {
     source: String,
+    /// Metadata of uniforms declared with `#pragma uniform`.
+    pragmas: HashMap<String, UniformPragma>,
 }
 
 impl Shader {
-    fn load(path: impl AsRef<Path>) -> Result<Self> {
-        let path = path.as_ref();
-        let mut source = String::new();
-        let mut file = File::open(path)?;
-        file.read_to_string(&mut source)?;
-        Ok(Shader { source })
+    fn load(source: &ser::ShaderSource) -> Result<Self> {
+        let mut source = match source {
+            ser::ShaderSource::Path(path) => {
+                let mut source = String::new();
+                let mut file = File::open(path)?;
+                file.read_to_string(&mut source)?;
+                source
+            }
+            ser::ShaderSource::Inline { source } => source.clone(),
+        };
+        let pragmas = pragma::extract(&mut source);
+        Ok(Shader { source, pragmas })
+    }
+
+    /// Returns the type and name of all output variables declared in the shader source.
+    pub fn outputs(&self) -> Vec<(&str, &str)> {
+        self.source
+            .split(';')
+            .filter_map(|decl| {
+                let mut tokens = decl.split_whitespace().skip_while(|x| *x != "out").skip(1);
+                Some((tokens.next()?, tokens.next()?))
+            })
+            .collect()
+    }
+
+    /// Returns the type of an output variable as declared in the shader source.
+    pub fn output_type(&self, name: &str) -> Option<&str> {
+        self.outputs()
+            .into_iter()
+            .find_map(|(ty, x)| (x == name).then_some(ty))
     }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum LoadedCamera {
-    LookAt { from: Vec3, to: Vec3, up: Vec3 },
-    Orbital { state: Vec2, distance: f32 },
+    LookAt {
+        from: Vec3,
+        to: Vec3,
+        up: Vec3,
+    },
+    Orbital {
+        state: Vec2,
+        /// Rotation from trackball movement, applied in view space after the yaw and pitch.
+        trackball: Quat,
+        distance: f32,
+        center: Vec3,
+    },
+    Flying(FlyingCamera),
+}
+
+impl LoadedCamera {
+    /// Returns the rotation of an orbital camera from its accumulated mouse movement.
+    pub fn orbital_rotation(state: Vec2, trackball: Quat) -> Quat {
+        let rotation_y = Quat::from_rotation_y(state.x * 0.01);
+        let rotation_x = Quat::from_axis_angle(rotation_y * Vec3::X, -state.y * 0.01);
+        (rotation_x * rotation_y * trackball).normalize()
+    }
+
+    /// Returns the view space rotation of a trackball dragged by the given movement, in the same
+    /// units as the orbital state.
+    ///
+    /// The rotation is around the axis in the view plane perpendicular to the movement, so
+    /// dragging straight sideways or up and down turns the view the same way as the yaw and pitch.
+    pub fn trackball_rotation(delta: Vec2) -> Quat {
+        let axis = Vec3::new(-delta.y, delta.x, 0.0);
+        let angle = axis.length() * 0.01;
+        if angle == 0.0 {
+            return Quat::IDENTITY;
+        }
+        Quat::from_axis_angle(axis / axis.length(), angle)
+    }
+
+    /// Returns how far the orbital camera state has been advanced by automatic rotation after the
+    /// given amount of seconds.
+    fn auto_rotation(camera: &ser::Camera, time: f32) -> Vec2 {
+        // The state rotates the camera by 0.01 radians per unit.
+        let x = camera
+            .auto_rotate
+            .map(|x| x.to_radians() * time / 0.01)
+            .unwrap_or(0.0);
+        Vec2::new(x, 0.0)
+    }
+}
+
+/// The position, rotation in degrees and scale of an object.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub position: Vec3,
+    pub rotation: Vec3,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    pub fn rotation_scale_matrix(&self) -> Mat4 {
+        let rot = Quat::from_rotation_ypr(
+            self.rotation.x.to_radians(),
+            self.rotation.y.to_radians(),
+            self.rotation.z.to_radians(),
+        );
+        Mat4::from_quat(rot) * Mat4::from_scale(self.scale)
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        self.rotation_scale_matrix() * Mat4::from_translation(self.position)
+    }
 }
 
 #[derive(Debug)]
 pub struct LoadedObject {
+    pub name: String,
     vertex: VertexBuffer<Vertex>,
+    /// Tangents of the vertices if enabled for the object.
+    tangent: Option<VertexBuffer<Tangent>>,
     index: IndexBuffer<u32>,
+    /// The unique edges of the triangles, used when a pass draws the object as lines.
+    edges: IndexBuffer<u32>,
+    front_face: Option<ser::FrontFace>,
+    transparent: bool,
+    usage: geom::BufferUsage,
+    transform: Transform,
     matrix: Mat4,
 }
 
+impl LoadedObject {
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+        self.matrix = transform.matrix();
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        self.matrix
+    }
+
+    /// Replace the vertices of the object, the amount of vertices can't change.
+    ///
+    /// Only possible for objects with a `dynamic` or `persistent` buffer usage. Tangents are not
+    /// recalculated.
+    pub fn update_vertices(&mut self, vertices: &[Vertex]) -> Result<()> {
+        ensure!(
+            self.usage != geom::BufferUsage::Immutable,
+            "Vertices of object `{}` can't be updated, set its `usage` to `dynamic` or `persistent`",
+            self.name
+        );
+        ensure!(
+            vertices.len() == self.vertex.len(),
+            "Object `{}` has {} vertices but {} were given",
+            self.name,
+            self.vertex.len(),
+            vertices.len()
+        );
+        self.vertex.write(vertices);
+        Ok(())
+    }
+
+    /// The amount of vertices uploaded for the object.
+    pub fn vertex_count(&self) -> usize {
+        self.vertex.len()
+    }
+
+    /// The amount of indices uploaded for the object.
+    pub fn index_count(&self) -> usize {
+        self.index.len()
+    }
+}
+
 #[derive(Debug)]
 pub struct LoadedTarget {
     color: Vec<(usize, String)>,
     depth: Option<usize>,
+    /// The part of the target textures the pass renders to.
+    region: Option<Rect>,
+    clear_color: [f32; 4],
+    /// Depth the target is cleared to, the far plane if not set.
+    clear_depth: Option<f32>,
+    /// Whether to clear the target before the pass.
+    clear: bool,
+}
+
+impl LoadedTarget {
+    /// Check that the region fits within all target textures.
+    fn check_region(&self, textures: &[LoadedTexture]) -> Result<()> {
+        let region = match self.region {
+            Some(ref x) => x,
+            None => return Ok(()),
+        };
+        ensure!(
+            region.width > 0 && region.height > 0,
+            "Pass target region must not be empty"
+        );
+        for texture in self.color.iter().map(|x| x.0).chain(self.depth) {
+            let (width, height) = textures[texture].dimensions();
+            ensure!(
+                region.left + region.width <= width && region.bottom + region.height <= height,
+                "Pass target region of {}x{} at ({}, {}) does not fit in a target texture of {}x{}",
+                region.width,
+                region.height,
+                region.left,
+                region.bottom,
+                width,
+                height
+            );
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
 pub struct Config {
     mouse_pressed: bool,
+    /// Whether the middle mouse button is held for panning the orbital camera.
+    pan_pressed: bool,
+    /// Whether input which would move the camera should be ignored.
+    pub camera_locked: bool,
     config: ser::Config,
     camera: LoadedCamera,
     pub objects: Vec<LoadedObject>,
     pub textures: Vec<LoadedTexture>,
+    pub buffers: Vec<LoadedBuffer>,
     pub passes: Vec<LoadedPass>,
+    pub clocks: Vec<LoadedClock>,
+    picker: Option<Picker>,
+    pub grading: Option<ColorGrading>,
+    pub fxaa: Fxaa,
+    pub tonemap: Tonemapping,
+    /// The object last clicked on if picking is enabled.
+    pub selected_object: Option<usize>,
+    /// Time since the first config was loaded, kept across reloads.
     start_time: Instant,
+    /// Time at which this config was loaded.
+    load_time: Instant,
+    /// Time at which the last frame was rendered.
+    last_frame: Cell<Instant>,
+    /// The amount of frames rendered since the first config was loaded.
+    frame_count: Cell<i32>,
+    /// The recording of uniform values in progress, if any.
+    recorder: RefCell<Option<Recorder>>,
     mouse_pos: Vec2,
+    /// Whether the left mouse button is held, regardless of whether the camera is locked.
+    mouse_down: bool,
+    /// Position of the cursor when the left mouse button was last pressed.
+    mouse_click: Vec2,
+    /// Position of the cursor while the left mouse button was last held.
+    mouse_drag: Vec2,
+    /// Whether the left mouse button was pressed since the last frame.
+    mouse_clicked: Cell<bool>,
+    /// Accumulated scroll wheel movement not used by the camera.
+    scroll: f32,
+    /// Random number between 0 and 1 chosen when the config is loaded.
+    seed: f32,
+    /// Which keys are held, indexed by [`keyboard::key_code`].
+    keys: [bool; KEY_COUNT],
+    /// Copies of the textures which are both sampled and rendered to by a pass, made before
+    /// every iteration of the pass.
+    feedback_textures: HashMap<usize, Texture2d>,
     window_size: Vec2,
     display: Display,
 }
 
 impl Config {
-    pub fn load(path: impl AsRef<Path>, display: &Display) -> Result<Self> {
-        let path = path.as_ref();
-        let file = File::open(path).context("could not find config file")?;
-        let config: ser::Config = match path.extension().and_then(OsStr::to_str) {
-            Some("ron") => ron::de::from_reader(file).context("Failed to parse config file")?,
-            Some("json") => serde_json::from_reader(file).context("Failed to parse config file")?,
-            _ => bail!("Invalid config extension!"),
-        };
+    /// Load a config from a file.
+    ///
+    /// If a previous config is given, textures which did not change are reused.
+    pub fn load(path: impl AsRef<Path>, display: &Display, old: Option<&Config>) -> Result<Self> {
+        let config = ser::Config::load(path.as_ref())?;
+        let base = path.as_ref().parent().unwrap_or_else(|| Path::new("."));
 
         let mut object_name_match = HashMap::new();
 
@@ -84,11 +347,14 @@ impl Config {
             .enumerate()
             .try_fold::<_, _, Result<_>>(Vec::new(), |mut acc, (idx, x)| {
                 object_name_match.insert(x.name.clone(), idx);
-                acc.push(Self::load_object(x, display)?);
+                acc.push(Self::load_object(x, base, display)?);
                 Result::Ok(acc)
             })?;
 
         let mut texture_name_match = HashMap::new();
+        let pool = old
+            .map(|x| TexturePool::new(&x.textures))
+            .unwrap_or_default();
 
         let textures = config
             .textures
@@ -96,63 +362,312 @@ impl Config {
             .enumerate()
             .try_fold::<_, _, Result<_>>(Vec::new(), |mut acc, (idx, x)| {
                 texture_name_match.insert(x.name.clone(), idx);
-                acc.push(LoadedTexture::load(x, display)?);
+                let x = x
+                    .resolve_sampler(&config.samplers)
+                    .with_context(|| format!("Error loading texture `{}`", x.name))?;
+                acc.push(pool.load(&x, display)?);
                 Result::Ok(acc)
             })?;
 
+        let mut buffer_name_match = HashMap::new();
+
+        let buffers = config
+            .buffers
+            .iter()
+            .enumerate()
+            .try_fold::<_, _, Result<_>>(Vec::new(), |mut acc, (idx, x)| {
+                buffer_name_match.insert(x.name.clone(), idx);
+                acc.push(LoadedBuffer::load(x, display)?);
+                Result::Ok(acc)
+            })?;
+
+        // Sorted by name so the clocks are always listed in the same order.
+        let mut clocks: Vec<_> = config
+            .clocks
+            .iter()
+            .map(|(name, x)| LoadedClock::new(name.clone(), x))
+            .collect();
+        clocks.sort_by(|a, b| a.name.cmp(&b.name));
+
         let passes = config
             .passes
             .iter()
             .enumerate()
             .try_fold::<_, _, Result<_>>(Vec::new(), |mut acc, (idx, x)| {
                 acc.push(
-                    Self::load_pass2(x, &object_name_match, &texture_name_match, display)
-                        .with_context(|| format!("Error loading pass `{}`", idx))?,
+                    Self::load_pass2(
+                        x,
+                        &object_name_match,
+                        &objects,
+                        &textures,
+                        &buffer_name_match,
+                        config.reverse_z,
+                        display,
+                    )
+                    .and_then(|mut pass| {
+                        if let Some(ref target) = pass.target {
+                            target.check_region(&textures)?;
+                        }
+                        pass.clock = x
+                            .clock
+                            .as_ref()
+                            .map(|name| Self::link_clock(name, &clocks))
+                            .transpose()?;
+                        Ok(pass)
+                    })
+                    .with_context(|| format!("Error loading pass `{}`", idx))?,
                 );
                 Result::Ok(acc)
             })?;
 
-        let camera = match config.camera.kind {
-            CameraKind::Lookat { from, to, up } => LoadedCamera::LookAt { from, to, up },
-            CameraKind::Orbital { distance, .. } => LoadedCamera::Orbital {
-                state: Vec2::ZERO,
-                distance,
-            },
+        let grading = config
+            .grading
+            .as_ref()
+            .map(|x| ColorGrading::load(x, &texture_name_match, &textures, display))
+            .transpose()
+            .context("Error loading color grading")?;
+        let fxaa = Fxaa::load(config.fxaa, display)?;
+        let tonemap = Tonemapping::load(config.tonemap, config.exposure, display)?;
+
+        let picker = if config.picking {
+            Some(Picker::new(display)?)
+        } else {
+            None
         };
 
+        let camera = Self::load_camera(&config.camera);
+
         debug!("reloaded config: {:#?}", &config);
 
         let (window_width, window_height) = display.get_framebuffer_dimensions();
 
-        Ok(Config {
+        let mut res = Config {
             mouse_pressed: false,
+            pan_pressed: false,
+            camera_locked: false,
             config,
             objects,
             textures,
+            buffers,
             passes,
+            clocks,
+            picker,
+            grading,
+            fxaa,
+            tonemap,
+            selected_object: None,
             start_time: Instant::now(),
+            load_time: Instant::now(),
+            last_frame: Cell::new(Instant::now()),
+            frame_count: Cell::new(0),
+            recorder: RefCell::new(None),
             camera,
             display: display.clone(),
             mouse_pos: Vec2::ZERO,
+            mouse_down: false,
+            mouse_click: Vec2::ZERO,
+            mouse_drag: Vec2::ZERO,
+            mouse_clicked: Cell::new(false),
+            scroll: 0.0,
+            seed: Rng::from_time().next_f32(),
+            keys: [false; KEY_COUNT],
+            feedback_textures: HashMap::new(),
             window_size: Vec2::new(window_width as f32, window_height as f32),
-        })
+        };
+        res.feedback_textures = res.create_feedback_textures()?;
+        Ok(res)
+    }
+
+    fn load_camera(camera: &ser::Camera) -> LoadedCamera {
+        match camera.kind {
+            CameraKind::Lookat { from, to, up } => LoadedCamera::LookAt { from, to, up },
+            CameraKind::Orbital {
+                distance, center, ..
+            } => LoadedCamera::Orbital {
+                state: Vec2::ZERO,
+                trackball: Quat::IDENTITY,
+                distance,
+                center,
+            },
+            CameraKind::Flying {
+                position,
+                yaw,
+                pitch,
+                speed,
+            } => LoadedCamera::Flying(FlyingCamera::new(position, yaw, pitch, speed)),
+        }
+    }
+
+    /// Reload the config file in place if only the camera changed, keeping the rest of the loaded
+    /// state intact.
+    ///
+    /// Returns `false` if anything other than the camera changed in which case the config needs to
+    /// be fully reloaded.
+    pub fn reload_camera(&mut self, path: impl AsRef<Path>) -> Result<bool> {
+        let mut config = ser::Config::load(path.as_ref())?;
+        if !self.config.eq_except_camera(&mut config) {
+            return Ok(false);
+        }
+
+        let time = self.start_time.elapsed().as_secs_f32();
+        let mut camera = Self::load_camera(&config.camera);
+        if let (
+            LoadedCamera::Orbital {
+                ref mut state,
+                ref mut trackball,
+                ref mut distance,
+                ref mut center,
+            },
+            LoadedCamera::Orbital {
+                state: old_state,
+                trackball: old_trackball,
+                distance: old_distance,
+                center: old_center,
+            },
+        ) = (&mut camera, self.camera)
+        {
+            // Keep the view in place if the rotation speed changed.
+            *state = old_state + LoadedCamera::auto_rotation(&self.config.camera, time)
+                - LoadedCamera::auto_rotation(&config.camera, time);
+            *trackball = old_trackball;
+            // Only keep the current zoom and pan if the configured values did not change.
+            if let (
+                CameraKind::Orbital {
+                    distance: new_distance,
+                    center: new_center,
+                    ..
+                },
+                CameraKind::Orbital {
+                    distance: prev_distance,
+                    center: prev_center,
+                    ..
+                },
+            ) = (&config.camera.kind, &self.config.camera.kind)
+            {
+                if new_distance == prev_distance {
+                    *distance = old_distance;
+                }
+                if new_center == prev_center {
+                    *center = old_center;
+                }
+            }
+        }
+
+        // Keep flying around from the current position unless the camera was moved in the config.
+        if let LoadedCamera::Flying(ref mut x) = self.camera {
+            if config.camera.kind == self.config.camera.kind {
+                x.release_keys();
+                camera = self.camera;
+            }
+        }
+
+        self.camera = camera;
+        self.config.camera = config.camera;
+        Ok(true)
+    }
+
+    /// Read back all storage buffers and write their contents to the log.
+    pub fn dump_buffers(&self) -> Result<()> {
+        for buffer in self.buffers.iter() {
+            info!("buffer `{}`: {:?}", buffer.config.name, buffer.read()?);
+        }
+        Ok(())
+    }
+
+    /// Pick a new value for the `seed` uniform.
+    pub fn reroll_seed(&mut self) {
+        self.seed = Rng::from_time().next_f32();
     }
 
     pub fn copy_state(&mut self, other: &Self) {
+        self.camera_locked = other.camera_locked;
+        self.start_time = other.start_time;
+        self.last_frame.set(other.last_frame.get());
+        self.frame_count.set(other.frame_count.get());
+        self.keys = other.keys;
+        self.scroll = self.config.scroll.clamp(other.scroll);
+        self.mouse_click = other.mouse_click;
+        self.mouse_drag = other.mouse_drag;
+        // Keep recording into the same file, uniforms are looked up by name in the new passes.
+        *self.recorder.get_mut() = other.recorder.take();
+        if let (Some(grading), Some(other)) = (self.grading.as_mut(), other.grading.as_ref()) {
+            grading.enabled = other.enabled;
+            grading.intensity = other.intensity;
+        }
+        self.fxaa.enabled = other.fxaa.enabled;
+        if self.config.tonemap == other.config.tonemap
+            && self.config.exposure == other.config.exposure
+        {
+            self.tonemap.operator = other.tonemap.operator;
+            self.tonemap.exposure = other.tonemap.exposure;
+        }
+        for clock in self.clocks.iter_mut() {
+            let same_config =
+                self.config.clocks.get(&clock.name) == other.config.clocks.get(&clock.name);
+            if let Some(x) = other.clocks.iter().find(|x| x.name == clock.name) {
+                if same_config {
+                    *clock = x.clone();
+                }
+            }
+        }
+        if self.picker.is_some() && self.objects.len() == other.objects.len() {
+            self.selected_object = other.selected_object;
+        }
         match (&mut self.camera, &other.camera) {
             (
-                LoadedCamera::Orbital { ref mut state, .. },
+                LoadedCamera::Orbital {
+                    ref mut state,
+                    ref mut trackball,
+                    ref mut center,
+                    ..
+                },
                 LoadedCamera::Orbital {
                     state: ref src_state,
+                    trackball: ref src_trackball,
+                    center: ref src_center,
                     ..
                 },
             ) => {
-                *state = *src_state;
+                *trackball = *src_trackball;
+                let time = self.start_time.elapsed().as_secs_f32();
+                *state = *src_state + LoadedCamera::auto_rotation(&other.config.camera, time)
+                    - LoadedCamera::auto_rotation(&self.config.camera, time);
+                if let (
+                    CameraKind::Orbital { center: new, .. },
+                    CameraKind::Orbital { center: old, .. },
+                ) = (&self.config.camera.kind, &other.config.camera.kind)
+                {
+                    if new == old {
+                        *center = *src_center;
+                    }
+                }
+            }
+            (LoadedCamera::Flying(ref mut x), LoadedCamera::Flying(ref src)) => {
+                *x = *src;
+                x.release_keys();
             }
             _ => {}
         }
     }
 
+    fn link_clock(name: &str, clocks: &[LoadedClock]) -> Result<usize> {
+        if let Some(x) = clocks.iter().position(|x| x.name == name) {
+            Ok(x)
+        } else {
+            let mut expects = String::new();
+            write!(expects, "Expected one of ").unwrap();
+            for (idx, k) in clocks.iter().enumerate() {
+                if idx != 0 {
+                    write!(expects, ",").unwrap();
+                }
+                write!(expects, "`{}`", k.name).unwrap();
+            }
+            write!(expects, ".").unwrap();
+
+            bail!("Could not find clock `{}`. {}", name, expects)
+        }
+    }
+
     fn link_texture(
         texture: &ser::TextureRef,
         texture_name_match: &HashMap<String, usize>,
@@ -195,87 +710,360 @@ impl Config {
         };
     }
 
-    pub fn load_object(object: &ser::Object, display: &Display) -> Result<LoadedObject> {
-        let rot = Quat::from_rotation_ypr(
-            object.rotation.x.to_radians(),
-            object.rotation.y.to_radians(),
-            object.rotation.z.to_radians(),
-        );
-        let mat = Mat4::from_quat(rot)
-            * Mat4::from_scale(object.scale)
-            * Mat4::from_translation(object.position);
-        let geom = match object.kind {
-            ser::ObjectKind::Geometry(ref x) => x
-                .to_buffers(display)
-                .context("Failed to load model geometry")?,
+    /// Load an object, paths to models are resolved relative to the given directory.
+    pub fn load_object(
+        object: &ser::Object,
+        base: &Path,
+        display: &Display,
+    ) -> Result<LoadedObject> {
+        let transform = Transform {
+            position: object.position,
+            rotation: object.rotation,
+            scale: object.scale,
         };
+        let mut mesh = match object.kind {
+            ser::ObjectKind::Geometry(ref x) => {
+                x.mesh().context("Failed to load model geometry")?
+            }
+            ser::ObjectKind::Model { ref path } => model::load(&base.join(path))?,
+            ser::ObjectKind::Gltf { ref path, ref mesh } => {
+                gltf::load(&base.join(path), mesh.as_deref())?
+            }
+            ser::ObjectKind::Binary {
+                ref vertices,
+                ref indices,
+                ref format,
+            } => binary::load(&base.join(vertices), &base.join(indices), format)
+                .context("Failed to load binary mesh")?,
+        };
+        // Imported models can have faces which are inside out, which shows up once culling is
+        // enabled.
+        if !matches!(object.kind, ser::ObjectKind::Geometry(_)) {
+            if object.fix_winding {
+                let count = geom::fix_winding(&mut mesh);
+                if count > 0 {
+                    info!("flipped {} faces of object `{}`", count, object.name);
+                }
+            } else {
+                let count = geom::count_wrongly_wound(&mesh);
+                if count > 0 {
+                    warn!(
+                        "{} of {} faces of object `{}` are wound opposite to their normals, set `fix_winding` to flip them",
+                        count,
+                        mesh.1.len() / 3,
+                        object.name
+                    );
+                }
+            }
+        }
+        if let Some(shading) = object.shading {
+            mesh = shading.apply(mesh);
+        }
+        let tangent = if object.tangents {
+            let tangents = geom::tangents(&mesh);
+            Some(VertexBuffer::immutable(display, &tangents)?)
+        } else {
+            None
+        };
+        let geom = geom::upload(&mesh, object.usage, display)?;
+        let edges = IndexBuffer::immutable(display, PrimitiveType::LinesList, &geom::edges(&mesh))?;
         Ok(LoadedObject {
-            matrix: mat,
+            name: object.name.clone(),
+            transform,
+            matrix: transform.matrix(),
             vertex: geom.0,
+            tangent,
             index: geom.1,
+            edges,
+            front_face: object.front_face,
+            transparent: object.transparent,
+            usage: object.usage,
+        })
+    }
+
+    /// Write the current transform of an object back into the config.
+    pub fn save_object_transform(&mut self, object: usize) {
+        let transform = self.objects[object].transform;
+        let object = &mut self.config.objects[object];
+        object.position = transform.position;
+        object.rotation = transform.rotation;
+        object.scale = transform.scale;
+    }
+
+    /// Discard changes made to the transform of an object since it was loaded or saved.
+    pub fn reset_object_transform(&mut self, object: usize) {
+        let config = &self.config.objects[object];
+        self.objects[object].set_transform(Transform {
+            position: config.position,
+            rotation: config.rotation,
+            scale: config.scale,
+        });
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.borrow().is_some()
+    }
+
+    /// Returns the file the recording in progress is written to.
+    pub fn recording_path(&self) -> Option<PathBuf> {
+        self.recorder
+            .borrow()
+            .as_ref()
+            .map(|x| x.path().to_path_buf())
+    }
+
+    /// Start writing the values of the uniforms marked for recording to a CSV file in the current
+    /// directory every frame.
+    pub fn start_recording(&mut self) -> Result<()> {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or(0);
+        let path = PathBuf::from(format!("uniforms_{}.csv", secs));
+        let recorder = Recorder::new(&path, &self.passes)?;
+        info!("recording uniforms to `{}`", path.display());
+        *self.recorder.get_mut() = Some(recorder);
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) -> Result<()> {
+        if let Some(x) = self.recorder.get_mut().take() {
+            let path = x.path().to_path_buf();
+            x.finish()?;
+            info!("finished recording `{}`", path.display());
+        }
+        Ok(())
+    }
+
+    /// Returns whether any pass binds the `keys` builtin.
+    fn reads_keys(&self) -> bool {
+        self.passes.iter().any(|pass| {
+            pass.uniforms
+                .values()
+                .any(|x| matches!(x.binding, UniformBinding::Builtin(BuiltinUniform::Keyboard)))
         })
     }
 
+    /// Move the camera back to where it was configured, the config file is not read again.
+    pub fn reset_camera(&mut self) {
+        self.camera = Self::load_camera(&self.config.camera);
+        self.display.gl_window().window().request_redraw();
+    }
+
+    /// Select the object under the cursor if picking is enabled.
+    fn pick(&mut self) {
+        let picker = match self.picker {
+            Some(ref x) => x,
+            None => return,
+        };
+        let view = self.get_camera_matrix();
+        let projection = self.get_perspective_matrix();
+        let pos = (self.mouse_pos.x as u32, self.mouse_pos.y as u32);
+        match picker.pick(
+            &self.display,
+            &self.objects,
+            view,
+            projection,
+            self.config.reverse_z,
+            pos,
+        ) {
+            Ok(x) => {
+                if let Some(idx) = x {
+                    info!("picked object `{}`", self.objects[idx].name);
+                }
+                self.selected_object = x;
+            }
+            Err(e) => error!("{:?}", e),
+        }
+    }
+
     pub fn handle_window_event(&mut self, event: &WindowEvent) {
         match event {
             WindowEvent::MouseInput {
                 button: MouseButton::Left,
                 state,
                 ..
-            } => match state {
-                ElementState::Pressed => {
-                    self.display.gl_window().window().set_cursor_grab(true).ok();
-                    self.display.gl_window().window().set_cursor_visible(false);
-                    self.mouse_pressed = true;
+            } => {
+                self.mouse_down = *state == ElementState::Pressed;
+                if self.mouse_down {
+                    self.mouse_click = self.mouse_pos;
+                    self.mouse_drag = self.mouse_pos;
+                    self.mouse_clicked.set(true);
                 }
-                ElementState::Released => {
-                    self.display
-                        .gl_window()
-                        .window()
-                        .set_cursor_grab(false)
-                        .ok();
-                    self.display.gl_window().window().set_cursor_visible(true);
-                    self.mouse_pressed = false;
+                match state {
+                    ElementState::Pressed if self.camera_locked => self.pick(),
+                    ElementState::Pressed => {
+                        self.pick();
+                        self.display.gl_window().window().set_cursor_grab(true).ok();
+                        self.display.gl_window().window().set_cursor_visible(false);
+                        self.mouse_pressed = true;
+                    }
+                    ElementState::Released => {
+                        self.display
+                            .gl_window()
+                            .window()
+                            .set_cursor_grab(false)
+                            .ok();
+                        self.display.gl_window().window().set_cursor_visible(true);
+                        self.mouse_pressed = false;
+                    }
                 }
-            },
+            }
+            WindowEvent::MouseInput {
+                button: MouseButton::Middle,
+                state,
+                ..
+            } => {
+                self.pan_pressed = *state == ElementState::Pressed && !self.camera_locked;
+            }
             WindowEvent::MouseWheel { delta, .. } => {
                 let delta = match delta {
                     MouseScrollDelta::LineDelta(_, x) => *x,
                     MouseScrollDelta::PixelDelta(x) => x.y as f32 * 20.0,
                 };
+                // The scroll uniform only accumulates scrolling which doesn't move the camera.
+                if self.camera_locked || matches!(self.camera, LoadedCamera::LookAt { .. }) {
+                    self.scroll = self.config.scroll.clamp(self.scroll + delta);
+                    return;
+                }
+                let (min_distance, max_distance) = match self.config.camera.kind {
+                    CameraKind::Orbital {
+                        min_distance,
+                        max_distance,
+                        ..
+                    } => (min_distance, max_distance),
+                    _ => (0.0, f32::INFINITY),
+                };
 
                 match &mut self.camera {
                     LoadedCamera::Orbital {
                         ref mut distance, ..
                     } => {
                         self.display.gl_window().window().request_redraw();
-                        *distance = 0.0f32.max(*distance + delta);
+                        *distance = (*distance + delta).clamp(min_distance, max_distance);
                     }
-                    //ser::CameraKind::Flying { mut speed } => speed += delta,
+                    LoadedCamera::Flying(ref mut x) => x.scale_speed(delta),
                     _ => {}
                 }
             }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(key),
+                        state,
+                        ..
+                    },
+                ..
+            } => {
+                let pressed = *state == ElementState::Pressed;
+                if let Some(code) = keyboard::key_code(*key) {
+                    self.keys[code] = pressed;
+                }
+                if !self.camera_locked {
+                    // Shaders reading the keyboard might use R themselves.
+                    if *key == VirtualKeyCode::R && pressed && !self.reads_keys() {
+                        self.reset_camera();
+                    } else if let LoadedCamera::Flying(ref mut x) = self.camera {
+                        if x.set_key(*key, pressed) {
+                            self.display.gl_window().window().request_redraw();
+                        }
+                    }
+                }
+            }
+            WindowEvent::Focused(false) => {
+                self.keys = [false; KEY_COUNT];
+                if let LoadedCamera::Flying(ref mut x) = self.camera {
+                    x.release_keys();
+                }
+            }
             WindowEvent::CursorMoved { position, .. } => {
                 self.mouse_pos = Vec2::new(position.x as f32, position.y as f32);
+                if self.mouse_down {
+                    self.mouse_drag = self.mouse_pos;
+                }
             }
             WindowEvent::Resized(size) => {
                 let dimensions = (size.width, size.height);
                 for t in self.textures.iter_mut() {
                     t.resize(dimensions, &self.display).unwrap()
                 }
+                if let Some(picker) = self.picker.as_mut() {
+                    picker.resize(dimensions, &self.display).unwrap()
+                }
+                if let Some(grading) = self.grading.as_mut() {
+                    grading.resize(dimensions, &self.display).unwrap()
+                }
+                self.fxaa.resize(dimensions, &self.display).unwrap();
+                self.tonemap.resize(dimensions, &self.display).unwrap();
+                self.feedback_textures = self.create_feedback_textures().unwrap();
                 self.window_size = Vec2::new(size.width as f32, size.height as f32);
             }
             _ => {}
         }
     }
 
+    /// Returns the state of an orbital camera including its automatic rotation.
+    pub fn orbital_state(&self, state: Vec2) -> Vec2 {
+        state
+            + LoadedCamera::auto_rotation(
+                &self.config.camera,
+                self.start_time.elapsed().as_secs_f32(),
+            )
+    }
+
     pub fn handle_device_event(&mut self, event: &DeviceEvent) {
+        let auto_rotation = LoadedCamera::auto_rotation(
+            &self.config.camera,
+            self.start_time.elapsed().as_secs_f32(),
+        );
+        let y_sign = if self.config.camera.invert_y {
+            1.0
+        } else {
+            -1.0
+        };
         match event {
-            DeviceEvent::MouseMotion { delta } => match &mut self.camera {
-                LoadedCamera::Orbital { ref mut state, .. } => {
+            DeviceEvent::MouseMotion { delta } if !self.camera_locked => match &mut self.camera {
+                LoadedCamera::Orbital {
+                    ref mut state,
+                    ref mut trackball,
+                    distance,
+                    ref mut center,
+                } => {
+                    if self.mouse_pressed {
+                        self.display.gl_window().window().request_redraw();
+                        let sensitivity = self.config.camera.mouse_sensitivity * 0.1;
+                        let movement =
+                            Vec2::new(delta.0 as f32, y_sign * delta.1 as f32) * sensitivity;
+                        if matches!(
+                            self.config.camera.kind,
+                            CameraKind::Orbital {
+                                trackball: true,
+                                ..
+                            }
+                        ) {
+                            *trackball = (*trackball * LoadedCamera::trackball_rotation(movement))
+                                .normalize();
+                        } else {
+                            *state += movement;
+                        }
+                    } else if self.pan_pressed {
+                        self.display.gl_window().window().request_redraw();
+                        // Scale with the distance so the scene roughly follows the cursor.
+                        let rotation =
+                            LoadedCamera::orbital_rotation(*state + auto_rotation, *trackball);
+                        let scale = distance.max(0.1) * 0.002;
+                        *center -= rotation * Vec3::X * delta.0 as f32 * scale;
+                        *center += rotation * Vec3::Y * delta.1 as f32 * scale;
+                    }
+                }
+                LoadedCamera::Flying(ref mut x) => {
                     if self.mouse_pressed {
                         self.display.gl_window().window().request_redraw();
-                        *state += Vec2::new(delta.0 as f32, -delta.1 as f32);
+                        let sensitivity = self.config.camera.mouse_sensitivity * 0.02;
+                        x.look(
+                            delta.0 as f32 * sensitivity,
+                            y_sign * delta.1 as f32 * sensitivity,
+                        );
                     }
                 }
                 LoadedCamera::LookAt { .. } => {}
@@ -284,3 +1072,57 @@ impl Config {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::LoadedCamera;
+    use glam::f32::{Quat, Vec2, Vec3};
+
+    /// Drags right, up, left and down back to where the drag started.
+    fn drag_loop() -> [Vec2; 4] {
+        [
+            Vec2::new(100.0, 0.0),
+            Vec2::new(0.0, 100.0),
+            Vec2::new(-100.0, 0.0),
+            Vec2::new(0.0, -100.0),
+        ]
+    }
+
+    #[test]
+    fn trackball_zero_delta() {
+        assert_eq!(LoadedCamera::trackball_rotation(Vec2::ZERO), Quat::IDENTITY);
+    }
+
+    #[test]
+    fn trackball_axis_perpendicular_to_drag() {
+        for delta in [
+            Vec2::new(3.0, 4.0),
+            Vec2::new(-10.0, 0.0),
+            Vec2::new(0.0, 7.0),
+        ] {
+            let (axis, angle) = LoadedCamera::trackball_rotation(delta).to_axis_angle();
+            assert!(axis.z.abs() < 1e-5);
+            assert!(axis.dot(delta.extend(0.0)).abs() < 1e-4);
+            assert!((angle - delta.length() * 0.01).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn trackball_rolls_unlike_orbital() {
+        let trackball = drag_loop().iter().fold(Quat::IDENTITY, |acc, x| {
+            (acc * LoadedCamera::trackball_rotation(*x)).normalize()
+        });
+        // Dragging in a loop rolls the trackball around the view direction.
+        assert!((trackball * Vec3::X).y.abs() > 0.1);
+
+        // The orbital camera keeps its horizon level and returns to where it started.
+        let mut state = Vec2::ZERO;
+        for x in drag_loop() {
+            state += x;
+            let rotation = LoadedCamera::orbital_rotation(state, Quat::IDENTITY);
+            assert!((rotation * Vec3::X).y.abs() < 1e-5);
+        }
+        let orbital = LoadedCamera::orbital_rotation(state, Quat::IDENTITY);
+        assert!(orbital.abs_diff_eq(Quat::IDENTITY, 1e-5));
+    }
+}
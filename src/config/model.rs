@@ -0,0 +1,144 @@
+//! Loading of Wavefront OBJ models.
+use crate::{geom::Mesh, render::Vertex};
+use anyhow::{Context, Result};
+use glam::f32::Vec3;
+use std::{collections::HashMap, fs, path::Path, str::SplitWhitespace};
+
+/// Indices into the position, texture coordinate and normal lists of a face vertex.
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+#[derive(Default)]
+struct ObjFile {
+    positions: Vec<[f32; 3]>,
+    tex_coords: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+    faces: Vec<Vec<FaceVertex>>,
+}
+
+fn parse_floats<const N: usize>(tokens: SplitWhitespace) -> Result<[f32; N]> {
+    let mut res = [0.0; N];
+    let mut count = 0;
+    for (idx, token) in tokens.take(N).enumerate() {
+        res[idx] = token
+            .parse()
+            .with_context(|| format!("Invalid number `{}`", token))?;
+        count += 1;
+    }
+    ensure!(count == N, "Expected {} numbers found {}", N, count);
+    Ok(res)
+}
+
+/// Resolve an OBJ index, which starts at 1 and can be negative to index from the end of the list.
+fn parse_index(token: &str, len: usize) -> Result<usize> {
+    let idx: isize = token
+        .parse()
+        .with_context(|| format!("Invalid index `{}`", token))?;
+    let res = match idx {
+        0 => bail!("Invalid index `0`, indices start at 1"),
+        x if x > 0 => x as usize - 1,
+        x => len
+            .checked_sub(x.unsigned_abs())
+            .with_context(|| format!("Index `{}` out of range", x))?,
+    };
+    ensure!(res < len, "Index `{}` out of range", idx);
+    Ok(res)
+}
+
+fn parse_face_vertex(token: &str, mesh: &ObjFile) -> Result<FaceVertex> {
+    let mut parts = token.split('/');
+    let position = parse_index(parts.next().unwrap_or(""), mesh.positions.len())?;
+    let tex_coord = match parts.next() {
+        Some("") | None => None,
+        Some(x) => Some(parse_index(x, mesh.tex_coords.len())?),
+    };
+    let normal = match parts.next() {
+        Some("") | None => None,
+        Some(x) => Some(parse_index(x, mesh.normals.len())?),
+    };
+    Ok((position, tex_coord, normal))
+}
+
+fn parse(source: &str) -> Result<ObjFile> {
+    let mut mesh = ObjFile::default();
+    for (line_idx, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let res: Result<()> = (|| {
+            match tokens.next() {
+                Some("v") => mesh.positions.push(parse_floats(tokens)?),
+                Some("vt") => mesh.tex_coords.push(parse_floats(tokens)?),
+                Some("vn") => mesh.normals.push(parse_floats(tokens)?),
+                Some("f") => {
+                    let face = tokens
+                        .map(|x| parse_face_vertex(x, &mesh))
+                        .collect::<Result<Vec<_>>>()?;
+                    ensure!(
+                        face.len() >= 3,
+                        "Face must have at least 3 vertices found {}",
+                        face.len()
+                    );
+                    mesh.faces.push(face);
+                }
+                // Groups, materials and other statements do not affect the geometry.
+                _ => {}
+            }
+            Ok(())
+        })();
+        res.with_context(|| format!("Error on line {}", line_idx + 1))?;
+    }
+    Ok(mesh)
+}
+
+/// Load an OBJ model, faces are triangulated and missing normals are calculated from the faces.
+pub fn load(path: &Path) -> Result<Mesh> {
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read model `{}`", path.display()))?;
+    let mesh =
+        parse(&source).with_context(|| format!("Failed to parse model `{}`", path.display()))?;
+
+    let mut vertex_match = HashMap::new();
+    let mut verticies = Vec::new();
+    let mut vertex_positions = Vec::new();
+    let mut index = Vec::new();
+
+    for face in mesh.faces.iter() {
+        let face_index = face
+            .iter()
+            .map(|x| {
+                *vertex_match.entry(*x).or_insert_with(|| {
+                    verticies.push(Vertex {
+                        position: mesh.positions[x.0],
+                        normal: x.2.map(|x| mesh.normals[x]).unwrap_or([0.0; 3]),
+                        tex_coord: x.1.map(|x| mesh.tex_coords[x]).unwrap_or([0.0; 2]),
+                    });
+                    vertex_positions.push(x.0);
+                    verticies.len() as u32 - 1
+                })
+            })
+            .collect::<Vec<_>>();
+        for i in 1..face_index.len() - 1 {
+            index.extend_from_slice(&[face_index[0], face_index[i], face_index[i + 1]]);
+        }
+    }
+
+    // Calculate smooth normals for vertices without one by averaging the normals of the faces
+    // sharing the vertex position.
+    let missing_normals = vertex_match.keys().any(|x| x.2.is_none());
+    if missing_normals {
+        let mut normals = vec![Vec3::ZERO; mesh.positions.len()];
+        for triangle in index.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(verticies[triangle[i] as usize].position));
+            let normal = (b - a).cross(c - a);
+            for i in triangle {
+                normals[vertex_positions[*i as usize]] += normal;
+            }
+        }
+        for (key, idx) in vertex_match.iter() {
+            if key.2.is_none() {
+                verticies[*idx as usize].normal = normals[key.0].normalize_or_zero().into();
+            }
+        }
+    }
+
+    Ok((verticies, index))
+}
@@ -4,41 +4,124 @@ use anyhow::{Context, Result};
 use egui::Vec2;
 use glam::{Mat4, Vec3, Vec4};
 use glium::{
-    program::Uniform,
+    program::{ProgramCreationInput, Uniform},
     uniforms::{AsUniformValue, UniformType},
-    Display, DrawParameters, Program,
+    CapabilitiesSource, Display, DrawParameters, Program, Rect,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use super::{ser, Config, LoadedTarget, Shader};
+use super::{ser, Config, LoadedObject, LoadedTarget, LoadedTexture, Shader};
+use crate::util::Rng;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum BuiltinUniform {
     Model,
+    /// Inverse transpose of the model matrix without translation, for transforming normals.
+    NormalMatrix,
     View,
     Perspective,
+    /// The perspective matrix multiplied with the view matrix.
+    ViewProjection,
     Time,
+    TimeSinceReload,
+    DeltaTime,
+    /// Accumulated scroll wheel movement.
+    Scroll,
+    /// A random number which changes every time the config is loaded.
+    Seed,
+    /// The year, month, day and seconds of the current date.
+    Date,
+    SunDirection,
+    CameraPosition,
+    CameraDirection,
     MouseX,
     MouseY,
     MousePos,
+    /// The cursor position while the left button is held and the position of the last click,
+    /// following the `iMouse` uniform of Shadertoy.
+    MouseButtons,
     WindowWidth,
     WindowHeight,
     WindowSize,
+    InvWindowSize,
+    Iteration,
+    FrameCount,
+    InverseTargetSize,
+    /// Which keys are held, bound to a `bool` array.
+    Keyboard,
 }
 
 impl BuiltinUniform {
     pub fn label(&self) -> &'static str {
         match *self {
             BuiltinUniform::Model => "Model",
+            BuiltinUniform::NormalMatrix => "Normal Matrix",
             BuiltinUniform::View => "View",
             BuiltinUniform::Perspective => "Perspective",
+            BuiltinUniform::ViewProjection => "View Projection",
             BuiltinUniform::Time => "Time",
+            BuiltinUniform::TimeSinceReload => "Time Since Reload",
+            BuiltinUniform::DeltaTime => "Delta Time",
+            BuiltinUniform::Date => "Date",
+            BuiltinUniform::Scroll => "Scroll",
+            BuiltinUniform::Seed => "Seed",
+            BuiltinUniform::SunDirection => "Sun Direction",
+            BuiltinUniform::CameraPosition => "Camera Position",
+            BuiltinUniform::CameraDirection => "Camera Direction",
             BuiltinUniform::MouseX => "Mouse X",
             BuiltinUniform::MouseY => "Mouse Y",
             BuiltinUniform::MousePos => "Mouse Position",
+            BuiltinUniform::MouseButtons => "Mouse Buttons",
             BuiltinUniform::WindowWidth => "Window Width",
             BuiltinUniform::WindowHeight => "Window Height",
             BuiltinUniform::WindowSize => "Window Size",
+            BuiltinUniform::InvWindowSize => "Inverse Window Size",
+            BuiltinUniform::Iteration => "Iteration",
+            BuiltinUniform::FrameCount => "Frame Count",
+            BuiltinUniform::InverseTargetSize => "Inverse Target Size",
+            BuiltinUniform::Keyboard => "Keyboard",
+        }
+    }
+
+    /// Returns the name of the uniform the builtin is bound to by default.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            BuiltinUniform::Model => "model",
+            BuiltinUniform::NormalMatrix => "normal_matrix",
+            BuiltinUniform::View => "view",
+            BuiltinUniform::Perspective => "projection",
+            BuiltinUniform::ViewProjection => "view_projection",
+            BuiltinUniform::Time => "time",
+            BuiltinUniform::TimeSinceReload => "reload_time",
+            BuiltinUniform::DeltaTime => "delta_time",
+            BuiltinUniform::Date => "date",
+            BuiltinUniform::Scroll => "scroll",
+            BuiltinUniform::Seed => "seed",
+            BuiltinUniform::SunDirection => "sun_dir",
+            BuiltinUniform::CameraPosition => "camera_pos",
+            BuiltinUniform::CameraDirection => "camera_dir",
+            BuiltinUniform::MouseX => "mouse_x",
+            BuiltinUniform::MouseY => "mouse_y",
+            BuiltinUniform::MousePos => "mouse_pos",
+            BuiltinUniform::MouseButtons => "mouse_buttons",
+            BuiltinUniform::WindowWidth => "window_width",
+            BuiltinUniform::WindowHeight => "window_height",
+            BuiltinUniform::WindowSize => "window_size",
+            BuiltinUniform::InvWindowSize => "inv_window_size",
+            BuiltinUniform::Iteration => "iteration",
+            BuiltinUniform::FrameCount => "frame",
+            BuiltinUniform::InverseTargetSize => "inv_target_size",
+            BuiltinUniform::Keyboard => "keys",
+        }
+    }
+
+    /// Returns the builtins which can be bound to a uniform, only the keyboard can be bound to
+    /// an array.
+    pub fn valid_for_uniform(kind: &Uniform) -> &'static [BuiltinUniform] {
+        match (kind.size, kind.ty) {
+            (Some(_), UniformType::Bool) => &[BuiltinUniform::Keyboard],
+            (Some(_), _) => &[],
+            (None, ty) => Self::valid_for_uniform_type(ty),
         }
     }
 
@@ -46,30 +129,145 @@ impl BuiltinUniform {
         match ty {
             UniformType::Float => &[
                 BuiltinUniform::Time,
+                BuiltinUniform::TimeSinceReload,
+                BuiltinUniform::DeltaTime,
+                BuiltinUniform::Scroll,
+                BuiltinUniform::Seed,
                 BuiltinUniform::MouseX,
                 BuiltinUniform::MouseY,
                 BuiltinUniform::WindowWidth,
                 BuiltinUniform::WindowHeight,
             ],
-            UniformType::FloatVec2 => &[BuiltinUniform::MousePos, BuiltinUniform::WindowSize],
+            UniformType::FloatVec2 => &[
+                BuiltinUniform::MousePos,
+                BuiltinUniform::WindowSize,
+                BuiltinUniform::InvWindowSize,
+                BuiltinUniform::InverseTargetSize,
+            ],
+            UniformType::FloatVec3 => &[
+                BuiltinUniform::SunDirection,
+                BuiltinUniform::CameraPosition,
+                BuiltinUniform::CameraDirection,
+            ],
+            UniformType::FloatVec4 => &[BuiltinUniform::Date, BuiltinUniform::MouseButtons],
+            UniformType::FloatMat3 => &[BuiltinUniform::NormalMatrix],
             UniformType::FloatMat4 => &[
                 BuiltinUniform::Model,
                 BuiltinUniform::View,
                 BuiltinUniform::Perspective,
+                BuiltinUniform::ViewProjection,
             ],
+            UniformType::Int => &[BuiltinUniform::Iteration, BuiltinUniform::FrameCount],
             _ => &[],
         }
     }
 }
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+/// A value for a uniform given in the config or edited in the GUI.
+///
+/// Whole numbers in the config are read as integers and other numbers as doubles, both are
+/// converted to match the type of the uniform with [`CustomUniform::convert_to`].
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
 #[serde(untagged)]
 pub enum CustomUniform {
     Mat4(Mat4),
     Vec4(Vec4),
     Vec3(Vec3),
     Vec2(Vec2),
+    Int(i32),
+    UInt(u32),
+    /// Comes before `Float` so numbers in the config are read without losing precision.
+    Double(f64),
     Float(f32),
+    Bool(bool),
+}
+
+/// A uniform value in the config together with hints on how to edit it in the GUI.
+///
+/// Both forms with fields can be given a `group` to show the uniform under in the GUI.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum UniformConfig {
+    /// A `vec3` or `vec4` edited with a color picker, for example `{ color: [1, 0, 0] }`.
+    Color {
+        color: CustomUniform,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        group: Option<String>,
+    },
+    /// A number or vector edited within bounds, for example
+    /// `{ value: 0.5, min: 0.0, max: 1.0, step: 0.01 }`.
+    Ranged {
+        value: CustomUniform,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        group: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<f32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<f32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        step: Option<f32>,
+    },
+    Value(CustomUniform),
+}
+
+impl UniformConfig {
+    pub fn value(&self) -> CustomUniform {
+        match *self {
+            UniformConfig::Color { color, .. } => color,
+            UniformConfig::Ranged { value, .. } => value,
+            UniformConfig::Value(x) => x,
+        }
+    }
+
+    /// Replace the value while keeping the hints.
+    pub fn set_value(&mut self, new: CustomUniform) {
+        match self {
+            UniformConfig::Color { color, .. } => *color = new,
+            UniformConfig::Ranged { value, .. } => *value = new,
+            UniformConfig::Value(x) => *x = new,
+        }
+    }
+
+    pub fn is_color(&self) -> bool {
+        matches!(self, UniformConfig::Color { .. })
+    }
+
+    pub fn group(&self) -> Option<&str> {
+        match self {
+            UniformConfig::Color { group, .. } | UniformConfig::Ranged { group, .. } => {
+                group.as_deref()
+            }
+            UniformConfig::Value(_) => None,
+        }
+    }
+
+    pub fn range(&self) -> UniformRange {
+        match *self {
+            UniformConfig::Ranged { min, max, step, .. } => UniformRange { min, max, step },
+            _ => UniformRange::default(),
+        }
+    }
+}
+
+/// Bounds for editing a uniform in the GUI, the value is unbounded if not set.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct UniformRange {
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+    /// The increment the value is changed by.
+    pub step: Option<f32>,
+}
+
+impl UniformRange {
+    fn validate(&self) -> Result<()> {
+        if let (Some(min), Some(max)) = (self.min, self.max) {
+            ensure!(min <= max, "`min` must not be larger than `max`");
+        }
+        if let Some(step) = self.step {
+            ensure!(step > 0.0, "`step` must be larger than 0");
+        }
+        Ok(())
+    }
 }
 
 impl AsUniformValue for CustomUniform {
@@ -81,7 +279,11 @@ impl AsUniformValue for CustomUniform {
             CustomUniform::Vec4(x) => UniformValue::Vec4(x.into()),
             CustomUniform::Vec3(x) => UniformValue::Vec3(x.into()),
             CustomUniform::Vec2(x) => UniformValue::Vec2(x.into()),
+            CustomUniform::Int(x) => UniformValue::SignedInt(x),
+            CustomUniform::UInt(x) => UniformValue::UnsignedInt(x),
+            CustomUniform::Double(x) => UniformValue::Double(x),
             CustomUniform::Float(x) => UniformValue::Float(x),
+            CustomUniform::Bool(x) => UniformValue::Bool(x),
         }
     }
 }
@@ -94,10 +296,56 @@ impl CustomUniform {
             UniformType::FloatVec3 => Some(CustomUniform::Vec3(Default::default())),
             UniformType::FloatVec2 => Some(CustomUniform::Vec2(Default::default())),
             UniformType::Float => Some(CustomUniform::Float(Default::default())),
+            UniformType::Double => Some(CustomUniform::Double(Default::default())),
+            UniformType::Int => Some(CustomUniform::Int(Default::default())),
+            UniformType::UnsignedInt => Some(CustomUniform::UInt(Default::default())),
+            UniformType::Bool => Some(CustomUniform::Bool(Default::default())),
             _ => None,
         }
     }
 
+    /// Convert a number read from the config to the numeric type of the uniform, other values
+    /// are returned unchanged.
+    pub fn convert_to(self, kind: UniformType) -> Self {
+        match (self, kind) {
+            (CustomUniform::Int(x), UniformType::Float) => CustomUniform::Float(x as f32),
+            (CustomUniform::UInt(x), UniformType::Float) => CustomUniform::Float(x as f32),
+            (CustomUniform::Double(x), UniformType::Float) => CustomUniform::Float(x as f32),
+            (CustomUniform::Int(x), UniformType::Double) => CustomUniform::Double(x.into()),
+            (CustomUniform::UInt(x), UniformType::Double) => CustomUniform::Double(x.into()),
+            (CustomUniform::Float(x), UniformType::Double) => CustomUniform::Double(x.into()),
+            (CustomUniform::Int(x), UniformType::UnsignedInt) if x >= 0 => {
+                CustomUniform::UInt(x as u32)
+            }
+            _ => self,
+        }
+    }
+
+    /// Replace the value with random numbers between 0 and 1.
+    ///
+    /// Booleans are set to either value. Matrices and integers are left alone as random matrices
+    /// are rarely useful and integers would always be 0.
+    pub fn randomize(&mut self, rng: &mut Rng) {
+        match self {
+            CustomUniform::Mat4(_) | CustomUniform::Int(_) | CustomUniform::UInt(_) => {}
+            CustomUniform::Vec4(x) => {
+                *x = Vec4::new(
+                    rng.next_f32(),
+                    rng.next_f32(),
+                    rng.next_f32(),
+                    rng.next_f32(),
+                )
+            }
+            CustomUniform::Vec3(x) => {
+                *x = Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32())
+            }
+            CustomUniform::Vec2(x) => *x = Vec2::new(rng.next_f32(), rng.next_f32()),
+            CustomUniform::Float(x) => *x = rng.next_f32(),
+            CustomUniform::Double(x) => *x = rng.next_f32().into(),
+            CustomUniform::Bool(x) => *x = rng.next_f32() < 0.5,
+        }
+    }
+
     pub fn ensure_compatible(&self, kind: &UniformType) -> Result<()> {
         match self {
             CustomUniform::Mat4(_) => {
@@ -132,6 +380,22 @@ impl CustomUniform {
                     );
                 }
             }
+            CustomUniform::Int(_) => {
+                if UniformType::Int != *kind {
+                    bail!(
+                        "Invalid uniform type in config, found `Int` expected `{:?}`",
+                        kind
+                    );
+                }
+            }
+            CustomUniform::UInt(_) => {
+                if UniformType::UnsignedInt != *kind {
+                    bail!(
+                        "Invalid uniform type in config, found `UnsignedInt` expected `{:?}`",
+                        kind
+                    );
+                }
+            }
             CustomUniform::Float(_) => {
                 if UniformType::Float != *kind {
                     bail!(
@@ -140,6 +404,22 @@ impl CustomUniform {
                     );
                 }
             }
+            CustomUniform::Double(_) => {
+                if UniformType::Double != *kind {
+                    bail!(
+                        "Invalid uniform type in config, found `Double` expected `{:?}`",
+                        kind
+                    );
+                }
+            }
+            CustomUniform::Bool(_) => {
+                if UniformType::Bool != *kind {
+                    bail!(
+                        "Invalid uniform type in config, found `Bool` expected `{:?}`",
+                        kind
+                    );
+                }
+            }
         }
         Ok(())
     }
@@ -149,6 +429,9 @@ impl CustomUniform {
 pub enum UniformBinding {
     Builtin(BuiltinUniform),
     Custom(CustomUniform),
+    /// A sampler bound to the loaded texture with the given index instead of the texture given
+    /// by its `texture_<name>` name.
+    Texture(usize),
     Unbound,
 }
 
@@ -156,6 +439,17 @@ pub enum UniformBinding {
 pub struct UniformData {
     pub kind: Uniform,
     pub binding: UniformBinding,
+    /// The value given to the uniform in the config.
+    pub default: Option<CustomUniform>,
+    /// Whether the value is written to the recording when one is started.
+    pub record: bool,
+    /// Whether the value is edited with a color picker.
+    pub color: bool,
+    pub range: UniformRange,
+    /// Name shown in the GUI instead of the uniform name.
+    pub label: Option<String>,
+    /// Section the uniform is shown under in the GUI.
+    pub group: Option<String>,
 }
 
 impl UniformData {
@@ -165,8 +459,29 @@ impl UniformData {
             ("projection", UniformType::FloatMat4) => {
                 UniformBinding::Builtin(BuiltinUniform::Perspective)
             }
+            ("view_projection", UniformType::FloatMat4) => {
+                UniformBinding::Builtin(BuiltinUniform::ViewProjection)
+            }
             ("model", UniformType::FloatMat4) => UniformBinding::Builtin(BuiltinUniform::Model),
+            ("normal_matrix", UniformType::FloatMat3) => {
+                UniformBinding::Builtin(BuiltinUniform::NormalMatrix)
+            }
             ("time", UniformType::Float) => UniformBinding::Builtin(BuiltinUniform::Time),
+            ("reload_time", UniformType::Float) => {
+                UniformBinding::Builtin(BuiltinUniform::TimeSinceReload)
+            }
+            ("delta_time", UniformType::Float) => {
+                UniformBinding::Builtin(BuiltinUniform::DeltaTime)
+            }
+            ("sun_dir", UniformType::FloatVec3) => {
+                UniformBinding::Builtin(BuiltinUniform::SunDirection)
+            }
+            ("camera_pos", UniformType::FloatVec3) => {
+                UniformBinding::Builtin(BuiltinUniform::CameraPosition)
+            }
+            ("camera_dir", UniformType::FloatVec3) => {
+                UniformBinding::Builtin(BuiltinUniform::CameraDirection)
+            }
             ("mouse_x", UniformType::Float) => UniformBinding::Builtin(BuiltinUniform::MouseX),
             ("mouse_y", UniformType::Float) => UniformBinding::Builtin(BuiltinUniform::MouseY),
             ("window_width", UniformType::Float) => {
@@ -181,11 +496,60 @@ impl UniformData {
             ("window_size", UniformType::FloatVec2) => {
                 UniformBinding::Builtin(BuiltinUniform::WindowSize)
             }
+            ("inv_window_size", UniformType::FloatVec2) => {
+                UniformBinding::Builtin(BuiltinUniform::InvWindowSize)
+            }
+            ("scroll", UniformType::Float) => UniformBinding::Builtin(BuiltinUniform::Scroll),
+            ("seed", UniformType::Float) => UniformBinding::Builtin(BuiltinUniform::Seed),
+            ("date", UniformType::FloatVec4) => UniformBinding::Builtin(BuiltinUniform::Date),
+            ("mouse_buttons", UniformType::FloatVec4) => {
+                UniformBinding::Builtin(BuiltinUniform::MouseButtons)
+            }
+            ("iteration", UniformType::Int) => UniformBinding::Builtin(BuiltinUniform::Iteration),
+            ("frame", UniformType::Int) => UniformBinding::Builtin(BuiltinUniform::FrameCount),
+            ("keys", UniformType::Bool) if kind.size.is_some() => {
+                UniformBinding::Builtin(BuiltinUniform::Keyboard)
+            }
+            ("inv_target_size", UniformType::FloatVec2)
+            | ("texel_size", UniformType::FloatVec2) => {
+                UniformBinding::Builtin(BuiltinUniform::InverseTargetSize)
+            }
             _ => UniformBinding::Unbound,
         };
         UniformData {
             kind: kind.clone(),
             binding,
+            default: None,
+            record: false,
+            color: false,
+            range: UniformRange::default(),
+            label: None,
+            group: None,
+        }
+    }
+
+    /// Returns the value the uniform has when it is bound to a custom value, the value from the
+    /// config if there is one.
+    pub fn default_custom(&self) -> Option<CustomUniform> {
+        self.default
+            .or_else(|| CustomUniform::from_uniform_type(self.kind.ty))
+    }
+
+    /// Bind the uniform to the value given in the config, which it is reset to later.
+    pub fn set_config_value(&mut self, value: CustomUniform) -> Result<()> {
+        let value = value.convert_to(self.kind.ty);
+        value.ensure_compatible(&self.kind.ty)?;
+        self.binding = UniformBinding::Custom(value);
+        self.default = Some(value);
+        Ok(())
+    }
+
+    /// Reset a uniform bound to a custom value to the value from the config.
+    pub fn reset(&mut self) {
+        if let UniformBinding::Custom(_) = self.binding {
+            if let Some(x) = self.default_custom() {
+                self.binding = UniformBinding::Custom(x);
+            }
         }
     }
 }
@@ -198,17 +562,45 @@ pub struct LoadedPass {
     pub draw_parameters: DrawParameters<'static>,
     pub objects: Vec<usize>,
     pub textures: Vec<(usize, String)>,
+    /// Declared `texture_<name>_size` uniforms with the texture whose size they hold.
+    pub texture_sizes: Vec<(usize, String)>,
+    pub buffers: Vec<(usize, String)>,
     pub target: Option<LoadedTarget>,
     pub uniforms: HashMap<String, UniformData>,
+    pub iterations: u32,
+    /// Textures which are both sampled and rendered to by this pass.
+    pub feedback: Vec<usize>,
+    /// The clock driving the `time` uniform, the default clock if not set.
+    pub clock: Option<usize>,
+    /// The primitive objects are drawn as, the triangles of the geometry if not set.
+    pub draw_as: Option<ser::DrawAs>,
+}
+
+impl LoadedPass {
+    /// Returns whether the pass renders to the texture with the given index.
+    pub fn renders_to(&self, texture: usize) -> bool {
+        self.target
+            .as_ref()
+            .is_some_and(|x| x.color.iter().any(|x| x.0 == texture) || x.depth == Some(texture))
+    }
 }
 
 impl Config {
     pub fn load_pass2(
         pass: &ser::Pass,
         object_name_match: &HashMap<String, usize>,
-        texture_name_match: &HashMap<String, usize>,
+        loaded_objects: &[LoadedObject],
+        loaded_textures: &[LoadedTexture],
+        buffer_name_match: &HashMap<String, usize>,
+        reverse_z: bool,
         display: &Display,
     ) -> Result<LoadedPass> {
+        let texture_name_match: HashMap<_, _> = loaded_textures
+            .iter()
+            .enumerate()
+            .map(|(idx, x)| (x.config.name.clone(), idx))
+            .collect();
+
         let objects = pass.objects.iter().try_fold(Vec::new(), |mut acc, x| {
             if let Some(x) = object_name_match.get(x).copied() {
                 acc.push(x);
@@ -233,41 +625,175 @@ impl Config {
                 .iter()
                 .try_fold::<_, _, Result<_>>(Vec::new(), |mut acc, x| {
                     acc.push(
-                        Self::link_texture(x, texture_name_match)
+                        Self::link_texture(x, &texture_name_match)
                             .context("Failed to link pass texture")?,
                     );
                     Result::Ok(acc)
                 })?;
 
+        let buffers = pass.buffers.iter().try_fold(Vec::new(), |mut acc, x| {
+            if let Some(idx) = buffer_name_match.get(x).copied() {
+                acc.push((idx, x.clone()));
+            } else {
+                let mut expects = String::new();
+                write!(expects, "Expected one of ").unwrap();
+                for (idx, k) in buffer_name_match.keys().enumerate() {
+                    if idx != 0 {
+                        write!(expects, ",").unwrap();
+                    }
+                    write!(expects, "`{}`", k).unwrap();
+                }
+                write!(expects, ".").unwrap();
+
+                bail!("Could not find buffer `{}`. {}", x, expects)
+            }
+            Ok(acc)
+        })?;
+
         let vertex = Shader::load(&pass.vertex_shader).context("Failed to load vertex shader")?;
         let fragment =
             Shader::load(&pass.fragment_shader).context("Failed to load fragment shader")?;
 
-        let program = Program::from_source(display, &vertex.source, &fragment.source, None)
-            .context("Failed to compile program")?;
+        // glium enables sRGB conversion on write unless the program claims to output sRGB itself.
+        let program = Program::new(
+            display,
+            ProgramCreationInput::SourceCode {
+                vertex_shader: &vertex.source,
+                tessellation_control_shader: None,
+                tessellation_evaluation_shader: None,
+                geometry_shader: None,
+                fragment_shader: &fragment.source,
+                transform_feedback_varyings: None,
+                outputs_srgb: !pass.srgb_write,
+                uses_point_size: false,
+            },
+        )
+        .context("Failed to compile program")?;
 
         for (name, _) in program.attributes() {
             match name.as_str() {
                 "position" | "normal" | "tex_coord" => {}
+                "tangent" => {
+                    if let Some(x) = objects
+                        .iter()
+                        .find(|x| loaded_objects[**x].tangent.is_none())
+                    {
+                        bail!(
+                            "Attribute `tangent` used in shader but object `{}` does not have `tangents` enabled",
+                            loaded_objects[*x].name
+                        )
+                    }
+                }
                 x => bail!("Invalid attribute `{}` used in shader", x,),
             }
         }
 
-        let mut uniforms: HashMap<_, _> = program
-            .uniforms()
-            .map(|(a, b)| {
-                let data = UniformData::from_name_uniform(a, b);
-                (a.clone(), data)
-            })
-            .collect();
+        // glium reflects every element of an array as a separate uniform, the elements of the
+        // `keys` array are bound together to the keyboard state.
+        let mut keys: Option<Uniform> = None;
+        let mut uniforms = HashMap::new();
+        for (a, b) in program.uniforms() {
+            let key_idx = a
+                .strip_prefix("keys[")
+                .and_then(|x| x.strip_suffix(']'))
+                .and_then(|x| x.parse::<usize>().ok());
+            match key_idx {
+                Some(idx) if b.ty == UniformType::Bool => {
+                    let x = keys.get_or_insert(*b);
+                    x.location = x.location.min(b.location);
+                    x.size = Some(x.size.unwrap_or(0).max(idx + 1));
+                }
+                _ => {
+                    uniforms.insert(a.clone(), UniformData::from_name_uniform(a, b));
+                }
+            }
+        }
+        if let Some(x) = keys {
+            uniforms.insert(
+                "keys".to_string(),
+                UniformData::from_name_uniform("keys", &x),
+            );
+        }
+
+        // The size uniforms of textures are always bound so they are not listed with the others.
+        let mut texture_sizes = Vec::new();
+        for (idx, name) in textures.iter() {
+            let size_name = format!("texture_{}_size", name);
+            if uniforms
+                .get(&size_name)
+                .is_some_and(|x| x.kind.ty == UniformType::FloatVec2)
+            {
+                uniforms.remove(&size_name);
+                texture_sizes.push((*idx, size_name));
+            }
+        }
+
+        // Glium panics when uploading a double without support for them.
+        if !display.get_extensions().gl_arb_gpu_shader_fp64 {
+            if let Some(name) = uniforms
+                .iter()
+                .find(|x| x.1.kind.ty == UniformType::Double)
+                .map(|x| x.0)
+            {
+                bail!(
+                    "Uniform `{}` is a double but this system does not support double precision floats",
+                    name
+                );
+            }
+        }
+
+        for (name, pragma) in vertex.pragmas.iter().chain(fragment.pragmas.iter()) {
+            if let Some(x) = uniforms.get_mut(name) {
+                match pragma.range.validate() {
+                    Ok(()) => x.range = pragma.range,
+                    Err(e) => warn!("ignoring range of `#pragma uniform {}`: {}", name, e),
+                }
+                if pragma.label.is_some() {
+                    x.label = pragma.label.clone();
+                }
+                if pragma.group.is_some() {
+                    x.group = pragma.group.clone();
+                }
+            }
+        }
 
         for (name, value) in pass.uniforms.iter() {
             if let Some(x) = uniforms.get_mut(name) {
                 ensure!(x.kind.size.is_none(), "Uniform arrays are not supported");
-                value
-                    .ensure_compatible(&x.kind.ty)
+                if value.is_color() {
+                    ensure!(
+                        matches!(x.kind.ty, UniformType::FloatVec3 | UniformType::FloatVec4),
+                        "Color uniform `{}` must be a vec3 or vec4",
+                        name
+                    );
+                    x.color = true;
+                }
+                if let Some(group) = value.group() {
+                    x.group = Some(group.to_string());
+                }
+                let range = value.range();
+                if range != UniformRange::default() {
+                    ensure!(
+                        matches!(
+                            x.kind.ty,
+                            UniformType::Float
+                                | UniformType::Double
+                                | UniformType::FloatVec2
+                                | UniformType::FloatVec3
+                                | UniformType::FloatVec4
+                                | UniformType::Int
+                                | UniformType::UnsignedInt
+                        ),
+                        "Range of uniform `{}` is only supported for numbers and vectors",
+                        name
+                    );
+                    range
+                        .validate()
+                        .with_context(|| format!("Invalid range for uniform `{}`", name))?;
+                    x.range = range;
+                }
+                x.set_config_value(value.value())
                     .with_context(|| format!("Invalid uniform binding `{}`", name))?;
-                x.binding = UniformBinding::Custom(*value);
             }
         }
 
@@ -278,7 +804,7 @@ impl Config {
                     .color
                     .iter()
                     .try_fold::<_, _, Result<_>>(Vec::new(), |mut acc, x| {
-                        acc.push(Self::link_texture(&x, texture_name_match)?);
+                        acc.push(Self::link_texture(&x, &texture_name_match)?);
                         Ok(acc)
                     })
                     .context("Failed to link pass target color attachment")?;
@@ -286,17 +812,67 @@ impl Config {
                     .depth
                     .as_ref()
                     .map(|x| {
-                        Self::link_texture(&ser::TextureRef::Name(x.clone()), texture_name_match)
+                        Self::link_texture(&ser::TextureRef::Name(x.clone()), &texture_name_match)
                     })
                     .transpose()
                     .context("Failed to link pass target depth attachment")?
                     .map(|x| x.0);
 
-                Some(LoadedTarget { color, depth })
+                let region = x.region.map(|region| Rect {
+                    left: region.x,
+                    bottom: region.y,
+                    width: region.width,
+                    height: region.height,
+                });
+
+                Some(LoadedTarget {
+                    color,
+                    depth,
+                    region,
+                    clear_color: x.clear_color,
+                    clear_depth: x.clear_depth,
+                    clear: x.clear,
+                })
+            }
+        };
+
+        ensure!(pass.iterations > 0, "Pass must have at least one iteration");
+        let feedback = match target {
+            Some(ref target) => {
+                // Double buffered textures only swap once every pass has rendered, so a later
+                // iteration would sample the previous frame instead of the previous iteration.
+                ensure!(
+                    pass.iterations == 1
+                        || !target
+                            .color
+                            .iter()
+                            .any(|x| loaded_textures[x.0].is_double_buffered()),
+                    "Pass with multiple iterations can't render to a double buffered texture"
+                );
+                target
+                    .color
+                    .iter()
+                    .map(|x| x.0)
+                    .filter(|x| textures.iter().any(|y| y.0 == *x))
+                    // Double buffered textures are sampled from the other texture so don't need a
+                    // copy.
+                    .filter(|x| !loaded_textures[*x].is_double_buffered())
+                    .collect()
+            }
+            None => {
+                ensure!(
+                    pass.iterations == 1,
+                    "Pass with multiple iterations must render to a buffer target"
+                );
+                Vec::new()
             }
         };
 
-        let draw_parameters = pass.settings.to_params();
+        let mut draw_parameters = pass.settings.to_params(reverse_z);
+        if let Some(region) = target.as_ref().and_then(|x| x.region) {
+            draw_parameters.viewport = Some(region);
+            draw_parameters.scissor = Some(region);
+        }
 
         Ok(LoadedPass {
             vertex,
@@ -304,9 +880,52 @@ impl Config {
             objects,
             draw_parameters,
             textures,
+            texture_sizes,
+            buffers,
             program,
             target,
             uniforms,
+            iterations: pass.iterations,
+            feedback,
+            clock: None,
+            draw_as: pass.draw_as,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{CustomUniform, UniformBinding, UniformData};
+    use glium::{program::Uniform, uniforms::UniformType};
+
+    fn uniform(ty: UniformType) -> Uniform {
+        Uniform {
+            location: 0,
+            ty,
+            size: None,
+        }
+    }
+
+    #[test]
+    fn reset_to_config_value() {
+        let mut data = UniformData::from_name_uniform("scale", &uniform(UniformType::Float));
+        data.set_config_value(CustomUniform::Float(2.0)).unwrap();
+        data.binding = UniformBinding::Custom(CustomUniform::Float(5.0));
+        data.reset();
+        assert!(matches!(
+            data.binding,
+            UniformBinding::Custom(CustomUniform::Float(x)) if x == 2.0
+        ));
+    }
+
+    #[test]
+    fn reset_without_config_value() {
+        let mut data = UniformData::from_name_uniform("scale", &uniform(UniformType::Float));
+        data.binding = UniformBinding::Custom(CustomUniform::Float(5.0));
+        data.reset();
+        assert!(matches!(
+            data.binding,
+            UniformBinding::Custom(CustomUniform::Float(x)) if x == 0.0
+        ));
+    }
+}
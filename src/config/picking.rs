@@ -0,0 +1,161 @@
+//! Picking of objects by rendering their index into an integer render target.
+use super::LoadedObject;
+use anyhow::{Context, Result};
+use glam::f32::Mat4;
+use glium::{
+    framebuffer::SimpleFrameBuffer,
+    texture::{
+        DepthFormat, DepthTexture2d, MipmapsOption, UncompressedUintFormat, UnsignedTexture2d,
+    },
+    uniform, Depth, DepthTest, Display, DrawParameters, Program, Rect, Surface,
+};
+
+const VERTEX_SHADER: &str = r#"
+#version 330
+
+in vec3 position;
+
+uniform mat4 model;
+uniform mat4 view;
+uniform mat4 projection;
+
+void main(){
+    gl_Position = projection * view * model * vec4(position, 1.0);
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+#version 330
+
+uniform uint object_id;
+
+out uint id;
+
+void main(){
+    id = object_id;
+}
+"#;
+
+#[derive(Debug)]
+pub struct Picker {
+    program: Program,
+    ids: UnsignedTexture2d,
+    depth: DepthTexture2d,
+}
+
+impl Picker {
+    pub fn new(display: &Display) -> Result<Self> {
+        let program = Program::from_source(display, VERTEX_SHADER, FRAGMENT_SHADER, None)
+            .context("failed to compile picking program")?;
+        let (width, height) = display.get_framebuffer_dimensions();
+        let (ids, depth) = Self::create_textures(display, (width, height))?;
+        Ok(Picker {
+            program,
+            ids,
+            depth,
+        })
+    }
+
+    fn create_textures(
+        display: &Display,
+        (width, height): (u32, u32),
+    ) -> Result<(UnsignedTexture2d, DepthTexture2d)> {
+        let ids = UnsignedTexture2d::empty_with_format(
+            display,
+            UncompressedUintFormat::U32,
+            MipmapsOption::NoMipmap,
+            width.max(1),
+            height.max(1),
+        )
+        .context("failed to create picking texture")?;
+        let depth = DepthTexture2d::empty_with_format(
+            display,
+            DepthFormat::I24,
+            MipmapsOption::NoMipmap,
+            width.max(1),
+            height.max(1),
+        )
+        .context("failed to create picking depth texture")?;
+        Ok((ids, depth))
+    }
+
+    pub fn resize(&mut self, dimensions: (u32, u32), display: &Display) -> Result<()> {
+        let (ids, depth) = Self::create_textures(display, dimensions)?;
+        self.ids = ids;
+        self.depth = depth;
+        Ok(())
+    }
+
+    /// Render the ids of all objects and return the index of the object at the given pixel, with
+    /// the origin in the top left corner.
+    pub fn pick(
+        &self,
+        display: &Display,
+        objects: &[LoadedObject],
+        view: Mat4,
+        projection: Mat4,
+        reverse_z: bool,
+        (x, y): (u32, u32),
+    ) -> Result<Option<usize>> {
+        let width = self.ids.get_width();
+        let height = self.ids.get_height().unwrap_or(1);
+        if x >= width || y >= height {
+            return Ok(None);
+        }
+
+        let mut target = SimpleFrameBuffer::with_depth_buffer(display, &self.ids, &self.depth)
+            .context("could not create picking frame buffer")?;
+        let (clear_depth, test) = if reverse_z {
+            (0.0, DepthTest::IfMore)
+        } else {
+            (1.0, DepthTest::IfLess)
+        };
+        target.clear_color_and_depth((0.0, 0.0, 0.0, 0.0), clear_depth);
+
+        let draw_parameters = DrawParameters {
+            depth: Depth {
+                test,
+                write: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let view = view.to_cols_array_2d();
+        let projection = projection.to_cols_array_2d();
+
+        for (idx, object) in objects.iter().enumerate() {
+            // Zero is reserved for pixels which are not covered by any object.
+            let uniforms = uniform! {
+                model: object.matrix.to_cols_array_2d(),
+                view: view,
+                projection: projection,
+                object_id: idx as u32 + 1,
+            };
+            target
+                .draw(
+                    &object.vertex,
+                    &object.index,
+                    &self.program,
+                    &uniforms,
+                    &draw_parameters,
+                )
+                .context("could not render picking pass")?;
+        }
+
+        let rect = Rect {
+            left: x,
+            bottom: height - y - 1,
+            width: 1,
+            height: 1,
+        };
+        let pixels: Vec<Vec<u32>> = self
+            .ids
+            .main_level()
+            .first_layer()
+            .into_image(None)
+            .unwrap()
+            .raw_read(&rect);
+        let id = pixels.first().and_then(|x| x.first()).copied().unwrap_or(0);
+        Ok(id.checked_sub(1).map(|x| x as usize))
+    }
+}
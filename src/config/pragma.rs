@@ -0,0 +1,127 @@
+//! Uniform metadata declared in shaders with `#pragma uniform` lines.
+//!
+//! A line like `#pragma uniform strength range(0.0, 1.0) step(0.01) label("Strength")` gives the
+//! uniform `strength` a range and label in the GUI, `group("Lighting")` puts it in a collapsible
+//! section. The lines are removed before the shader is compiled.
+use super::pass::UniformRange;
+use std::collections::HashMap;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct UniformPragma {
+    pub range: UniformRange,
+    /// Name shown in the GUI instead of the uniform name.
+    pub label: Option<String>,
+    /// Section of the GUI the uniform is shown under.
+    pub group: Option<String>,
+}
+
+/// Split an attribute like `range(0.0, 1.0)` into its name and arguments, returning the rest of
+/// the line after it.
+fn parse_attribute(line: &str) -> Option<(&str, Vec<&str>, &str)> {
+    let open = line.find('(')?;
+    let name = line[..open].trim();
+    let mut in_string = false;
+    let mut args = Vec::new();
+    let mut start = open + 1;
+    for (idx, c) in line.char_indices().skip_while(|x| x.0 <= open) {
+        match c {
+            '"' => in_string = !in_string,
+            ',' if !in_string => {
+                args.push(line[start..idx].trim());
+                start = idx + 1;
+            }
+            ')' if !in_string => {
+                let last = line[start..idx].trim();
+                if !last.is_empty() || !args.is_empty() {
+                    args.push(last);
+                }
+                return Some((name, args, &line[idx + 1..]));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_float(arg: Option<&&str>) -> Option<f32> {
+    arg?.parse().ok()
+}
+
+/// Parse the part of a `#pragma uniform` line after `uniform`, unknown attributes are skipped with
+/// a warning.
+fn parse(line: &str) -> Option<(String, UniformPragma)> {
+    let line = line.trim_start();
+    let name_end = line
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(line.len());
+    if name_end == 0 {
+        warn!("missing uniform name in `#pragma uniform {}`", line);
+        return None;
+    }
+    let name = line[..name_end].to_string();
+
+    let mut pragma = UniformPragma::default();
+    let mut rest = &line[name_end..];
+    while !rest.trim().is_empty() {
+        let (attribute, args, next) = match parse_attribute(rest) {
+            Some(x) => x,
+            None => {
+                warn!(
+                    "invalid `#pragma uniform` for `{}`: `{}`",
+                    name,
+                    rest.trim()
+                );
+                break;
+            }
+        };
+        match (attribute, args.len()) {
+            ("range", 2) => {
+                pragma.range.min = parse_float(args.first());
+                pragma.range.max = parse_float(args.get(1));
+            }
+            ("step", 1) => pragma.range.step = parse_float(args.first()),
+            ("label", 1) => {
+                pragma.label = Some(args[0].trim_matches('"').to_string());
+            }
+            ("group", 1) => {
+                pragma.group = Some(args[0].trim_matches('"').to_string());
+            }
+            _ => warn!(
+                "unknown attribute `{}` in `#pragma uniform` for `{}`",
+                attribute, name
+            ),
+        }
+        rest = next;
+    }
+    Some((name, pragma))
+}
+
+/// Remove the `#pragma uniform` lines from a shader and return the metadata they declare by
+/// uniform name.
+///
+/// The lines are replaced with empty lines so that line numbers in compile errors stay correct.
+pub fn extract(source: &mut String) -> HashMap<String, UniformPragma> {
+    let mut res = HashMap::new();
+    let mut stripped = String::with_capacity(source.len());
+    for line in source.split_inclusive('\n') {
+        let directive = line
+            .trim_start()
+            .strip_prefix("#pragma")
+            .map(str::trim_start)
+            .and_then(|x| x.strip_prefix("uniform"))
+            .filter(|x| x.starts_with(char::is_whitespace));
+        match directive {
+            Some(x) => {
+                if let Some((name, pragma)) = parse(x.trim_end()) {
+                    res.insert(name, pragma);
+                }
+                if line.ends_with('\n') {
+                    stripped.push('\n');
+                }
+            }
+            None => stripped.push_str(line),
+        }
+    }
+    *source = stripped;
+    res
+}
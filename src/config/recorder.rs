@@ -0,0 +1,140 @@
+//! Recording of uniform values to a CSV file while rendering.
+use super::{
+    pass::{CustomUniform, LoadedPass, UniformBinding},
+    render::BuiltinUniforms,
+};
+use anyhow::{Context, Result};
+use glium::uniforms::UniformType;
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// Returns the amount of numbers in a value of the given type, zero if the type can't be recorded.
+pub fn component_count(ty: UniformType) -> usize {
+    match ty {
+        UniformType::Float
+        | UniformType::Double
+        | UniformType::Int
+        | UniformType::UnsignedInt
+        | UniformType::Bool => 1,
+        UniformType::FloatVec2 => 2,
+        UniformType::FloatVec3 => 3,
+        UniformType::FloatVec4 => 4,
+        UniformType::FloatMat4 => 16,
+        _ => 0,
+    }
+}
+
+impl CustomUniform {
+    pub fn components(&self) -> Vec<f32> {
+        match *self {
+            CustomUniform::Mat4(x) => x.to_cols_array().to_vec(),
+            CustomUniform::Vec4(x) => <[f32; 4]>::from(x).to_vec(),
+            CustomUniform::Vec3(x) => <[f32; 3]>::from(x).to_vec(),
+            CustomUniform::Vec2(x) => vec![x.x, x.y],
+            CustomUniform::Int(x) => vec![x as f32],
+            CustomUniform::UInt(x) => vec![x as f32],
+            CustomUniform::Double(x) => vec![x as f32],
+            CustomUniform::Float(x) => vec![x],
+            CustomUniform::Bool(x) => vec![if x { 1.0 } else { 0.0 }],
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RecordedUniform {
+    pass: usize,
+    name: String,
+    components: usize,
+}
+
+#[derive(Debug)]
+pub struct Recorder {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    uniforms: Vec<RecordedUniform>,
+    /// Values of the frame currently being rendered.
+    row: Vec<Option<Vec<f32>>>,
+}
+
+impl Recorder {
+    /// Start recording the uniforms marked for recording in the given passes.
+    pub fn new(path: &Path, passes: &[LoadedPass]) -> Result<Self> {
+        let mut uniforms = Vec::new();
+        for (pass_id, pass) in passes.iter().enumerate() {
+            let mut names: Vec<_> = pass.uniforms.iter().filter(|x| x.1.record).collect();
+            names.sort_by(|a, b| a.0.cmp(b.0));
+            for (name, data) in names {
+                uniforms.push(RecordedUniform {
+                    pass: pass_id,
+                    name: name.clone(),
+                    components: component_count(data.kind.ty),
+                });
+            }
+        }
+        ensure!(!uniforms.is_empty(), "No uniforms selected for recording");
+
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create recording `{}`", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        let mut header = String::from("frame,time");
+        for x in uniforms.iter() {
+            if x.components == 1 {
+                write!(header, ",pass{}.{}", x.pass, x.name).unwrap();
+            } else {
+                for idx in 0..x.components {
+                    write!(header, ",pass{}.{}.{}", x.pass, x.name, idx).unwrap();
+                }
+            }
+        }
+        writeln!(writer, "{}", header)?;
+
+        Ok(Recorder {
+            path: path.to_path_buf(),
+            writer,
+            row: uniforms.iter().map(|_| None).collect(),
+            uniforms,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Store the values the uniforms of a pass had while rendering it.
+    pub fn record_pass(&mut self, pass_id: usize, pass: &LoadedPass, builtin: &BuiltinUniforms) {
+        for (x, value) in self.uniforms.iter().zip(self.row.iter_mut()) {
+            if x.pass != pass_id {
+                continue;
+            }
+            *value = match pass.uniforms.get(&x.name).map(|x| x.binding) {
+                Some(UniformBinding::Builtin(b)) => Some(builtin.components(b)),
+                Some(UniformBinding::Custom(c)) => Some(c.components()),
+                Some(UniformBinding::Unbound) | Some(UniformBinding::Texture(_)) | None => None,
+            };
+        }
+    }
+
+    /// Write the values of the frame to the file, uniforms without a value are left empty.
+    pub fn finish_frame(&mut self, frame: i32, time: f32) -> Result<()> {
+        let mut line = format!("{},{}", frame, time);
+        for (x, value) in self.uniforms.iter().zip(self.row.iter_mut()) {
+            match value.take() {
+                Some(values) if values.len() == x.components => {
+                    for v in values {
+                        write!(line, ",{}", v).unwrap();
+                    }
+                }
+                _ => line.push_str(&",".repeat(x.components)),
+            }
+        }
+        writeln!(self.writer, "{}", line).context("Failed to write recording")
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.writer.flush().context("Failed to write recording")
+    }
+}
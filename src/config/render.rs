@@ -1,15 +1,25 @@
 use super::{
-    texture::LoadedTextureKind, BuiltinUniform, Config, LoadedCamera, LoadedPass, LoadedTarget,
-    UniformBinding,
+    keyboard::KEY_COUNT,
+    ser,
+    texture::{LoadedTexture, LoadedTextureKind},
+    BuiltinUniform, ColorGrading, Config, Fxaa, LoadedCamera, LoadedObject, LoadedPass,
+    LoadedTarget, Tonemapping, UniformBinding,
 };
+use crate::util::date;
 use anyhow::{Context, Result};
-use glam::f32::{Mat4, Quat, Vec3};
-use std::collections::HashMap;
+use glam::f32::{Mat3, Mat4, Vec2, Vec3};
+use std::{
+    collections::HashMap,
+    time::{Instant, SystemTime},
+};
 
 use glium::{
-    framebuffer::MultiOutputFrameBuffer,
-    uniforms::{AsUniformValue, Sampler, UniformValue, Uniforms},
-    Frame, Surface,
+    draw_parameters::{BackfaceCullingMode, TimeElapsedQuery},
+    framebuffer::{MultiOutputFrameBuffer, SimpleFrameBuffer, ToColorAttachment},
+    index::{IndicesSource, NoIndices, PrimitiveType},
+    texture::{MipmapsOption, Texture2d, UncompressedFloatFormat},
+    uniforms::{AsUniformValue, MagnifySamplerFilter, Sampler, UniformValue, Uniforms},
+    Display, DrawError, DrawParameters, Frame, Program, Surface,
 };
 
 #[derive(Clone)]
@@ -35,54 +45,341 @@ impl<'b> Uniforms for DynUniformStorage<'b> {
 
 pub struct BuiltinUniforms {
     time: f32,
+    time_since_reload: f32,
+    delta_time: f32,
+    date: [f32; 4],
+    scroll: f32,
+    seed: f32,
+    sun_direction: [f32; 3],
+    camera_position: [f32; 3],
+    camera_direction: [f32; 3],
     model: [[f32; 4]; 4],
+    normal_matrix: [[f32; 3]; 3],
     view: [[f32; 4]; 4],
     perspective: [[f32; 4]; 4],
+    view_projection: [[f32; 4]; 4],
     mouse_x: f32,
     mouse_y: f32,
     mouse_pos: [f32; 2],
+    mouse_buttons: [f32; 4],
     window_width: f32,
     window_height: f32,
     window_size: [f32; 2],
+    inv_window_size: [f32; 2],
+    iteration: i32,
+    frame_count: i32,
+    inverse_target_size: [f32; 2],
+    keys: [bool; KEY_COUNT],
+}
+
+impl BuiltinUniforms {
+    /// Returns the value of a builtin uniform as a list of numbers.
+    pub fn components(&self, builtin: BuiltinUniform) -> Vec<f32> {
+        let mat = |x: &[[f32; 4]; 4]| x.iter().flatten().copied().collect();
+        match builtin {
+            BuiltinUniform::Model => mat(&self.model),
+            BuiltinUniform::NormalMatrix => self.normal_matrix.iter().flatten().copied().collect(),
+            BuiltinUniform::View => mat(&self.view),
+            BuiltinUniform::Perspective => mat(&self.perspective),
+            BuiltinUniform::ViewProjection => mat(&self.view_projection),
+            BuiltinUniform::Time => vec![self.time],
+            BuiltinUniform::TimeSinceReload => vec![self.time_since_reload],
+            BuiltinUniform::DeltaTime => vec![self.delta_time],
+            BuiltinUniform::Date => self.date.to_vec(),
+            BuiltinUniform::Scroll => vec![self.scroll],
+            BuiltinUniform::Seed => vec![self.seed],
+            BuiltinUniform::SunDirection => self.sun_direction.to_vec(),
+            BuiltinUniform::CameraPosition => self.camera_position.to_vec(),
+            BuiltinUniform::CameraDirection => self.camera_direction.to_vec(),
+            BuiltinUniform::MouseX => vec![self.mouse_x],
+            BuiltinUniform::MouseY => vec![self.mouse_y],
+            BuiltinUniform::MousePos => self.mouse_pos.to_vec(),
+            BuiltinUniform::MouseButtons => self.mouse_buttons.to_vec(),
+            BuiltinUniform::WindowWidth => vec![self.window_width],
+            BuiltinUniform::WindowHeight => vec![self.window_height],
+            BuiltinUniform::WindowSize => self.window_size.to_vec(),
+            BuiltinUniform::InvWindowSize => self.inv_window_size.to_vec(),
+            BuiltinUniform::Iteration => vec![self.iteration as f32],
+            BuiltinUniform::FrameCount => vec![self.frame_count as f32],
+            BuiltinUniform::InverseTargetSize => self.inverse_target_size.to_vec(),
+            BuiltinUniform::Keyboard => self
+                .keys
+                .iter()
+                .map(|x| if *x { 1.0 } else { 0.0 })
+                .collect(),
+        }
+    }
+}
+
+/// Returns the inverse transpose of the upper left 3x3 part of a model matrix.
+fn normal_matrix(model: Mat4) -> [[f32; 3]; 3] {
+    Mat3::from_cols(
+        model.x_axis.truncate(),
+        model.y_axis.truncate(),
+        model.z_axis.truncate(),
+    )
+    .inverse()
+    .transpose()
+    .to_cols_array_2d()
+}
+
+/// Returns the order to draw objects in as indices into the list of whether each object is
+/// transparent and its position.
+///
+/// Opaque objects keep their order, transparent objects are drawn after them from the furthest
+/// to the closest to the camera.
+fn sort_draw_order(objects: &[(bool, Vec3)], camera: Vec3) -> Vec<usize> {
+    let (opaque, mut transparent): (Vec<_>, Vec<_>) =
+        (0..objects.len()).partition(|x| !objects[*x].0);
+    let distance = |x: usize| camera.distance_squared(objects[x].1);
+    transparent.sort_by(|a, b| distance(*b).total_cmp(&distance(*a)));
+    let mut res = opaque;
+    res.extend(transparent);
+    res
+}
+
+/// A post processing step applied to the rendered frame.
+enum PostStage<'a> {
+    Tonemap(&'a Tonemapping),
+    Grading(&'a ColorGrading),
+    Fxaa(&'a Fxaa),
+}
+
+impl<'a> PostStage<'a> {
+    fn target(&self, display: &Display) -> Result<SimpleFrameBuffer<'a>> {
+        match *self {
+            PostStage::Tonemap(x) => x.target(display),
+            PostStage::Grading(x) => x.target(display),
+            PostStage::Fxaa(x) => x.target(display),
+        }
+    }
+
+    fn apply<S: Surface>(&self, surface: &mut S, textures: &[LoadedTexture]) -> Result<()> {
+        match *self {
+            PostStage::Tonemap(x) => x.apply(surface),
+            PostStage::Grading(x) => x.apply(surface, textures),
+            PostStage::Fxaa(x) => x.apply(surface),
+        }
+    }
 }
 
 impl Config {
     pub fn get_camera_matrix(&self) -> Mat4 {
         match self.camera {
             LoadedCamera::LookAt { from, to, up } => Mat4::look_at_lh(from, to, up),
-            LoadedCamera::Orbital { state, distance } => {
-                let rotation_y = Quat::from_rotation_y(state.x * 0.01);
-                let rotation_x = Quat::from_axis_angle(rotation_y * Vec3::X, -state.y * 0.01);
-                let rotation = (rotation_x * rotation_y).normalize();
-                let position = rotation * Vec3::new(0.0, 0.0, -1.0) * distance;
+            LoadedCamera::Flying(ref x) => x.view_matrix(),
+            LoadedCamera::Orbital {
+                state,
+                trackball,
+                distance,
+                center,
+            } => {
+                let rotation = LoadedCamera::orbital_rotation(self.orbital_state(state), trackball);
+                let position = center + rotation * Vec3::new(0.0, 0.0, -1.0) * distance;
 
                 Mat4::from_quat(rotation.conjugate()) * Mat4::from_translation(-position)
             }
         }
     }
 
-    pub fn get_builtin_uniforms(&self) -> BuiltinUniforms {
-        let perspective = Mat4::perspective_lh(
-            self.config.camera.fov.to_radians(),
+    pub fn get_perspective_matrix(&self) -> Mat4 {
+        let camera = &self.config.camera;
+        let (near, far) = if self.config.reverse_z {
+            (camera.far, camera.near)
+        } else {
+            (camera.near, camera.far)
+        };
+        Mat4::perspective_lh(
+            self.config
+                .camera
+                .fov
+                .evaluate(self.start_time.elapsed().as_secs_f32())
+                .to_radians(),
             self.window_size.x / self.window_size.y,
-            0.01,
-            100.0,
+            near,
+            far,
         )
-        .to_cols_array_2d();
+    }
+
+    /// Returns the value depth buffers should be cleared with.
+    pub fn clear_depth(&self) -> f32 {
+        if self.config.reverse_z {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    /// Returns the color the window should be cleared with.
+    pub fn clear_color(&self) -> [f32; 4] {
+        let time = self.start_time.elapsed().as_secs_f32();
+        self.config.clear_color.map(|x| x.evaluate(time))
+    }
+
+    /// Returns the objects of a pass in the order they should be drawn.
+    fn draw_order(&self, pass: &LoadedPass) -> Vec<usize> {
+        let objects: Vec<_> = pass
+            .objects
+            .iter()
+            .map(|x| {
+                let object = &self.objects[*x];
+                (object.transparent, object.matrix.w_axis.truncate())
+            })
+            .collect();
+        let camera = self.get_camera_matrix().inverse().w_axis.truncate();
+        sort_draw_order(&objects, camera)
+            .into_iter()
+            .map(|x| pass.objects[x])
+            .collect()
+    }
+
+    /// Returns the time of the clock used by the pass.
+    fn pass_time(&self, pass: &LoadedPass) -> f32 {
+        match pass.clock {
+            Some(x) => self.clocks[x].time(),
+            None => self.start_time.elapsed().as_secs_f32(),
+        }
+    }
+
+    pub fn get_builtin_uniforms(&self) -> BuiltinUniforms {
+        let perspective = self.get_perspective_matrix();
+        let view = self.get_camera_matrix();
+        // The camera looks along positive z in view space.
+        let camera = view.inverse();
         BuiltinUniforms {
             time: self.start_time.elapsed().as_secs_f32(),
+            time_since_reload: self.load_time.elapsed().as_secs_f32(),
+            date: date::date(SystemTime::now()),
+            scroll: self.scroll,
+            seed: self.seed,
+            delta_time: self
+                .last_frame
+                .replace(Instant::now())
+                .elapsed()
+                .as_secs_f32(),
+            sun_direction: self
+                .config
+                .sun
+                .direction(self.start_time.elapsed().as_secs_f32())
+                .into(),
+            camera_position: camera.w_axis.truncate().into(),
+            camera_direction: camera.z_axis.truncate().normalize_or_zero().into(),
             model: Mat4::IDENTITY.to_cols_array_2d(),
-            view: self.get_camera_matrix().to_cols_array_2d(),
-            perspective,
+            normal_matrix: Mat3::IDENTITY.to_cols_array_2d(),
+            view: view.to_cols_array_2d(),
+            perspective: perspective.to_cols_array_2d(),
+            view_projection: (perspective * view).to_cols_array_2d(),
             mouse_x: self.mouse_pos.x,
             mouse_y: self.mouse_pos.y,
             mouse_pos: self.mouse_pos.into(),
+            // The click position is positive while the button is held and the y coordinate only
+            // in the first frame after the click.
+            mouse_buttons: [
+                self.mouse_drag.x,
+                self.mouse_drag.y,
+                if self.mouse_down {
+                    self.mouse_click.x
+                } else {
+                    -self.mouse_click.x
+                },
+                if self.mouse_clicked.replace(false) {
+                    self.mouse_click.y
+                } else {
+                    -self.mouse_click.y
+                },
+            ],
             window_width: self.window_size.x,
             window_height: self.window_size.y,
             window_size: self.window_size.into(),
+            // The window is 0x0 while minimized.
+            inv_window_size: self.window_size.max(Vec2::ONE).recip().into(),
+            iteration: 0,
+            frame_count: self
+                .frame_count
+                .replace(self.frame_count.get().wrapping_add(1)),
+            inverse_target_size: [
+                1.0 / self.window_size.x.max(1.0),
+                1.0 / self.window_size.y.max(1.0),
+            ],
+            keys: self.keys,
+        }
+    }
+
+    /// Returns the size of the surface a pass renders to.
+    fn target_size(&self, pass: &LoadedPass) -> Vec2 {
+        if let Some(region) = pass.target.as_ref().and_then(|x| x.region) {
+            return Vec2::new(region.width as f32, region.height as f32);
+        }
+        let texture = pass
+            .target
+            .as_ref()
+            .and_then(|x| x.color.first().map(|x| x.0).or(x.depth));
+        match texture {
+            Some(x) => {
+                let (width, height) = self.textures[x].dimensions();
+                Vec2::new(width as f32, height as f32)
+            }
+            None => self.window_size,
         }
     }
 
+    /// Create a texture to hold a copy of a texture which is both sampled and rendered to by a
+    /// pass.
+    fn create_feedback_texture(&self, texture: usize) -> Result<Texture2d> {
+        let (format, texture) = match self.textures[texture].kind {
+            LoadedTextureKind::File { ref texture } => (UncompressedFloatFormat::U8U8U8U8, texture),
+            LoadedTextureKind::SrgbFile { .. } => {
+                bail!("sRGB textures can not be both sampled and rendered to by a pass, set `srgb: false` on the texture")
+            }
+            LoadedTextureKind::Empty {
+                format,
+                ref texture,
+                ..
+            } => (format, texture),
+            LoadedTextureKind::Unsigned { .. } => {
+                bail!("Unsigned textures can not be both sampled and rendered to by a pass")
+            }
+            LoadedTextureKind::Depth { .. } => {
+                bail!("Tried to use depth texture as color attachment")
+            }
+            LoadedTextureKind::Lut { .. } => {
+                bail!("Tried to use LUT texture as color attachment")
+            }
+            LoadedTextureKind::Volume { .. } => {
+                bail!("Tried to use volume texture as color attachment")
+            }
+            LoadedTextureKind::Sequence { .. } => {
+                bail!("Tried to use sequence texture as color attachment")
+            }
+        };
+        Texture2d::empty_with_format(
+            &self.display,
+            format,
+            MipmapsOption::NoMipmap,
+            texture.get_width(),
+            texture.get_height().unwrap_or(1),
+        )
+        .context("failed to create texture")
+    }
+
+    /// Create the copies of all textures which are both sampled and rendered to by a pass,
+    /// indexed by texture.
+    pub fn create_feedback_textures(&self) -> Result<HashMap<usize, Texture2d>> {
+        let mut res = HashMap::new();
+        for (pass_id, pass) in self.passes.iter().enumerate() {
+            for x in pass.feedback.iter() {
+                if res.contains_key(x) {
+                    continue;
+                }
+                let texture = self.create_feedback_texture(*x).with_context(|| {
+                    format!("Failed to create feedback textures for pass {}", pass_id)
+                })?;
+                res.insert(*x, texture);
+            }
+        }
+        Ok(res)
+    }
+
     pub fn get_target<'a>(
         &'a self,
         pass_id: usize,
@@ -93,30 +390,60 @@ impl Config {
             .color
             .iter()
             .try_fold(Vec::new(), |mut acc, text| {
-                acc.push(match self.textures[text.0].kind {
-                    LoadedTextureKind::File { ref texture, .. } => {
-                        if pass
-                            .program
-                            .get_frag_data_location(text.1.as_str())
-                            .is_none()
-                        {
-                            bail!("Pass does not have fragment output `{}`", text.1.as_str())
+                if let LoadedTextureKind::Depth { .. } = self.textures[text.0].kind {
+                    bail!("Tried to use depth texture as color attachment")
+                }
+                if pass
+                    .program
+                    .get_frag_data_location(text.1.as_str())
+                    .is_none()
+                {
+                    bail!("Pass does not have fragment output `{}`", text.1.as_str())
+                }
+                let output_unsigned = pass
+                    .fragment
+                    .output_type(text.1.as_str())
+                    .map(|x| x.starts_with("uint") || x.starts_with("uvec"));
+                let kind = &self.textures[text.0].kind;
+                acc.push(match kind {
+                    LoadedTextureKind::File { .. } | LoadedTextureKind::Empty { .. } => {
+                        if output_unsigned == Some(true) {
+                            bail!(
+                                "Fragment output `{}` is unsigned but the target texture is not",
+                                text.1.as_str()
+                            )
                         }
-                        (text.1.as_str(), texture)
+                        let texture = kind.write_texture().unwrap();
+                        (text.1.as_str(), texture.to_color_attachment())
                     }
-                    LoadedTextureKind::Empty { ref texture, .. } => {
-                        if pass
-                            .program
-                            .get_frag_data_location(text.1.as_str())
-                            .is_none()
-                        {
-                            bail!("Pass does not have fragment output `{}`", text.1.as_str())
+                    LoadedTextureKind::SrgbFile { ref texture } => {
+                        if output_unsigned == Some(true) {
+                            bail!(
+                                "Fragment output `{}` is unsigned but the target texture is not",
+                                text.1.as_str()
+                            )
+                        }
+                        (text.1.as_str(), texture.to_color_attachment())
+                    }
+                    LoadedTextureKind::Unsigned { ref texture, .. } => {
+                        if output_unsigned == Some(false) {
+                            bail!(
+                                "Fragment output `{}` is not unsigned but the target texture is",
+                                text.1.as_str()
+                            )
                         }
-                        (text.1.as_str(), texture)
+                        (text.1.as_str(), texture.to_color_attachment())
                     }
-                    LoadedTextureKind::Depth { .. } => {
-                        bail!("Tried to use depth texture as color attachment")
+                    LoadedTextureKind::Lut { .. } => {
+                        bail!("Tried to use LUT texture as color attachment")
                     }
+                    LoadedTextureKind::Volume { .. } => {
+                        bail!("Tried to use volume texture as color attachment")
+                    }
+                    LoadedTextureKind::Sequence { .. } => {
+                        bail!("Tried to use sequence texture as color attachment")
+                    }
+                    LoadedTextureKind::Depth { .. } => unreachable!(),
                 });
                 Ok(acc)
             })
@@ -125,7 +452,7 @@ impl Config {
         match target.depth {
             Some(depth) => {
                 let depth_texture = match self.textures[depth].kind {
-                    LoadedTextureKind::Depth { ref texture, .. } => texture,
+                    LoadedTextureKind::Depth { ref texture, .. } => &**texture,
                     _ => bail!("Tried to use color texture as a depth attachment"),
                 };
 
@@ -144,131 +471,484 @@ impl Config {
     }
 
     pub fn render(&self, frame: &mut Frame) -> Result<bool> {
+        self.render_timed(frame, None)
+    }
+
+    /// Render the config, if queries are given the GPU time of each pass is recorded in the query
+    /// with the same index as the pass.
+    pub fn render_timed(
+        &self,
+        frame: &mut Frame,
+        queries: Option<&[TimeElapsedQuery]>,
+    ) -> Result<bool> {
+        let mut stages = Vec::new();
+        if self.tonemap.enabled() {
+            stages.push(PostStage::Tonemap(&self.tonemap));
+        }
+        match self.grading {
+            Some(ref grading) if grading.enabled => stages.push(PostStage::Grading(grading)),
+            _ => {}
+        }
+        if self.fxaa.enabled {
+            stages.push(PostStage::Fxaa(&self.fxaa));
+        }
+
+        let should_poll = match stages.first() {
+            Some(first) => {
+                let mut target = first.target(&self.display)?;
+                self.clear_target(&mut target);
+                self.render_passes(&mut target, queries)?
+            }
+            None => return self.render_passes(frame, queries),
+        };
+
+        // Every stage draws into the target of the next one, the last one draws to the frame.
+        for (idx, stage) in stages.iter().enumerate() {
+            match stages.get(idx + 1) {
+                Some(next) => {
+                    let mut target = next.target(&self.display)?;
+                    stage.apply(&mut target, &self.textures)?;
+                }
+                None => stage.apply(frame, &self.textures)?,
+            }
+        }
+        Ok(should_poll)
+    }
+
+    /// Clear an intermediate surface the same way the frame is cleared.
+    fn clear_target<S: Surface>(&self, target: &mut S) {
+        let clear_color = self.clear_color();
+        target.clear_color_and_depth(
+            (
+                clear_color[0],
+                clear_color[1],
+                clear_color[2],
+                clear_color[3],
+            ),
+            self.clear_depth(),
+        );
+    }
+
+    fn render_passes<S: Surface>(
+        &self,
+        frame: &mut S,
+        queries: Option<&[TimeElapsedQuery]>,
+    ) -> Result<bool> {
         let mut builtin_uniforms = self.get_builtin_uniforms();
+        let mut recorder = self.recorder.borrow_mut();
 
-        let mut should_poll = false;
+        let mut should_poll = self.config.is_animated()
+            || matches!(self.camera, LoadedCamera::Flying(ref x) if x.is_moving());
 
         for (pass_id, pass) in self.passes.iter().enumerate() {
-            if let Some(x) = &pass.target {
-                let clear_color = egui::Rgba::from_rgb(0.1, 0.3, 0.2);
+            let target_size = self.target_size(pass).max(Vec2::ONE);
+            builtin_uniforms.inverse_target_size = target_size.recip().into();
+            builtin_uniforms.time = self.pass_time(pass);
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.record_pass(pass_id, pass, &builtin_uniforms);
+            }
+
+            if let Some(x) = pass.target.as_ref().filter(|x| x.clear) {
+                let clear_color = x.clear_color;
                 self.get_target(pass_id, pass, &x)
                     .with_context(|| {
                         format!("Failed to create traget for render pass {}", pass_id)
                     })?
-                    .clear_color_and_depth(
-                        (
+                    .clear(
+                        x.region.as_ref(),
+                        Some((
                             clear_color[0],
                             clear_color[1],
                             clear_color[2],
                             clear_color[3],
-                        ),
-                        1.0,
+                        )),
+                        false,
+                        Some(x.clear_depth.unwrap_or_else(|| self.clear_depth())),
+                        None,
                     );
             }
-            let mut texture_samplers = Vec::new();
-            let mut depth_texture_samplers = Vec::new();
-
-            for (text_id, name) in pass.textures.iter() {
-                match self.textures[*text_id].kind {
-                    LoadedTextureKind::File { ref texture, .. }
-                    | LoadedTextureKind::Empty { ref texture, .. } => {
-                        let sampler = Sampler::new(texture);
-                        let sampler = self.textures[*text_id].config.apply_to_sampler(sampler);
+            let timed_parameters;
+            let draw_parameters = match queries {
+                Some(x) => {
+                    timed_parameters = DrawParameters {
+                        time_elapsed_query: Some(&x[pass_id]),
+                        ..pass.draw_parameters.clone()
+                    };
+                    &timed_parameters
+                }
+                None => &pass.draw_parameters,
+            };
+
+            let feedback: Vec<_> = pass
+                .feedback
+                .iter()
+                .map(|x| (*x, &self.feedback_textures[x]))
+                .collect();
+
+            let draw_order = self.draw_order(pass);
+            for iteration in 0..pass.iterations {
+                builtin_uniforms.iteration = iteration as i32;
+
+                // Copy the result of the previous iteration so it can be read while rendering to
+                // the texture.
+                for (text_id, copy) in feedback.iter() {
+                    let kind = &self.textures[*text_id].kind;
+                    match kind {
+                        LoadedTextureKind::File { .. } | LoadedTextureKind::Empty { .. } => kind
+                            .write_texture()
+                            .unwrap()
+                            .as_surface()
+                            .fill(&copy.as_surface(), MagnifySamplerFilter::Nearest),
+                        LoadedTextureKind::SrgbFile { .. }
+                        | LoadedTextureKind::Unsigned { .. }
+                        | LoadedTextureKind::Depth { .. }
+                        | LoadedTextureKind::Lut { .. }
+                        | LoadedTextureKind::Volume { .. }
+                        | LoadedTextureKind::Sequence { .. } => unreachable!(),
+                    }
+                }
+
+                let mut texture_samplers = Vec::new();
+                let mut srgb_texture_samplers = Vec::new();
+                let mut unsigned_texture_samplers = Vec::new();
+                let mut depth_texture_samplers = Vec::new();
+                let mut texture_3d_samplers = Vec::new();
+
+                // Textures are bound to `texture_<name>` unless a sampler uniform is bound to a
+                // texture from the GUI, which is added last so it replaces the default.
+                let overrides = pass
+                    .uniforms
+                    .iter()
+                    .filter_map(|(name, x)| match x.binding {
+                        UniformBinding::Texture(idx) => Some((idx, name.clone())),
+                        _ => None,
+                    });
+                let bound = pass
+                    .textures
+                    .iter()
+                    .map(|(idx, name)| (*idx, format!("texture_{}", name)))
+                    .chain(overrides);
+                for (text_id, name) in bound {
+                    if let Some((_, copy)) = feedback.iter().find(|x| x.0 == text_id) {
+                        let sampler = Sampler::new(*copy);
+                        let sampler = self.textures[text_id].config.apply_to_sampler(sampler);
                         texture_samplers.push((name, sampler));
+                        continue;
                     }
-                    LoadedTextureKind::Depth { ref texture, .. } => {
-                        let sampler = Sampler::new(texture);
-                        let sampler = self.textures[*text_id].config.apply_to_sampler(sampler);
-                        depth_texture_samplers.push((name, sampler));
+                    let kind = &self.textures[text_id].kind;
+                    if let LoadedTextureKind::Sequence { .. } = kind {
+                        should_poll = true;
                     }
-                };
-            }
+                    let kind = kind.frame(builtin_uniforms.time);
+                    match kind {
+                        LoadedTextureKind::Sequence { .. } => unreachable!(),
+                        LoadedTextureKind::File { .. } | LoadedTextureKind::Empty { .. } => {
+                            let sampler = Sampler::new(kind.read_texture().unwrap());
+                            let sampler = self.textures[text_id].config.apply_to_sampler(sampler);
+                            texture_samplers.push((name, sampler));
+                        }
+                        LoadedTextureKind::SrgbFile { ref texture } => {
+                            let sampler = Sampler::new(&**texture);
+                            let sampler = self.textures[text_id].config.apply_to_sampler(sampler);
+                            srgb_texture_samplers.push((name, sampler));
+                        }
+                        LoadedTextureKind::Unsigned { ref texture, .. } => {
+                            let sampler = Sampler::new(&**texture);
+                            let sampler = self.textures[text_id].config.apply_to_sampler(sampler);
+                            unsigned_texture_samplers.push((name, sampler));
+                        }
+                        LoadedTextureKind::Depth { ref texture, .. } => {
+                            let sampler = Sampler::new(&**texture);
+                            let sampler = self.textures[text_id].config.apply_to_sampler(sampler);
+                            depth_texture_samplers.push((name, sampler));
+                        }
+                        LoadedTextureKind::Lut { ref texture, .. }
+                        | LoadedTextureKind::Volume { ref texture } => {
+                            let sampler = Sampler::new(&**texture);
+                            let sampler = self.textures[text_id].config.apply_to_sampler(sampler);
+                            texture_3d_samplers.push((name, sampler));
+                        }
+                    };
+                }
 
-            for object in pass.objects.iter().copied() {
-                let object = &self.objects[object];
-                builtin_uniforms.model = object.matrix.to_cols_array_2d();
+                let buffers: Vec<_> = pass
+                    .buffers
+                    .iter()
+                    .map(|(idx, name)| (format!("buffer_{}", name), &self.buffers[*idx].buffer))
+                    .collect();
 
-                let mut uniforms = DynUniformStorage::new();
+                // Viewport sized textures are resized with the window, so the size is read every frame.
+                let texture_sizes: Vec<_> = pass
+                    .texture_sizes
+                    .iter()
+                    .map(|(idx, name)| {
+                        let (width, height) = self.textures[*idx].dimensions();
+                        (name, [width as f32, height as f32])
+                    })
+                    .collect();
 
-                for (name, value) in pass.uniforms.iter() {
-                    match value.binding {
-                        UniformBinding::Unbound => {}
-                        UniformBinding::Custom(ref x) => {
-                            uniforms.add(name.clone(), x);
-                        }
-                        UniformBinding::Builtin(builtin) => match builtin {
-                            BuiltinUniform::View => {
-                                uniforms.add(name.clone(), &builtin_uniforms.view)
-                            }
-                            BuiltinUniform::Model => {
-                                uniforms.add(name.clone(), &builtin_uniforms.model)
-                            }
-                            BuiltinUniform::Perspective => {
-                                uniforms.add(name.clone(), &builtin_uniforms.perspective)
-                            }
-                            BuiltinUniform::Time => {
-                                should_poll = true;
-                                uniforms.add(name.clone(), &builtin_uniforms.time)
-                            }
-                            BuiltinUniform::MouseX => {
-                                uniforms.add(name.clone(), &builtin_uniforms.mouse_x)
-                            }
-                            BuiltinUniform::MouseY => {
-                                uniforms.add(name.clone(), &builtin_uniforms.mouse_y)
-                            }
-                            BuiltinUniform::MousePos => {
-                                uniforms.add(name.clone(), &builtin_uniforms.mouse_pos)
-                            }
-                            BuiltinUniform::WindowWidth => {
-                                uniforms.add(name.clone(), &builtin_uniforms.window_width)
-                            }
-                            BuiltinUniform::WindowHeight => {
-                                uniforms.add(name.clone(), &builtin_uniforms.window_height)
+                for object in draw_order.iter().copied() {
+                    let object = &self.objects[object];
+                    builtin_uniforms.model = object.matrix.to_cols_array_2d();
+                    builtin_uniforms.normal_matrix = normal_matrix(object.matrix);
+
+                    let mut uniforms = DynUniformStorage::new();
+
+                    for (name, value) in pass.uniforms.iter() {
+                        match value.binding {
+                            // Sampler overrides are bound together with the other textures.
+                            UniformBinding::Unbound | UniformBinding::Texture(_) => {}
+                            UniformBinding::Custom(ref x) => {
+                                uniforms.add(name.clone(), x);
                             }
-                            BuiltinUniform::WindowSize => {
-                                uniforms.add(name.clone(), &builtin_uniforms.window_size)
+                            UniformBinding::Builtin(builtin) => {
+                                match builtin {
+                                    BuiltinUniform::View => {
+                                        uniforms.add(name.clone(), &builtin_uniforms.view)
+                                    }
+                                    BuiltinUniform::Model => {
+                                        uniforms.add(name.clone(), &builtin_uniforms.model)
+                                    }
+                                    BuiltinUniform::NormalMatrix => {
+                                        uniforms.add(name.clone(), &builtin_uniforms.normal_matrix)
+                                    }
+                                    BuiltinUniform::Perspective => {
+                                        uniforms.add(name.clone(), &builtin_uniforms.perspective)
+                                    }
+                                    BuiltinUniform::ViewProjection => uniforms
+                                        .add(name.clone(), &builtin_uniforms.view_projection),
+                                    BuiltinUniform::Time => {
+                                        should_poll = true;
+                                        uniforms.add(name.clone(), &builtin_uniforms.time)
+                                    }
+                                    BuiltinUniform::Scroll => {
+                                        uniforms.add(name.clone(), &builtin_uniforms.scroll)
+                                    }
+                                    BuiltinUniform::Seed => {
+                                        uniforms.add(name.clone(), &builtin_uniforms.seed)
+                                    }
+                                    BuiltinUniform::Date => {
+                                        should_poll = true;
+                                        uniforms.add(name.clone(), &builtin_uniforms.date)
+                                    }
+                                    BuiltinUniform::TimeSinceReload => {
+                                        should_poll = true;
+                                        uniforms
+                                            .add(name.clone(), &builtin_uniforms.time_since_reload)
+                                    }
+                                    BuiltinUniform::DeltaTime => {
+                                        should_poll = true;
+                                        uniforms.add(name.clone(), &builtin_uniforms.delta_time)
+                                    }
+                                    BuiltinUniform::SunDirection => {
+                                        should_poll = true;
+                                        uniforms.add(name.clone(), &builtin_uniforms.sun_direction)
+                                    }
+                                    BuiltinUniform::CameraPosition => uniforms
+                                        .add(name.clone(), &builtin_uniforms.camera_position),
+                                    BuiltinUniform::CameraDirection => uniforms
+                                        .add(name.clone(), &builtin_uniforms.camera_direction),
+                                    BuiltinUniform::MouseX => {
+                                        uniforms.add(name.clone(), &builtin_uniforms.mouse_x)
+                                    }
+                                    BuiltinUniform::MouseY => {
+                                        uniforms.add(name.clone(), &builtin_uniforms.mouse_y)
+                                    }
+                                    BuiltinUniform::MousePos => {
+                                        uniforms.add(name.clone(), &builtin_uniforms.mouse_pos)
+                                    }
+                                    BuiltinUniform::MouseButtons => {
+                                        // Keep rendering so the click state changes the next frame.
+                                        should_poll |= self.mouse_down;
+                                        uniforms.add(name.clone(), &builtin_uniforms.mouse_buttons)
+                                    }
+                                    BuiltinUniform::WindowWidth => {
+                                        uniforms.add(name.clone(), &builtin_uniforms.window_width)
+                                    }
+                                    BuiltinUniform::WindowHeight => {
+                                        uniforms.add(name.clone(), &builtin_uniforms.window_height)
+                                    }
+                                    BuiltinUniform::WindowSize => {
+                                        uniforms.add(name.clone(), &builtin_uniforms.window_size)
+                                    }
+                                    BuiltinUniform::InvWindowSize => uniforms
+                                        .add(name.clone(), &builtin_uniforms.inv_window_size),
+                                    BuiltinUniform::Iteration => {
+                                        uniforms.add(name.clone(), &builtin_uniforms.iteration)
+                                    }
+                                    BuiltinUniform::FrameCount => {
+                                        should_poll = true;
+                                        uniforms.add(name.clone(), &builtin_uniforms.frame_count)
+                                    }
+                                    BuiltinUniform::InverseTargetSize => uniforms
+                                        .add(name.clone(), &builtin_uniforms.inverse_target_size),
+                                    BuiltinUniform::Keyboard => {
+                                        should_poll = true;
+                                        let len = value.kind.size.unwrap_or(1).min(KEY_COUNT);
+                                        for (idx, x) in
+                                            builtin_uniforms.keys[..len].iter().enumerate()
+                                        {
+                                            uniforms.add(format!("{}[{}]", name, idx), x);
+                                        }
+                                    }
+                                }
                             }
-                        },
+                        }
+                    }
+                    for (name, s) in texture_samplers.iter() {
+                        uniforms.add(name.clone(), s)
                     }
-                }
-                for (name, s) in texture_samplers.iter() {
-                    uniforms.add(format!("texture_{}", name), s)
-                }
 
-                for (name, s) in depth_texture_samplers.iter() {
-                    uniforms.add(format!("texture_{}", name), s)
-                }
+                    for (name, s) in srgb_texture_samplers.iter() {
+                        uniforms.add(name.clone(), s)
+                    }
 
-                match pass.target {
-                    None => {
-                        frame
-                            .draw(
-                                &object.vertex,
-                                &object.index,
-                                &pass.program,
-                                &uniforms,
-                                &pass.draw_parameters,
-                            )
-                            .with_context(|| format!("Could not render pass {}", pass_id))?;
+                    for (name, s) in unsigned_texture_samplers.iter() {
+                        uniforms.add(name.clone(), s)
                     }
-                    Some(ref target) => {
-                        let mut target =
-                            self.get_target(pass_id, pass, target).with_context(|| {
-                                format!("Failed to create traget for render pass {}", pass_id)
-                            })?;
-                        target
-                            .draw(
-                                &object.vertex,
-                                &object.index,
-                                &pass.program,
-                                &uniforms,
-                                &pass.draw_parameters,
-                            )
-                            .with_context(|| format!("Could not render pass {}", pass_id))?
+
+                    for (name, s) in depth_texture_samplers.iter() {
+                        uniforms.add(name.clone(), s)
+                    }
+
+                    for (name, s) in texture_3d_samplers.iter() {
+                        uniforms.add(name.clone(), s)
+                    }
+
+                    for (name, b) in buffers.iter() {
+                        uniforms.add(name.clone(), b)
+                    }
+
+                    for (name, size) in texture_sizes.iter() {
+                        uniforms.add(name.to_string(), size)
                     }
+
+                    match pass.target {
+                        None => {
+                            object
+                                .draw(
+                                    frame,
+                                    &pass.program,
+                                    &uniforms,
+                                    draw_parameters,
+                                    pass.draw_as,
+                                )
+                                .with_context(|| format!("Could not render pass {}", pass_id))?;
+                        }
+                        Some(ref target) => {
+                            let mut target =
+                                self.get_target(pass_id, pass, target).with_context(|| {
+                                    format!("Failed to create traget for render pass {}", pass_id)
+                                })?;
+                            object
+                                .draw(
+                                    &mut target,
+                                    &pass.program,
+                                    &uniforms,
+                                    draw_parameters,
+                                    pass.draw_as,
+                                )
+                                .with_context(|| format!("Could not render pass {}", pass_id))?
+                        }
+                    }
+                }
+            }
+
+            // Later passes may sample lower mip levels of what this pass rendered.
+            if let Some(target) = &pass.target {
+                for (idx, _) in target.color.iter() {
+                    self.textures[*idx].generate_mipmaps();
                 }
             }
         }
+        if let Some(x) = recorder.as_mut() {
+            let time = self.start_time.elapsed().as_secs_f32();
+            if let Err(e) = x.finish_frame(builtin_uniforms.frame_count, time) {
+                error!("{:?}", e);
+                *recorder = None;
+            }
+        }
+
+        // Passes sample the previous frame of double buffered textures until every pass is done.
+        for texture in self.textures.iter() {
+            texture.swap();
+        }
+
         Ok(should_poll)
     }
 }
+
+impl LoadedObject {
+    /// Draw the object, binding the tangent buffer if the object has one.
+    fn draw<S: Surface, U: Uniforms>(
+        &self,
+        surface: &mut S,
+        program: &Program,
+        uniforms: &U,
+        draw_parameters: &DrawParameters,
+        draw_as: Option<ser::DrawAs>,
+    ) -> Result<(), DrawError> {
+        let indices = match draw_as {
+            None | Some(ser::DrawAs::Triangles) => IndicesSource::from(&self.index),
+            Some(ser::DrawAs::Lines) => IndicesSource::from(&self.edges),
+            Some(ser::DrawAs::Points) => IndicesSource::from(NoIndices(PrimitiveType::Points)),
+        };
+        let culled = match self.front_face {
+            Some(ser::FrontFace::Clockwise) => BackfaceCullingMode::CullCounterClockwise,
+            Some(ser::FrontFace::CounterClockwise) => BackfaceCullingMode::CullClockwise,
+            None => draw_parameters.backface_culling,
+        };
+        let object_parameters;
+        let draw_parameters = match draw_parameters.backface_culling {
+            BackfaceCullingMode::CullingDisabled => draw_parameters,
+            x if x == culled => draw_parameters,
+            _ => {
+                object_parameters = DrawParameters {
+                    backface_culling: culled,
+                    ..draw_parameters.clone()
+                };
+                &object_parameters
+            }
+        };
+        match self.tangent {
+            Some(ref tangent) => surface.draw(
+                (&self.vertex, tangent),
+                indices,
+                program,
+                uniforms,
+                draw_parameters,
+            ),
+            None => surface.draw(&self.vertex, indices, program, uniforms, draw_parameters),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sort_draw_order;
+    use glam::f32::Vec3;
+
+    #[test]
+    fn opaque_before_transparent() {
+        let objects = [
+            (true, Vec3::new(0.0, 0.0, 1.0)),
+            (false, Vec3::new(0.0, 0.0, 100.0)),
+            (true, Vec3::new(0.0, 0.0, 50.0)),
+            (false, Vec3::new(0.0, 0.0, 2.0)),
+        ];
+        // Opaque objects keep their order no matter how far away they are.
+        assert_eq!(sort_draw_order(&objects, Vec3::ZERO), vec![1, 3, 2, 0]);
+    }
+
+    #[test]
+    fn transparent_back_to_front() {
+        let objects = [
+            (true, Vec3::new(0.0, 0.0, 1.0)),
+            (true, Vec3::new(0.0, 0.0, 3.0)),
+            (true, Vec3::new(0.0, 0.0, 2.0)),
+        ];
+        assert_eq!(sort_draw_order(&objects, Vec3::ZERO), vec![1, 2, 0]);
+        let camera = Vec3::new(0.0, 0.0, 4.0);
+        assert_eq!(sort_draw_order(&objects, camera), vec![0, 2, 1]);
+    }
+}
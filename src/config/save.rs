@@ -0,0 +1,425 @@
+//! Writing edited uniform values back into the config file.
+//!
+//! Only the `uniforms` field of each pass is rewritten, the rest of the file including comments
+//! and formatting is kept as is.
+use super::{
+    pass::{UniformBinding, UniformConfig},
+    Config,
+};
+use anyhow::{Context, Result};
+use std::{collections::BTreeMap, ffi::OsStr, fs, path::Path};
+
+/// An entry of a struct, map or list in the config source.
+#[derive(Debug)]
+struct Item {
+    /// Start of the first character which is not whitespace or a comment.
+    start: usize,
+    /// End of the last character which is not whitespace or a comment.
+    end: usize,
+    /// Position of the comma following the entry if there is one.
+    comma: Option<usize>,
+}
+
+/// Returns the position after the string or comment starting at `pos`, or `None` if there is no
+/// string or comment at the position.
+fn skip_ignored(s: &[u8], pos: usize) -> Option<usize> {
+    match s[pos] {
+        b'"' => {
+            let mut idx = pos + 1;
+            while idx < s.len() {
+                match s[idx] {
+                    b'\\' => idx += 2,
+                    b'"' => return Some(idx + 1),
+                    _ => idx += 1,
+                }
+            }
+            Some(s.len())
+        }
+        b'/' if s.get(pos + 1) == Some(&b'/') => Some(
+            s[pos..]
+                .iter()
+                .position(|x| *x == b'\n')
+                .map_or(s.len(), |x| pos + x),
+        ),
+        b'/' if s.get(pos + 1) == Some(&b'*') => Some(
+            s[pos + 2..]
+                .windows(2)
+                .position(|x| x == b"*/")
+                .map_or(s.len(), |x| pos + 2 + x + 2),
+        ),
+        _ => None,
+    }
+}
+
+/// Returns the position of the first bracket at or after `pos`, skipping strings and comments.
+fn find_open(s: &[u8], mut pos: usize) -> Option<usize> {
+    while pos < s.len() {
+        if let Some(next) = skip_ignored(s, pos) {
+            // Strings before the bracket are not expected, only comments.
+            if s[pos] == b'"' {
+                return None;
+            }
+            pos = next;
+            continue;
+        }
+        if matches!(s[pos], b'{' | b'(' | b'[') {
+            return Some(pos);
+        }
+        pos += 1;
+    }
+    None
+}
+
+/// Split the contents of the bracket at `open` into its entries, returns the entries and the
+/// position of the closing bracket.
+fn split_items(s: &[u8], open: usize) -> Result<(Vec<Item>, usize)> {
+    let mut items = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+    let mut depth = 0;
+    let mut pos = open + 1;
+    while pos < s.len() {
+        let c = s[pos];
+        if let Some(next) = skip_ignored(s, pos) {
+            if c == b'"' {
+                let span = current.get_or_insert((pos, next));
+                span.1 = next;
+            }
+            pos = next;
+            continue;
+        }
+        match c {
+            b'{' | b'(' | b'[' => depth += 1,
+            b'}' | b')' | b']' if depth == 0 => {
+                if let Some((start, end)) = current {
+                    items.push(Item {
+                        start,
+                        end,
+                        comma: None,
+                    });
+                }
+                return Ok((items, pos));
+            }
+            b'}' | b')' | b']' => depth -= 1,
+            b',' if depth == 0 => {
+                if let Some((start, end)) = current.take() {
+                    items.push(Item {
+                        start,
+                        end,
+                        comma: Some(pos),
+                    });
+                }
+                pos += 1;
+                continue;
+            }
+            _ => {}
+        }
+        if !c.is_ascii_whitespace() {
+            let span = current.get_or_insert((pos, pos + 1));
+            span.1 = pos + 1;
+        }
+        pos += 1;
+    }
+    bail!("Unclosed bracket at byte {}", open)
+}
+
+/// Returns the key of a struct or map entry and the position its value starts at.
+fn split_field(s: &[u8], item: &Item) -> Option<(String, usize)> {
+    let mut pos = item.start;
+    while pos < item.end {
+        if let Some(next) = skip_ignored(s, pos) {
+            pos = next;
+            continue;
+        }
+        if s[pos] == b':' {
+            let key = String::from_utf8_lossy(&s[item.start..pos]);
+            let key = key.trim().trim_matches('"').to_string();
+            let value = (pos + 1..item.end).find(|x| !s[*x].is_ascii_whitespace())?;
+            return Some((key, value));
+        }
+        pos += 1;
+    }
+    None
+}
+
+/// Returns the whitespace at the start of the line containing `pos`.
+fn indentation(source: &str, pos: usize) -> &str {
+    let line_start = source[..pos].rfind('\n').map_or(0, |x| x + 1);
+    let line = &source[line_start..];
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// Replace the `uniforms` field of every pass in the source of a config with the given values.
+fn rewrite(source: &str, uniforms: &[String], json: bool) -> Result<String> {
+    let s = source.as_bytes();
+    let root = find_open(s, 0).context("Could not find the start of the config")?;
+    let (fields, _) = split_items(s, root)?;
+    let passes = fields
+        .iter()
+        .filter_map(|x| split_field(s, x))
+        .find(|x| x.0 == "passes")
+        .context("Could not find the `passes` field")?;
+    ensure!(s[passes.1] == b'[', "Expected a list of passes");
+    let (passes, _) = split_items(s, passes.1)?;
+    ensure!(
+        passes.len() == uniforms.len(),
+        "Expected {} passes in the config file found {}",
+        uniforms.len(),
+        passes.len()
+    );
+
+    // Edits are collected as (start, end, replacement) and applied back to front.
+    let mut edits = Vec::new();
+    for (pass, value) in passes.iter().zip(uniforms) {
+        let open = find_open(s, pass.start).context("Expected a pass")?;
+        let (fields, _) = split_items(s, open)?;
+        let existing = fields
+            .iter()
+            .filter_map(|x| split_field(s, x))
+            .find(|x| x.0 == "uniforms");
+        match existing {
+            Some((_, start)) => {
+                let indent = indentation(source, start);
+                let value = value.replace('\n', &format!("\n{}", indent));
+                let end = match s[start] {
+                    b'{' => split_items(s, start)?.1 + 1,
+                    _ => bail!("Expected the `uniforms` of a pass to be a map"),
+                };
+                edits.push((start, end, value));
+            }
+            None => {
+                let key = if json { "\"uniforms\"" } else { "uniforms" };
+                let (pos, separator, indent) = match fields.last() {
+                    Some(last) => match last.comma {
+                        Some(comma) => (comma + 1, "", indentation(source, last.start)),
+                        None => (last.end, ",", indentation(source, last.start)),
+                    },
+                    None => (open + 1, "", indentation(source, open)),
+                };
+                let value = value.replace('\n', &format!("\n{}", indent));
+                let text = format!("{}\n{}{}: {}", separator, indent, key, value);
+                // Keep a trailing comma if the pass already used one.
+                let text = if fields.last().and_then(|x| x.comma).is_some() {
+                    format!("{},", text)
+                } else {
+                    text
+                };
+                edits.push((pos, pos, text));
+            }
+        }
+    }
+
+    let mut res = source.to_string();
+    for (start, end, text) in edits.into_iter().rev() {
+        res.replace_range(start..end, &text);
+    }
+    Ok(res)
+}
+
+impl Config {
+    /// Returns the uniforms of every pass as they would be written to the config, with the
+    /// values currently bound to custom uniforms.
+    fn edited_uniforms(&self) -> Vec<BTreeMap<String, UniformConfig>> {
+        self.passes
+            .iter()
+            .zip(self.config.passes.iter())
+            .map(|(pass, config)| {
+                let mut res: BTreeMap<_, _> = config
+                    .uniforms
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect();
+                for (name, data) in pass.uniforms.iter() {
+                    if let UniformBinding::Custom(value) = data.binding {
+                        let entry = res
+                            .entry(name.clone())
+                            .or_insert(UniformConfig::Value(value));
+                        entry.set_value(value);
+                    }
+                }
+                res
+            })
+            .collect()
+    }
+
+    /// Write the values of custom uniforms into the `uniforms` of the passes in the config file.
+    ///
+    /// The file is written to a temporary file first which then replaces the config, so the
+    /// config is never left half written.
+    pub fn save_uniforms(&self, path: &Path) -> Result<()> {
+        let json = match path.extension().and_then(OsStr::to_str) {
+            Some("ron") => false,
+            Some("json") => true,
+            _ => bail!("Invalid config extension!"),
+        };
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config `{}`", path.display()))?;
+        let uniforms = self
+            .edited_uniforms()
+            .iter()
+            .map(|x| {
+                if json {
+                    serde_json::to_string_pretty(x).map_err(anyhow::Error::from)
+                } else {
+                    let pretty = ron::ser::PrettyConfig::new().indentor("    ".to_string());
+                    ron::ser::to_string_pretty(x, pretty).map_err(anyhow::Error::from)
+                }
+            })
+            .collect::<Result<Vec<_>>>()
+            .context("Failed to serialize uniforms")?;
+        let source = rewrite(&source, &uniforms, json)
+            .with_context(|| format!("Failed to update config `{}`", path.display()))?;
+
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, source).with_context(|| format!("Failed to write `{}`", tmp.display()))?;
+        fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to replace config `{}`", path.display()))?;
+        info!("saved uniforms to `{}`", path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::rewrite;
+
+    const RON_VALUE: &str = "{\n    \"scale\": 2.0,\n}";
+    const JSON_VALUE: &str = "{\n  \"scale\": 2.0\n}";
+
+    fn rewrite_ron(source: &str) -> String {
+        rewrite(source, &[RON_VALUE.to_string()], false).unwrap()
+    }
+
+    #[test]
+    fn replace_existing_uniforms() {
+        let source = r#"(
+    passes: [
+        (
+            vertex_shader: "pass.vert",
+            uniforms: {
+                "scale": 1.0,
+            },
+            fragment_shader: "pass.frag",
+        ),
+    ],
+)"#;
+        let expected = r#"(
+    passes: [
+        (
+            vertex_shader: "pass.vert",
+            uniforms: {
+                "scale": 2.0,
+            },
+            fragment_shader: "pass.frag",
+        ),
+    ],
+)"#;
+        assert_eq!(rewrite_ron(source), expected);
+    }
+
+    #[test]
+    fn insert_after_trailing_comma() {
+        let source = r#"(
+    passes: [
+        (
+            vertex_shader: "pass.vert",
+            fragment_shader: "pass.frag",
+        ),
+    ],
+)"#;
+        let expected = r#"(
+    passes: [
+        (
+            vertex_shader: "pass.vert",
+            fragment_shader: "pass.frag",
+            uniforms: {
+                "scale": 2.0,
+            },
+        ),
+    ],
+)"#;
+        assert_eq!(rewrite_ron(source), expected);
+    }
+
+    #[test]
+    fn insert_without_trailing_comma() {
+        let source = r#"(
+    passes: [
+        (
+            vertex_shader: "pass.vert",
+            fragment_shader: "pass.frag"
+        ),
+    ],
+)"#;
+        let expected = r#"(
+    passes: [
+        (
+            vertex_shader: "pass.vert",
+            fragment_shader: "pass.frag",
+            uniforms: {
+                "scale": 2.0,
+            }
+        ),
+    ],
+)"#;
+        assert_eq!(rewrite_ron(source), expected);
+    }
+
+    #[test]
+    fn ignore_brackets_in_comments_and_strings() {
+        let source = r#"(
+    // The passes: [ are listed below, {
+    passes: [
+        (
+            vertex_shader: "weird, (name].vert", /* ], } */
+            uniforms: { "scale": 1.0 }, // closing }
+        ),
+    ],
+)"#;
+        let expected = r#"(
+    // The passes: [ are listed below, {
+    passes: [
+        (
+            vertex_shader: "weird, (name].vert", /* ], } */
+            uniforms: {
+                "scale": 2.0,
+            }, // closing }
+        ),
+    ],
+)"#;
+        assert_eq!(rewrite_ron(source), expected);
+    }
+
+    #[test]
+    fn insert_into_json() {
+        let source = r#"{
+    "passes": [
+        {
+            "vertex_shader": "pass.vert"
+        }
+    ]
+}"#;
+        let expected = r#"{
+    "passes": [
+        {
+            "vertex_shader": "pass.vert",
+            "uniforms": {
+              "scale": 2.0
+            }
+        }
+    ]
+}"#;
+        let res = rewrite(source, &[JSON_VALUE.to_string()], true).unwrap();
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn pass_count_mismatch() {
+        let source = "(passes: [(vertex_shader: \"pass.vert\")])";
+        let uniforms = [RON_VALUE.to_string(), RON_VALUE.to_string()];
+        let error = rewrite(source, &uniforms, false).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Expected 2 passes in the config file found 1"
+        );
+    }
+}
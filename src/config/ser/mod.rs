@@ -1,7 +1,8 @@
-use std::collections::HashMap;
+use anyhow::{Context, Result};
+use std::{collections::HashMap, ffi::OsStr, fs::File, path::Path};
 
-use crate::geom::Geometry;
-use glam::f32::Vec3;
+use crate::geom::{BufferUsage, Geometry, Shading};
+use glam::f32::{Quat, Vec3};
 use serde::Deserialize;
 
 mod settings;
@@ -10,19 +11,62 @@ use settings::Settings;
 mod texture;
 pub use texture::*;
 
-use super::pass::CustomUniform;
+use super::pass::UniformConfig;
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, PartialEq)]
 pub enum ObjectKind {
     #[serde(rename = "geometry")]
     Geometry(Geometry),
+    /// A Wavefront OBJ model loaded from a path relative to the config file.
+    #[serde(rename = "model")]
+    Model { path: String },
+    /// A mesh loaded from a glTF 2.0 file relative to the config file, either `.gltf` or `.glb`.
+    ///
+    /// Uses the first primitive of the mesh with the given name or of the first mesh if no name
+    /// is given.
+    #[serde(rename = "gltf")]
+    Gltf {
+        path: String,
+        #[serde(default)]
+        mesh: Option<String>,
+    },
+    /// A mesh loaded from binary files relative to the config file.
+    ///
+    /// The vertex file contains the attributes of the format as interleaved little endian `f32`s,
+    /// the index file contains little endian `u32` indices of a triangle list.
+    #[serde(rename = "binary")]
+    Binary {
+        vertices: String,
+        indices: String,
+        #[serde(default = "default_vertex_format")]
+        format: Vec<VertexAttribute>,
+    },
+}
+
+/// An attribute of the vertices in a binary mesh file.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VertexAttribute {
+    #[serde(rename = "position")]
+    Position,
+    #[serde(rename = "normal")]
+    Normal,
+    #[serde(rename = "tex_coord")]
+    TexCoord,
+}
+
+fn default_vertex_format() -> Vec<VertexAttribute> {
+    vec![
+        VertexAttribute::Position,
+        VertexAttribute::Normal,
+        VertexAttribute::TexCoord,
+    ]
 }
 
 const fn default_object_scale() -> Vec3 {
     Vec3::ONE
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, PartialEq)]
 pub struct Object {
     pub name: String,
     pub kind: ObjectKind,
@@ -32,23 +76,93 @@ pub struct Object {
     pub scale: Vec3,
     #[serde(default)]
     pub rotation: Vec3,
+    /// Generate a `tangent` vertex attribute for normal mapping.
+    #[serde(default)]
+    pub tangents: bool,
+    /// Winding of the front faces of the object, overrides which faces are culled by the pass.
+    ///
+    /// Only has an effect when the pass has culling enabled.
+    #[serde(default)]
+    pub front_face: Option<FrontFace>,
+    /// How the vertex buffer is used, only objects which are updated while running need to set
+    /// this.
+    #[serde(default)]
+    pub usage: BufferUsage,
+    /// Draw the object after the opaque objects of a pass, sorted back to front.
+    ///
+    /// Objects are opaque by default and drawn in the order the pass lists them.
+    #[serde(default)]
+    pub transparent: bool,
+    /// Override the shading of the normals of the object.
+    ///
+    /// By default the normals are used as generated, which is flat for cubes and smooth for
+    /// curved shapes.
+    #[serde(default)]
+    pub shading: Option<Shading>,
+    /// Flip faces of models which are wound opposite to their normals.
+    ///
+    /// Without this the amount of inconsistently wound faces is only reported as a warning.
+    #[serde(default)]
+    pub fix_winding: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrontFace {
+    #[serde(rename = "cw")]
+    Clockwise,
+    #[serde(rename = "ccw")]
+    CounterClockwise,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, PartialEq)]
 #[serde(untagged)]
 pub enum TextureRef {
     Name(String),
     Renamed { name: String, r#as: String },
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, PartialEq)]
 pub struct PassTargetBuffer {
     pub color: Vec<TextureRef>,
     #[serde(default)]
     pub depth: Option<String>,
+    /// Only render to and clear this part of the target textures.
+    #[serde(default)]
+    pub region: Option<TargetRegion>,
+    /// Color the target textures are cleared to before the pass.
+    #[serde(default = "default_target_clear_color")]
+    pub clear_color: [f32; 4],
+    /// Depth the depth texture is cleared to, the far plane if not set.
+    #[serde(default)]
+    pub clear_depth: Option<f32>,
+    /// Clear the target textures before the pass, if false the contents are kept between frames
+    /// for accumulating results.
+    ///
+    /// Resizing the window still reallocates window sized textures which clears them once. A
+    /// double buffered texture is written to its back buffer which holds the frame before the
+    /// previous one.
+    #[serde(default = "default_target_clear")]
+    pub clear: bool,
+}
+
+const fn default_target_clear_color() -> [f32; 4] {
+    [0.1, 0.3, 0.2, 1.0]
 }
 
-#[derive(Debug, Deserialize)]
+const fn default_target_clear() -> bool {
+    true
+}
+
+/// A rectangle in pixels with the origin in the bottom left corner of the texture.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub struct TargetRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
 #[serde(untagged)]
 pub enum PassTarget {
     Frame,
@@ -61,27 +175,125 @@ impl Default for PassTarget {
     }
 }
 
-#[derive(Debug, Deserialize)]
+const fn default_pass_iterations() -> u32 {
+    1
+}
+
+const fn default_srgb_write() -> bool {
+    true
+}
+
+/// The source of a shader, either a path to a file or the source code itself.
+///
+/// Inline shaders are part of the config so they are reloaded when the config file changes.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum ShaderSource {
+    Path(String),
+    Inline { source: String },
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
 pub struct Pass {
-    pub vertex_shader: String,
-    pub fragment_shader: String,
+    pub vertex_shader: ShaderSource,
+    pub fragment_shader: ShaderSource,
     #[serde(default)]
     pub objects: Vec<String>,
     #[serde(default)]
     pub textures: Vec<TextureRef>,
+    /// Storage buffers bound to the pass as the shader storage block `buffer_<name>`.
+    #[serde(default)]
+    pub buffers: Vec<String>,
     #[serde(default)]
     pub target: PassTarget,
     #[serde(default)]
     pub settings: Settings,
     #[serde(default)]
-    pub uniforms: HashMap<String, CustomUniform>,
+    pub uniforms: HashMap<String, UniformConfig>,
+    /// The amount of times the pass is rendered each frame.
+    ///
+    /// All iterations run within a single frame, the current iteration is available to the shader
+    /// as the `iteration` uniform. Textures which are both rendered to and sampled by the pass are
+    /// ping-ponged so that each iteration reads the result of the previous one.
+    #[serde(default = "default_pass_iterations")]
+    pub iterations: u32,
+    /// Name of the clock driving the `time` uniform of the pass.
+    #[serde(default)]
+    pub clock: Option<String>,
+    /// Draw the objects of the pass as a different primitive, the triangles of the geometry are
+    /// used if not set.
+    #[serde(default)]
+    pub draw_as: Option<DrawAs>,
+    /// Whether colors written to sRGB targets are converted from linear to sRGB.
+    ///
+    /// Disable this if the shader already outputs sRGB encoded colors.
+    #[serde(default = "default_srgb_write")]
+    pub srgb_write: bool,
 }
 
-#[derive(Deserialize, Debug, PartialEq)]
+/// The primitive the objects of a pass are drawn as.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawAs {
+    /// Every vertex is drawn as a single point.
+    #[serde(rename = "points")]
+    Points,
+    /// Every edge of the triangles is drawn once as a line.
+    #[serde(rename = "lines")]
+    Lines,
+    #[serde(rename = "triangles")]
+    Triangles,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub enum CameraKind {
-    Orbital { distance: f32, center: Vec3 },
-    //Flying { speed: f32 },
-    Lookat { from: Vec3, to: Vec3, up: Vec3 },
+    Orbital {
+        distance: f32,
+        center: Vec3,
+        /// The closest the camera can be zoomed in.
+        #[serde(default = "default_min_distance")]
+        min_distance: f32,
+        /// The furthest the camera can be zoomed out.
+        #[serde(default = "default_max_distance")]
+        max_distance: f32,
+        /// Rotate like a virtual trackball, around the screen space axis perpendicular to the
+        /// mouse movement, instead of turning the yaw and pitch.
+        #[serde(default)]
+        trackball: bool,
+    },
+    /// A camera moved with WASD, Q and E and rotated by dragging the mouse.
+    ///
+    /// The yaw and pitch are in degrees, the speed is in units per second.
+    Flying {
+        #[serde(default = "default_flying_position")]
+        position: Vec3,
+        #[serde(default)]
+        yaw: f32,
+        #[serde(default)]
+        pitch: f32,
+        #[serde(default = "default_flying_speed")]
+        speed: f32,
+    },
+    Lookat {
+        from: Vec3,
+        to: Vec3,
+        up: Vec3,
+    },
+}
+
+fn default_min_distance() -> f32 {
+    0.1
+}
+
+fn default_max_distance() -> f32 {
+    1000.0
+}
+
+fn default_flying_position() -> Vec3 {
+    Vec3::new(0.0, 0.0, -10.0)
+}
+
+fn default_flying_speed() -> f32 {
+    5.0
 }
 
 impl Default for CameraKind {
@@ -89,39 +301,257 @@ impl Default for CameraKind {
         CameraKind::Orbital {
             distance: 10.0,
             center: Vec3::ZERO,
+            min_distance: default_min_distance(),
+            max_distance: default_max_distance(),
+            trackball: false,
         }
     }
 }
 
-fn default_mouse_fov() -> f32 {
-    60.0
+fn default_mouse_fov() -> Scalar {
+    Scalar::Constant(60.0)
 }
 
 fn default_mouse_sensitifity() -> f32 {
     10.0
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Camera {
+    /// How fast the camera rotates when dragging the mouse, the default is 10.
     #[serde(default = "default_mouse_sensitifity")]
     pub mouse_sensitivity: f32,
+    /// Rotate the camera up when moving the mouse down.
+    #[serde(default)]
+    pub invert_y: bool,
     #[serde(default = "default_mouse_fov")]
-    pub fov: f32,
+    pub fov: Scalar,
     #[serde(default)]
     pub kind: CameraKind,
+    /// Distance to the near clipping plane.
+    #[serde(default = "default_near")]
+    pub near: f32,
+    /// Distance to the far clipping plane.
+    #[serde(default = "default_far")]
+    pub far: f32,
+    /// Spin the orbital camera around its center at the given amount of degrees per second.
+    #[serde(default)]
+    pub auto_rotate: Option<f32>,
+}
+
+fn default_near() -> f32 {
+    0.01
+}
+
+fn default_far() -> f32 {
+    100.0
 }
 
 impl Default for Camera {
     fn default() -> Self {
         Camera {
             mouse_sensitivity: 10.0,
-            fov: 60.0,
+            invert_y: false,
+            fov: default_mouse_fov(),
             kind: CameraKind::default(),
+            near: default_near(),
+            far: default_far(),
+            auto_rotate: None,
+        }
+    }
+}
+
+/// A config value which is either constant or animated over time.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+pub enum Scalar {
+    Constant(f32),
+    /// A sine wave, `offset + amplitude * sin(2π * (frequency * time + phase))`.
+    Sine {
+        #[serde(default)]
+        offset: f32,
+        amplitude: f32,
+        #[serde(default = "default_frequency")]
+        frequency: f32,
+        #[serde(default)]
+        phase: f32,
+    },
+}
+
+fn default_frequency() -> f32 {
+    1.0
+}
+
+impl Scalar {
+    /// Returns the value at the given time in seconds.
+    pub fn evaluate(&self, time: f32) -> f32 {
+        match *self {
+            Scalar::Constant(x) => x,
+            Scalar::Sine {
+                offset,
+                amplitude,
+                frequency,
+                phase,
+            } => offset + amplitude * (std::f32::consts::TAU * (frequency * time + phase)).sin(),
         }
     }
+
+    pub fn is_animated(&self) -> bool {
+        !matches!(self, Scalar::Constant(_))
+    }
 }
 
-#[derive(Deserialize, Debug)]
+fn default_clear_color() -> [Scalar; 4] {
+    [
+        Scalar::Constant(0.1),
+        Scalar::Constant(0.3),
+        Scalar::Constant(0.2),
+        Scalar::Constant(1.0),
+    ]
+}
+
+const fn default_sun_axis() -> Vec3 {
+    Vec3::X
+}
+
+fn default_sun_period() -> f32 {
+    60.0
+}
+
+/// A sun rotating around an axis, bound to shaders as the `sun_dir` uniform.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Sun {
+    /// The axis the sun rotates around.
+    #[serde(default = "default_sun_axis")]
+    pub axis: Vec3,
+    /// The length of a day in seconds.
+    #[serde(default = "default_sun_period")]
+    pub period: f32,
+    /// The time of day at the start as a fraction of the period, where zero is noon.
+    #[serde(default)]
+    pub phase: f32,
+}
+
+impl Default for Sun {
+    fn default() -> Self {
+        Sun {
+            axis: default_sun_axis(),
+            period: default_sun_period(),
+            phase: 0.0,
+        }
+    }
+}
+
+impl Sun {
+    /// Returns the direction towards the sun at the given time in seconds.
+    ///
+    /// At noon the sun is straight up.
+    pub fn direction(&self, time: f32) -> Vec3 {
+        let axis = self.axis.normalize_or_zero();
+        if axis == Vec3::ZERO || self.period == 0.0 {
+            return Vec3::Y;
+        }
+        let angle = std::f32::consts::TAU * (time / self.period + self.phase);
+        // Start from the up direction projected onto the plane the sun moves in.
+        let noon = (Vec3::Y - axis * axis.dot(Vec3::Y)).normalize_or_zero();
+        let noon = if noon == Vec3::ZERO {
+            axis.any_orthonormal_vector()
+        } else {
+            noon
+        };
+        Quat::from_axis_angle(axis, angle) * noon
+    }
+}
+
+/// Operator mapping HDR colors to the range of the display.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Tonemap {
+    #[default]
+    #[serde(rename = "none")]
+    None,
+    #[serde(rename = "reinhard")]
+    Reinhard,
+    #[serde(rename = "aces")]
+    Aces,
+    #[serde(rename = "filmic")]
+    Filmic,
+}
+
+impl Tonemap {
+    pub const ALL: [Tonemap; 4] = [
+        Tonemap::None,
+        Tonemap::Reinhard,
+        Tonemap::Aces,
+        Tonemap::Filmic,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match *self {
+            Tonemap::None => "None",
+            Tonemap::Reinhard => "Reinhard",
+            Tonemap::Aces => "ACES",
+            Tonemap::Filmic => "Filmic",
+        }
+    }
+}
+
+fn default_exposure() -> f32 {
+    1.0
+}
+
+fn default_grading_intensity() -> f32 {
+    1.0
+}
+
+/// Color grading of the final frame using a LUT texture.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Grading {
+    /// Name of the LUT texture.
+    pub lut: String,
+    #[serde(default = "default_grading_intensity")]
+    pub intensity: f32,
+}
+
+fn default_clock_scale() -> f32 {
+    1.0
+}
+
+/// A clock which passes can use for their `time` uniform instead of the default clock.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Clock {
+    /// How fast the clock runs compared to real time.
+    #[serde(default = "default_clock_scale")]
+    pub scale: f32,
+    /// Whether the clock starts out paused.
+    #[serde(default)]
+    pub paused: bool,
+}
+
+/// Bounds of the `scroll` uniform, which is unbounded by default.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct ScrollRange {
+    #[serde(default)]
+    pub min: Option<f32>,
+    #[serde(default)]
+    pub max: Option<f32>,
+}
+
+impl ScrollRange {
+    pub fn clamp(&self, value: f32) -> f32 {
+        let value = self.min.map_or(value, |x| value.max(x));
+        self.max.map_or(value, |x| value.min(x))
+    }
+}
+
+/// A buffer of floats which can be written to by shaders.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct StorageBuffer {
+    pub name: String,
+    /// Amount of floats in the buffer.
+    pub size: usize,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
 pub struct Config {
     #[serde(default)]
     pub objects: Vec<Object>,
@@ -131,6 +561,106 @@ pub struct Config {
     pub camera: Camera,
     #[serde(default)]
     pub textures: Vec<Texture>,
+    /// Named sampler settings which textures can refer to.
+    #[serde(default)]
+    pub samplers: HashMap<String, SamplerPreset>,
+    #[serde(default)]
+    pub buffers: Vec<StorageBuffer>,
+    /// Named clocks passes can use instead of the default clock.
+    #[serde(default)]
+    pub clocks: HashMap<String, Clock>,
+    /// Render object ids to allow selecting objects by clicking on them.
+    #[serde(default)]
+    pub picking: bool,
+    /// The color the window is cleared with before rendering.
+    #[serde(default = "default_clear_color")]
+    pub clear_color: [Scalar; 4],
+    #[serde(default)]
+    pub grading: Option<Grading>,
+    /// Render the passes to an HDR buffer and map it to the display with the given operator.
+    #[serde(default)]
+    pub tonemap: Tonemap,
+    /// Multiplier applied to the colors before tone mapping.
+    #[serde(default = "default_exposure")]
+    pub exposure: f32,
+    /// Smooth jagged edges of the final frame with FXAA.
+    #[serde(default)]
+    pub fxaa: bool,
+    #[serde(default)]
+    pub sun: Sun,
+    /// Range the scroll wheel accumulator of the `scroll` uniform is kept within.
+    #[serde(default)]
+    pub scroll: ScrollRange,
+    /// Map the near plane to the largest depth and the far plane to the smallest.
+    ///
+    /// Depth buffers are cleared to 0 and the depth comparisons of passes are mirrored, so `less`
+    /// still passes fragments closer to the camera. This only mirrors the depth values and does
+    /// not improve depth precision: without clip control, which glium does not expose, OpenGL
+    /// still maps the depth into the upper half of the depth range.
+    #[serde(default)]
+    pub reverse_z: bool,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).context("could not find config file")?;
+        let config: Self = match path.extension().and_then(OsStr::to_str) {
+            Some("ron") => ron::de::from_reader(file).context("Failed to parse config file")?,
+            Some("json") => serde_json::from_reader(file).context("Failed to parse config file")?,
+            _ => bail!("Invalid config extension!"),
+        };
+        ensure!(
+            config.camera.near > 0.0,
+            "Camera near plane must be larger than 0, found {}",
+            config.camera.near
+        );
+        ensure!(
+            config.camera.far > config.camera.near,
+            "Camera far plane must be further away than the near plane, found near {} and far {}",
+            config.camera.near,
+            config.camera.far
+        );
+        if let CameraKind::Orbital {
+            min_distance,
+            max_distance,
+            ..
+        } = config.camera.kind
+        {
+            ensure!(
+                min_distance <= max_distance,
+                "Camera min_distance must not be larger than max_distance, found {} and {}",
+                min_distance,
+                max_distance
+            );
+        }
+        if let (Some(min), Some(max)) = (config.scroll.min, config.scroll.max) {
+            ensure!(
+                min <= max,
+                "Scroll min must not be larger than max, found {} and {}",
+                min,
+                max
+            );
+        }
+        Ok(config)
+    }
+
+    /// Returns whether any value in the config changes over time.
+    pub fn is_animated(&self) -> bool {
+        self.camera.fov.is_animated()
+            || self.clear_color.iter().any(Scalar::is_animated)
+            || (self.camera.auto_rotate.is_some()
+                && matches!(self.camera.kind, CameraKind::Orbital { .. }))
+    }
+
+    /// Returns whether the configs only differ in their camera.
+    ///
+    /// The camera of `other` is temporarily replaced so every other field is compared.
+    pub fn eq_except_camera(&self, other: &mut Self) -> bool {
+        let camera = std::mem::replace(&mut other.camera, self.camera.clone());
+        let res = *self == *other;
+        other.camera = camera;
+        res
+    }
 }
 
 #[derive(Deserialize)]
@@ -140,3 +670,57 @@ pub enum Color {
     RGBA { r: f32, g: f32, b: f32, a: f32 },
     HSV { h: f32, s: f32, v: f32 },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, ShaderSource};
+
+    const FRAGMENT: &str = "void main(){ color = vec4(1.0); }";
+
+    fn check_shaders(config: &Config) {
+        assert_eq!(config.passes.len(), 1);
+        let pass = &config.passes[0];
+        assert_eq!(
+            pass.vertex_shader,
+            ShaderSource::Path("shader.vert".to_string())
+        );
+        assert_eq!(
+            pass.fragment_shader,
+            ShaderSource::Inline {
+                source: FRAGMENT.to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn inline_shader_ron() {
+        let source = format!(
+            r#"(
+    passes: [
+        (
+            vertex_shader: "shader.vert",
+            fragment_shader: (source: "{}"),
+        ),
+    ],
+)"#,
+            FRAGMENT
+        );
+        check_shaders(&ron::from_str(&source).unwrap());
+    }
+
+    #[test]
+    fn inline_shader_json() {
+        let source = format!(
+            r#"{{
+    "passes": [
+        {{
+            "vertex_shader": "shader.vert",
+            "fragment_shader": {{ "source": "{}" }}
+        }}
+    ]
+}}"#,
+            FRAGMENT
+        );
+        check_shaders(&serde_json::from_str(&source).unwrap());
+    }
+}
@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 use glium::draw_parameters::{
-    self as dp, BackfaceCullingMode, DepthClamp, DepthTest, DrawParameters,
+    self as dp, BackfaceCullingMode, DepthClamp, DepthTest, DrawParameters, PolygonMode,
 };
 
 #[derive(Serialize, Deserialize)]
@@ -48,7 +48,7 @@ fn test() -> DepthTest {
     DepthTest::Ignore
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
 pub struct Depth {
     #[serde(default = "test")]
     #[serde(with = "DepthTestDef")]
@@ -85,13 +85,32 @@ pub enum BackfaceCullingModeDef {
     CullClockwise,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+fn polygon_mode() -> PolygonMode {
+    PolygonMode::Fill
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(remote = "PolygonMode")]
+pub enum PolygonModeDef {
+    #[serde(rename = "point")]
+    Point,
+    #[serde(rename = "line")]
+    Line,
+    #[serde(rename = "fill")]
+    Fill,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
 pub struct Settings {
     #[serde(default)]
     depth: Depth,
     #[serde(with = "BackfaceCullingModeDef")]
     #[serde(default = "cull")]
     cull: BackfaceCullingMode,
+    /// Whether triangles are filled or only their edges or vertices are drawn.
+    #[serde(with = "PolygonModeDef")]
+    #[serde(default = "polygon_mode")]
+    polygon_mode: PolygonMode,
 }
 
 impl Default for Settings {
@@ -99,20 +118,39 @@ impl Default for Settings {
         Settings {
             depth: Depth::default(),
             cull: cull(),
+            polygon_mode: polygon_mode(),
         }
     }
 }
 
+/// Mirror a depth comparison for a depth buffer where larger values are closer.
+fn reverse_depth_test(test: DepthTest) -> DepthTest {
+    match test {
+        DepthTest::IfLess => DepthTest::IfMore,
+        DepthTest::IfLessOrEqual => DepthTest::IfMoreOrEqual,
+        DepthTest::IfMore => DepthTest::IfLess,
+        DepthTest::IfMoreOrEqual => DepthTest::IfLessOrEqual,
+        x => x,
+    }
+}
+
 impl Settings {
-    pub fn to_params(&self) -> DrawParameters<'static> {
+    /// Create the draw parameters for a pass, if `reverse_z` is set depth comparisons are
+    /// mirrored so that `less` keeps meaning closer to the camera.
+    pub fn to_params(&self, reverse_z: bool) -> DrawParameters<'static> {
         DrawParameters {
             depth: dp::Depth {
-                test: self.depth.compare,
+                test: if reverse_z {
+                    reverse_depth_test(self.depth.compare)
+                } else {
+                    self.depth.compare
+                },
                 write: self.depth.write,
                 clamp: self.depth.clamp,
                 ..Default::default()
             },
             backface_culling: self.cull,
+            polygon_mode: self.polygon_mode,
             ..DrawParameters::default()
         }
     }
@@ -1,10 +1,15 @@
+use anyhow::{Context, Result};
 use glium::{
-    texture::{DepthFormat, MipmapsOption, UncompressedFloatFormat},
-    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction},
+    texture::{DepthFormat, MipmapsOption, UncompressedFloatFormat, UncompressedUintFormat},
+    uniforms::{
+        DepthTextureComparison, MagnifySamplerFilter, MinifySamplerFilter, Sampler,
+        SamplerWrapFunction,
+    },
 };
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
+use std::{collections::HashMap, fmt::Write};
 
-#[derive(Deserialize, Debug, Clone, Copy)]
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum TextureSize {
     ViewPort,
@@ -21,19 +26,35 @@ fn text_format() -> UncompressedFloatFormat {
     UncompressedFloatFormat::F32F32F32F32
 }
 
-#[derive(Deserialize, Debug, Clone, Copy)]
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct EmptyTexture {
     pub size: TextureSize,
     #[serde(with = "UncompressedFloatFormatDef")]
     #[serde(default = "text_format")]
     pub format: UncompressedFloatFormat,
+    /// Allocate a second texture so passes can sample the previous frame while rendering to it.
+    #[serde(default)]
+    pub double_buffer: bool,
+}
+
+fn uint_format() -> UncompressedUintFormat {
+    UncompressedUintFormat::U32
+}
+
+/// A texture storing unsigned integers, sampled as a `usampler2D`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UnsignedTexture {
+    pub size: TextureSize,
+    #[serde(with = "UncompressedUintFormatDef")]
+    #[serde(default = "uint_format")]
+    pub format: UncompressedUintFormat,
 }
 
 fn depth_format() -> DepthFormat {
     DepthFormat::F32
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct DepthTexture {
     pub size: TextureSize,
     #[serde(with = "DepthFormatDef")]
@@ -41,11 +62,79 @@ pub struct DepthTexture {
     pub format: DepthFormat,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// A 3D texture loaded from a numbered sequence of images, one per slice.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VolumeTexture {
+    /// Path of the slices where a run of `#` is replaced by the zero padded slice number, for
+    /// example `slices/density_###.png`.
+    pub path: String,
+    /// The amount of slices.
+    pub depth: u32,
+    /// Number of the first slice.
+    #[serde(default)]
+    pub start: u32,
+}
+
+/// Returns `count` paths starting at number `start`, where the first run of `#` in the pattern is
+/// replaced by the zero padded number.
+fn numbered_paths(pattern: &str, start: u32, count: u32) -> Result<Vec<String>> {
+    let idx = pattern
+        .find('#')
+        .with_context(|| format!("Path `{}` does not contain a `#`", pattern))?;
+    let width = pattern[idx..]
+        .find(|x| x != '#')
+        .unwrap_or(pattern.len() - idx);
+    let (prefix, suffix) = (&pattern[..idx], &pattern[idx + width..]);
+    Ok((start..start + count)
+        .map(|x| format!("{}{:0width$}{}", prefix, x, suffix, width = width))
+        .collect())
+}
+
+impl VolumeTexture {
+    /// Returns the paths of all slices.
+    pub fn slice_paths(&self) -> Result<Vec<String>> {
+        numbered_paths(&self.path, self.start, self.depth)
+    }
+}
+
+/// An animated texture cycling through a numbered sequence of images.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SequenceTexture {
+    /// Path of the frames where a run of `#` is replaced by the zero padded frame number, for
+    /// example `frames/####.png`.
+    pub path: String,
+    /// The amount of frames.
+    pub count: u32,
+    /// Frames shown per second.
+    pub fps: u32,
+    /// Number of the first frame.
+    #[serde(default)]
+    pub start: u32,
+}
+
+impl SequenceTexture {
+    /// Returns the paths of all frames.
+    pub fn frame_paths(&self) -> Result<Vec<String>> {
+        numbered_paths(&self.path, self.start, self.count)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub enum TextureKind {
     File(String),
     Empty(EmptyTexture),
+    Unsigned(UnsignedTexture),
     Depth(DepthTexture),
+    /// A 3D color lookup table loaded from an Adobe `.cube` file, sampled as a `sampler3D`.
+    Lut(String),
+    /// Image slices stacked into a 3D texture, sampled as a `sampler3D`.
+    Volume(VolumeTexture),
+    /// Image files shown one after the other, sampled as a `sampler2D`.
+    Sequence(SequenceTexture),
+}
+
+fn srgb() -> bool {
+    true
 }
 
 fn wrap() -> SamplerWrapFunction {
@@ -60,32 +149,86 @@ fn magnify_filter() -> MagnifySamplerFilter {
     MagnifySamplerFilter::Linear
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// Sampler settings which can be shared between textures.
+///
+/// Settings which are not given fall back to the defaults.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SamplerPreset {
+    #[serde(default, deserialize_with = "de_wrap")]
+    pub wrap: Option<SamplerWrapFunction>,
+    #[serde(default, deserialize_with = "de_minify_filter")]
+    pub minify_filter: Option<MinifySamplerFilter>,
+    #[serde(default, deserialize_with = "de_magnify_filter")]
+    pub magnify_filter: Option<MagnifySamplerFilter>,
+    #[serde(default)]
+    pub anisotropy: Option<u16>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Texture {
     pub name: String,
     pub kind: TextureKind,
-    #[serde(with = "SamplerWrapFunctionDef")]
-    #[serde(default = "wrap")]
-    pub wrap: SamplerWrapFunction,
-    #[serde(with = "MinifySamplerFilterDef")]
-    #[serde(default = "minify_filter")]
-    pub minify_filter: MinifySamplerFilter,
-    #[serde(with = "MagnifySamplerFilterDef")]
-    #[serde(default = "magnify_filter")]
-    pub magnify_filter: MagnifySamplerFilter,
+    /// Name of a sampler preset from the config, settings given on the texture take precedence.
+    #[serde(default)]
+    pub sampler: Option<String>,
+    #[serde(default, deserialize_with = "de_wrap")]
+    pub wrap: Option<SamplerWrapFunction>,
+    #[serde(default, deserialize_with = "de_minify_filter")]
+    pub minify_filter: Option<MinifySamplerFilter>,
+    #[serde(default, deserialize_with = "de_magnify_filter")]
+    pub magnify_filter: Option<MagnifySamplerFilter>,
     #[serde(default)]
     pub anisotropy: Option<u16>,
+    /// Comparison done when sampling a depth texture with a `sampler2DShadow`, only valid for
+    /// depth textures.
+    #[serde(default, deserialize_with = "de_compare")]
+    pub compare: Option<DepthTextureComparison>,
     #[serde(default)]
     pub mipmaps: Mipmaps,
+    /// Whether an image file holds sRGB colors which are converted to linear when sampled, should
+    /// be disabled for data like normal maps.
+    #[serde(default = "srgb")]
+    pub srgb: bool,
 }
 
 impl Texture {
+    /// Fill in the sampler settings not given on the texture from its sampler preset.
+    pub fn resolve_sampler(&self, presets: &HashMap<String, SamplerPreset>) -> Result<Self> {
+        let preset = match self.sampler {
+            Some(ref name) => match presets.get(name) {
+                Some(x) => *x,
+                None => {
+                    let mut expects = String::new();
+                    write!(expects, "Expected one of ").unwrap();
+                    for (idx, k) in presets.keys().enumerate() {
+                        if idx != 0 {
+                            write!(expects, ",").unwrap();
+                        }
+                        write!(expects, "`{}`", k).unwrap();
+                    }
+                    write!(expects, ".").unwrap();
+
+                    bail!("Could not find sampler preset `{}`. {}", name, expects)
+                }
+            },
+            None => SamplerPreset::default(),
+        };
+        Ok(Texture {
+            wrap: self.wrap.or(preset.wrap),
+            minify_filter: self.minify_filter.or(preset.minify_filter),
+            magnify_filter: self.magnify_filter.or(preset.magnify_filter),
+            anisotropy: self.anisotropy.or(preset.anisotropy),
+            ..self.clone()
+        })
+    }
+
     pub fn apply_to_sampler<'t, T>(&self, sampler: Sampler<'t, T>) -> Sampler<'t, T> {
         let res = sampler
-            .wrap_function(self.wrap)
-            .minify_filter(self.minify_filter)
-            .magnify_filter(self.magnify_filter);
+            .wrap_function(self.wrap.unwrap_or_else(wrap))
+            .minify_filter(self.minify_filter.unwrap_or_else(minify_filter))
+            .magnify_filter(self.magnify_filter.unwrap_or_else(magnify_filter));
 
+        let res = res.depth_texture_comparison(self.compare);
         if let Some(x) = self.anisotropy {
             res.anisotropy(x)
         } else {
@@ -94,6 +237,53 @@ impl Texture {
     }
 }
 
+// The sampler settings are deserialized as plain values rather than options so RON configs do not
+// need to wrap them in `Some`.
+fn de_wrap<'de, D: Deserializer<'de>>(d: D) -> Result<Option<SamplerWrapFunction>, D::Error> {
+    #[derive(Deserialize)]
+    struct Helper(#[serde(with = "SamplerWrapFunctionDef")] SamplerWrapFunction);
+    Ok(Some(Helper::deserialize(d)?.0))
+}
+
+fn de_compare<'de, D: Deserializer<'de>>(d: D) -> Result<Option<DepthTextureComparison>, D::Error> {
+    #[derive(Deserialize)]
+    struct Helper(#[serde(with = "DepthTextureComparisonDef")] DepthTextureComparison);
+    Ok(Some(Helper::deserialize(d)?.0))
+}
+
+fn de_minify_filter<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Option<MinifySamplerFilter>, D::Error> {
+    #[derive(Deserialize)]
+    struct Helper(#[serde(with = "MinifySamplerFilterDef")] MinifySamplerFilter);
+    Ok(Some(Helper::deserialize(d)?.0))
+}
+
+fn de_magnify_filter<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Option<MagnifySamplerFilter>, D::Error> {
+    #[derive(Deserialize)]
+    struct Helper(#[serde(with = "MagnifySamplerFilterDef")] MagnifySamplerFilter);
+    Ok(Some(Helper::deserialize(d)?.0))
+}
+
+/// Named like the depth tests of pass settings.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(remote = "DepthTextureComparison")]
+pub enum DepthTextureComparisonDef {
+    #[serde(rename = "less_equal")]
+    LessOrEqual,
+    #[serde(rename = "greater_equal")]
+    GreaterOrEqual,
+    Less,
+    Greater,
+    Equal,
+    NotEqual,
+    Always,
+    Never,
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[serde(remote = "SamplerWrapFunction")]
@@ -125,7 +315,7 @@ pub enum MagnifySamplerFilterDef {
     Linear,
 }
 
-#[derive(Deserialize, Debug, Clone, Copy)]
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum Mipmaps {
     None,
@@ -284,6 +474,25 @@ pub enum UncompressedFloatFormatDef {
     F9F9F9,
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(remote = "UncompressedUintFormat")]
+pub enum UncompressedUintFormatDef {
+    U8,
+    U16,
+    U32,
+    U8U8,
+    U16U16,
+    U32U32,
+    U8U8U8,
+    U16U16U16,
+    U32U32U32,
+    U8U8U8U8,
+    U16U16U16U16,
+    U32U32U32U32,
+    U10U10U10U2,
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[serde(remote = "DepthFormat")]
@@ -1,69 +1,435 @@
-use std::path::Path;
+use std::{
+    borrow::Cow,
+    cell::Cell,
+    collections::HashMap,
+    ffi::OsStr,
+    fs::{self, File},
+    io::BufReader,
+    path::Path,
+    rc::Rc,
+    time::SystemTime,
+};
 
-use super::ser::{self, TextureSize};
+use super::{
+    lut::Lut,
+    ser::{self, TextureSize},
+};
 use anyhow::{Context, Result};
 use glium::{
-    texture::{DepthFormat, DepthTexture2d, RawImage2d, Texture2d, UncompressedFloatFormat},
+    texture::{
+        ClientFormat, DepthFormat, DepthTexture2d, MipmapsOption, RawImage2d, RawImage3d,
+        SrgbTexture2d, Texture2d, Texture3d, UncompressedFloatFormat, UncompressedUintFormat,
+        UnsignedTexture2d,
+    },
+    uniforms::UniformType,
     Display,
 };
-use image::RgbaImage;
+use image::{codecs::hdr::HdrDecoder, RgbaImage};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum LoadedTextureKind {
     File {
-        texture: Texture2d,
+        texture: Rc<Texture2d>,
+    },
+    /// An image file with colors in sRGB.
+    SrgbFile {
+        texture: Rc<SrgbTexture2d>,
     },
     Empty {
         size: TextureSize,
         format: UncompressedFloatFormat,
-        texture: Texture2d,
+        texture: Rc<Texture2d>,
+        /// The second texture of a double buffered texture.
+        back: Option<Rc<Texture2d>>,
+        /// Whether `back` holds the previous frame instead of `texture`.
+        swapped: Cell<bool>,
+    },
+    Unsigned {
+        size: TextureSize,
+        format: UncompressedUintFormat,
+        texture: Rc<UnsignedTexture2d>,
     },
     Depth {
         size: TextureSize,
         format: DepthFormat,
-        texture: DepthTexture2d,
+        texture: Rc<DepthTexture2d>,
+    },
+    Lut {
+        domain_min: [f32; 3],
+        domain_max: [f32; 3],
+        texture: Rc<Texture3d>,
+    },
+    Volume {
+        texture: Rc<Texture3d>,
+    },
+    Sequence {
+        /// Every frame is either a `File` or an `SrgbFile`.
+        frames: Vec<LoadedTextureKind>,
+        fps: u32,
     },
 }
 
 #[derive(Debug)]
-pub struct FileTexture {
-    image: RgbaImage,
+pub enum FileTexture {
+    Ldr(RgbaImage),
+    /// Radiance HDR images keep values above 1.0 and are loaded into a float texture.
+    Hdr {
+        width: u32,
+        height: u32,
+        /// Rgba values with the alpha set to 1.0.
+        data: Vec<f32>,
+    },
 }
 
 impl FileTexture {
     fn load(path: impl AsRef<Path>) -> Result<Self> {
-        Ok(FileTexture {
-            image: image::open(path)?.to_rgba8(),
-        })
+        let path = path.as_ref();
+        match path.extension().and_then(OsStr::to_str) {
+            Some("hdr") => {
+                let file = BufReader::new(File::open(path)?);
+                let decoder = HdrDecoder::new(file)?;
+                let meta = decoder.metadata();
+                let data = decoder
+                    .read_image_hdr()?
+                    .into_iter()
+                    .flat_map(|x| [x[0], x[1], x[2], 1.0])
+                    .collect();
+                Ok(FileTexture::Hdr {
+                    width: meta.width,
+                    height: meta.height,
+                    data,
+                })
+            }
+            // image 0.23 has no OpenEXR decoder, the `exr` crate is not a dependency.
+            Some("exr") => bail!("OpenEXR images are not supported, use a `.hdr` image instead"),
+            _ => Ok(FileTexture::Ldr(image::open(path)?.to_rgba8())),
+        }
+    }
+
+    /// Upload the image as sRGB, HDR images are always linear.
+    fn into_srgb_texture(
+        self,
+        display: &Display,
+        mipmaps: MipmapsOption,
+    ) -> Result<LoadedTextureKind> {
+        match self {
+            FileTexture::Ldr(image) => {
+                let dimensions = image.dimensions();
+                let raw_image = RawImage2d::from_raw_rgba(image.into_vec(), dimensions);
+                let texture = SrgbTexture2d::with_mipmaps(display, raw_image, mipmaps)
+                    .context("failed to load texture")?;
+                Ok(LoadedTextureKind::SrgbFile {
+                    texture: Rc::new(texture),
+                })
+            }
+            x => Ok(LoadedTextureKind::File {
+                texture: Rc::new(x.into_texture(display, mipmaps)?),
+            }),
+        }
+    }
+
+    fn into_texture(self, display: &Display, mipmaps: MipmapsOption) -> Result<Texture2d> {
+        let texture = match self {
+            FileTexture::Ldr(image) => {
+                let dimensions = image.dimensions();
+                let raw_image = RawImage2d::from_raw_rgba(image.into_vec(), dimensions);
+                Texture2d::with_mipmaps(display, raw_image, mipmaps)
+            }
+            FileTexture::Hdr {
+                width,
+                height,
+                data,
+            } => {
+                let raw_image = RawImage2d::from_raw_rgba(data, (width, height));
+                Texture2d::with_format(
+                    display,
+                    raw_image,
+                    UncompressedFloatFormat::F16F16F16F16,
+                    mipmaps,
+                )
+            }
+        };
+        texture.context("failed to load texture")
     }
 }
 
-#[derive(Debug)]
+/// Load the slices of a volume texture, every slice must have the same size.
+fn load_volume(
+    config: &ser::VolumeTexture,
+    display: &Display,
+    mipmaps: MipmapsOption,
+) -> Result<Texture3d> {
+    ensure!(
+        config.depth > 0,
+        "Volume texture must have at least one slice"
+    );
+    let mut dimensions = None;
+    let mut data = Vec::new();
+    for path in config.slice_paths()? {
+        let image = image::open(&path)
+            .with_context(|| format!("failed to load volume slice `{}`", path))?
+            .to_rgba8();
+        match dimensions {
+            None => dimensions = Some(image.dimensions()),
+            Some(x) => ensure!(
+                x == image.dimensions(),
+                "Volume slice `{}` is {}x{} but the first slice is {}x{}",
+                path,
+                image.width(),
+                image.height(),
+                x.0,
+                x.1
+            ),
+        }
+        data.extend_from_slice(&image.into_raw());
+    }
+    let (width, height) = dimensions.unwrap();
+    let image = RawImage3d {
+        data: Cow::Owned(data),
+        width,
+        height,
+        depth: config.depth,
+        format: ClientFormat::U8U8U8U8,
+    };
+    Texture3d::with_mipmaps(display, image, mipmaps).context("failed to create volume texture")
+}
+
+#[derive(Debug, Clone)]
 pub struct LoadedTexture {
     pub kind: LoadedTextureKind,
     pub config: ser::Texture,
+    /// Modification time of the image file the texture was loaded from.
+    modified: Option<SystemTime>,
+}
+
+/// A pool of textures from a previously loaded config which can be reused by a new config.
+///
+/// Textures are shared between configs so reusing a texture does not allocate any GPU memory.
+///
+/// Entries are keyed by the config they were loaded from together with the modification time of
+/// their files, as a new config has no other way to refer to the textures of the previous one.
+pub struct TexturePool<T = LoadedTexture>(HashMap<ser::Texture, (Option<SystemTime>, T)>);
+
+impl<T> Default for TexturePool<T> {
+    fn default() -> Self {
+        TexturePool(HashMap::new())
+    }
+}
+
+impl<T: Clone> TexturePool<T> {
+    /// Returns the entry loaded from the same config if its files were not modified since.
+    fn get(&self, config: &ser::Texture, modified: Option<SystemTime>) -> Option<T> {
+        self.0
+            .get(config)
+            .filter(|x| x.0 == modified)
+            .map(|x| x.1.clone())
+    }
+}
+
+impl TexturePool {
+    pub fn new(textures: &[LoadedTexture]) -> Self {
+        TexturePool(
+            textures
+                .iter()
+                .map(|x| (x.config.clone(), (x.modified, x.clone())))
+                .collect(),
+        )
+    }
+
+    /// Returns a texture from the pool if the config is unchanged otherwise loads a new texture.
+    pub fn load(&self, config: &ser::Texture, display: &Display) -> Result<LoadedTexture> {
+        match self.get(config, LoadedTexture::modified(config)) {
+            Some(x) => Ok(x),
+            None => LoadedTexture::load(config, display),
+        }
+    }
+}
+
+impl LoadedTextureKind {
+    /// Returns the color texture sampled by passes, for a double buffered texture the one holding
+    /// the previous frame.
+    pub fn read_texture(&self) -> Option<&Texture2d> {
+        match *self {
+            LoadedTextureKind::File { ref texture } => Some(texture),
+            LoadedTextureKind::Empty {
+                ref texture,
+                back: Some(ref back),
+                ref swapped,
+                ..
+            } if swapped.get() => Some(back),
+            LoadedTextureKind::Empty { ref texture, .. } => Some(texture),
+            _ => None,
+        }
+    }
+
+    /// Returns the frame of a sequence texture shown at the given time in seconds, other
+    /// textures are returned as is.
+    pub fn frame(&self, time: f32) -> &LoadedTextureKind {
+        match *self {
+            LoadedTextureKind::Sequence { ref frames, fps } => {
+                let idx = (time.max(0.0) * fps as f32) as usize % frames.len();
+                &frames[idx]
+            }
+            ref x => x,
+        }
+    }
+
+    /// Returns the color texture rendered to, for a double buffered texture the one not sampled.
+    pub fn write_texture(&self) -> Option<&Texture2d> {
+        match *self {
+            LoadedTextureKind::Empty {
+                ref texture,
+                back: Some(ref back),
+                ref swapped,
+                ..
+            } if !swapped.get() => Some(back),
+            _ => self.read_texture(),
+        }
+    }
 }
 
 impl LoadedTexture {
+    fn latest_modified(paths: &[String]) -> Option<SystemTime> {
+        paths
+            .iter()
+            .filter_map(|x| fs::metadata(x).and_then(|x| x.modified()).ok())
+            .max()
+    }
+
+    fn modified(config: &ser::Texture) -> Option<SystemTime> {
+        match config.kind {
+            ser::TextureKind::File(ref x) | ser::TextureKind::Lut(ref x) => {
+                fs::metadata(x).and_then(|x| x.modified()).ok()
+            }
+            // The latest change to any of the slices or frames.
+            ser::TextureKind::Volume(ref x) => Self::latest_modified(&x.slice_paths().ok()?),
+            ser::TextureKind::Sequence(ref x) => Self::latest_modified(&x.frame_paths().ok()?),
+            _ => None,
+        }
+    }
+
+    pub fn is_double_buffered(&self) -> bool {
+        matches!(self.kind, LoadedTextureKind::Empty { back: Some(_), .. })
+    }
+
+    /// Regenerate the mipmaps of a rendered texture from its first level if the texture asks for
+    /// generated mipmaps. Textures loaded from files already have their mipmaps.
+    pub fn generate_mipmaps(&self) {
+        if !matches!(
+            self.config.mipmaps,
+            ser::Mipmaps::Generate | ser::Mipmaps::GenerateAmount(_)
+        ) {
+            return;
+        }
+        if let LoadedTextureKind::Empty { .. } = self.kind {
+            let texture = self.kind.write_texture().unwrap();
+            // Safe as the texture was created with the mip levels to generate.
+            unsafe { texture.generate_mipmaps() }
+        }
+    }
+
+    /// Swap the sampled and rendered texture of a double buffered texture.
+    pub fn swap(&self) {
+        if let LoadedTextureKind::Empty { ref swapped, .. } = self.kind {
+            swapped.set(!swapped.get());
+        }
+    }
+
+    /// Returns whether a uniform of the given type can be bound to one of the kinds of texture.
+    pub fn is_sampler(ty: UniformType) -> bool {
+        matches!(
+            ty,
+            UniformType::Sampler2d
+                | UniformType::USampler2d
+                | UniformType::Sampler2dShadow
+                | UniformType::Sampler3d
+        )
+    }
+
+    /// Returns whether the texture can be bound to a sampler uniform of the given type.
+    pub fn matches_sampler(&self, ty: UniformType) -> bool {
+        match self.kind {
+            LoadedTextureKind::File { .. }
+            | LoadedTextureKind::SrgbFile { .. }
+            | LoadedTextureKind::Empty { .. } => ty == UniformType::Sampler2d,
+            LoadedTextureKind::Unsigned { .. } => ty == UniformType::USampler2d,
+            LoadedTextureKind::Depth { .. } => {
+                matches!(ty, UniformType::Sampler2d | UniformType::Sampler2dShadow)
+            }
+            LoadedTextureKind::Lut { .. } | LoadedTextureKind::Volume { .. } => {
+                ty == UniformType::Sampler3d
+            }
+            LoadedTextureKind::Sequence { .. } => ty == UniformType::Sampler2d,
+        }
+    }
+
+    /// Read back the contents of a color texture, rows start at the bottom of the image.
+    ///
+    /// Returns `None` for textures which don't hold colors.
+    ///
+    /// Sequence textures return their first frame.
+    pub fn read_rgba(&self) -> Option<RgbaImage> {
+        let kind = self.kind.frame(0.0);
+        let image: RawImage2d<u8> = match kind {
+            LoadedTextureKind::File { .. } | LoadedTextureKind::Empty { .. } => {
+                kind.read_texture()?.read()
+            }
+            LoadedTextureKind::SrgbFile { ref texture } => texture.read(),
+            _ => return None,
+        };
+        RgbaImage::from_raw(image.width, image.height, image.data.into_owned())
+    }
+
     /// Load a texture from a config.
     pub fn load(config: &ser::Texture, display: &Display) -> Result<Self> {
+        ensure!(
+            config.compare.is_none() || matches!(config.kind, ser::TextureKind::Depth(_)),
+            "Texture `{}` sets `compare` but only depth textures can be compared",
+            config.name
+        );
+        let modified = Self::modified(config);
         let kind = match config.kind {
             ser::TextureKind::File(ref x) => {
                 let loaded = FileTexture::load(x).with_context(|| {
                     format!("failed to load image file for texture at path: {}", x)
                 })?;
-                let dimensions = loaded.image.dimensions();
-                let raw_image = RawImage2d::from_raw_rgba(loaded.image.into_vec(), dimensions);
-                let texture = Texture2d::with_mipmaps(display, raw_image, config.mipmaps.into())
-                    .context("failed to load texture")?;
-                LoadedTextureKind::File { texture }
+                if config.srgb {
+                    loaded.into_srgb_texture(display, config.mipmaps.into())?
+                } else {
+                    LoadedTextureKind::File {
+                        texture: Rc::new(loaded.into_texture(display, config.mipmaps.into())?),
+                    }
+                }
             }
             ser::TextureKind::Empty(ref x) => {
                 let size = match x.size {
                     TextureSize::ViewPort => display.get_framebuffer_dimensions(),
                     TextureSize::Size { width, height } => (width, height),
                 };
-                let texture = Texture2d::empty_with_format(
+                let create = || {
+                    Texture2d::empty_with_format(
+                        display,
+                        x.format,
+                        config.mipmaps.into(),
+                        size.0,
+                        size.1,
+                    )
+                    .context("failed to create texture")
+                };
+                LoadedTextureKind::Empty {
+                    size: x.size,
+                    format: x.format,
+                    texture: Rc::new(create()?),
+                    back: x.double_buffer.then(create).transpose()?.map(Rc::new),
+                    swapped: Cell::new(false),
+                }
+            }
+            ser::TextureKind::Unsigned(ref x) => {
+                let size = match x.size {
+                    TextureSize::ViewPort => display.get_framebuffer_dimensions(),
+                    TextureSize::Size { width, height } => (width, height),
+                };
+                let texture = UnsignedTexture2d::empty_with_format(
                     display,
                     x.format,
                     config.mipmaps.into(),
@@ -71,10 +437,10 @@ impl LoadedTexture {
                     size.1,
                 )
                 .context("failed to create texture")?;
-                LoadedTextureKind::Empty {
+                LoadedTextureKind::Unsigned {
                     size: x.size,
                     format: x.format,
-                    texture,
+                    texture: Rc::new(texture),
                 }
             }
             ser::TextureKind::Depth(ref x) => {
@@ -93,35 +459,142 @@ impl LoadedTexture {
                 LoadedTextureKind::Depth {
                     size: x.size,
                     format: x.format,
-                    texture,
+                    texture: Rc::new(texture),
                 }
             }
+            ser::TextureKind::Lut(ref x) => {
+                let lut = Lut::load(x)?;
+                LoadedTextureKind::Lut {
+                    domain_min: lut.domain_min,
+                    domain_max: lut.domain_max,
+                    texture: Rc::new(lut.to_texture(display)?),
+                }
+            }
+            ser::TextureKind::Volume(ref x) => LoadedTextureKind::Volume {
+                texture: Rc::new(load_volume(x, display, config.mipmaps.into())?),
+            },
+            ser::TextureKind::Sequence(ref x) => {
+                ensure!(x.count > 0, "Sequence texture must have at least one frame");
+                ensure!(x.fps > 0, "Sequence texture must have an fps larger than 0");
+                let mut dimensions = None;
+                let mut frames = Vec::new();
+                for path in x.frame_paths()? {
+                    let loaded = FileTexture::load(&path)
+                        .with_context(|| format!("failed to load sequence frame `{}`", path))?;
+                    let frame = if config.srgb {
+                        loaded.into_srgb_texture(display, config.mipmaps.into())?
+                    } else {
+                        LoadedTextureKind::File {
+                            texture: Rc::new(loaded.into_texture(display, config.mipmaps.into())?),
+                        }
+                    };
+                    let size = frame.dimensions();
+                    match dimensions {
+                        None => dimensions = Some(size),
+                        Some(x) => ensure!(
+                            x == size,
+                            "Sequence frame `{}` is {}x{} but the first frame is {}x{}",
+                            path,
+                            size.0,
+                            size.1,
+                            x.0,
+                            x.1
+                        ),
+                    }
+                    frames.push(frame);
+                }
+                LoadedTextureKind::Sequence { frames, fps: x.fps }
+            }
         };
         Ok(LoadedTexture {
             kind,
             config: config.clone(),
+            modified,
         })
     }
 
+    /// Returns the width and height of the texture.
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.kind.dimensions()
+    }
+}
+
+impl LoadedTextureKind {
+    fn dimensions(&self) -> (u32, u32) {
+        match *self {
+            LoadedTextureKind::File { ref texture }
+            | LoadedTextureKind::Empty { ref texture, .. } => {
+                (texture.get_width(), texture.get_height().unwrap_or(1))
+            }
+            LoadedTextureKind::SrgbFile { ref texture } => {
+                (texture.get_width(), texture.get_height().unwrap_or(1))
+            }
+            LoadedTextureKind::Unsigned { ref texture, .. } => {
+                (texture.get_width(), texture.get_height().unwrap_or(1))
+            }
+            LoadedTextureKind::Depth { ref texture, .. } => {
+                (texture.get_width(), texture.get_height().unwrap_or(1))
+            }
+            LoadedTextureKind::Lut { ref texture, .. }
+            | LoadedTextureKind::Volume { ref texture } => {
+                (texture.get_width(), texture.get_height().unwrap_or(1))
+            }
+            LoadedTextureKind::Sequence { ref frames, .. } => frames[0].dimensions(),
+        }
+    }
+}
+
+impl LoadedTexture {
     /// Resizes the texture if the texture size is a factor of the viewport size.
     pub fn resize(&mut self, dimensions: (u32, u32), display: &Display) -> Result<()> {
         match self.kind {
-            LoadedTextureKind::File { .. } => {}
+            LoadedTextureKind::File { .. }
+            | LoadedTextureKind::SrgbFile { .. }
+            | LoadedTextureKind::Lut { .. }
+            | LoadedTextureKind::Volume { .. }
+            | LoadedTextureKind::Sequence { .. } => {}
             LoadedTextureKind::Empty {
                 size,
                 format,
                 ref mut texture,
+                ref mut back,
+                ..
             } => match size {
                 TextureSize::Size { .. } => {}
                 TextureSize::ViewPort => {
-                    *texture = Texture2d::empty_with_format(
-                        display,
-                        format,
-                        self.config.mipmaps.into(),
-                        dimensions.0,
-                        dimensions.1,
-                    )
-                    .context("failed to create texture")?;
+                    let create = || {
+                        Texture2d::empty_with_format(
+                            display,
+                            format,
+                            self.config.mipmaps.into(),
+                            dimensions.0,
+                            dimensions.1,
+                        )
+                        .context("failed to create texture")
+                    };
+                    *texture = Rc::new(create()?);
+                    if let Some(back) = back {
+                        *back = Rc::new(create()?);
+                    }
+                }
+            },
+            LoadedTextureKind::Unsigned {
+                size,
+                format,
+                ref mut texture,
+            } => match size {
+                TextureSize::Size { .. } => {}
+                TextureSize::ViewPort => {
+                    *texture = Rc::new(
+                        UnsignedTexture2d::empty_with_format(
+                            display,
+                            format,
+                            self.config.mipmaps.into(),
+                            dimensions.0,
+                            dimensions.1,
+                        )
+                        .context("failed to create texture")?,
+                    );
                 }
             },
             LoadedTextureKind::Depth {
@@ -131,17 +604,78 @@ impl LoadedTexture {
             } => match size {
                 TextureSize::Size { .. } => {}
                 TextureSize::ViewPort => {
-                    *texture = DepthTexture2d::empty_with_format(
-                        display,
-                        format,
-                        self.config.mipmaps.into(),
-                        dimensions.0,
-                        dimensions.1,
-                    )
-                    .context("failed to create texture")?;
+                    *texture = Rc::new(
+                        DepthTexture2d::empty_with_format(
+                            display,
+                            format,
+                            self.config.mipmaps.into(),
+                            dimensions.0,
+                            dimensions.1,
+                        )
+                        .context("failed to create texture")?,
+                    );
                 }
             },
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ser, TexturePool};
+    use std::{
+        rc::Rc,
+        time::{Duration, SystemTime},
+    };
+
+    fn texture(source: &str) -> ser::Texture {
+        ron::from_str(source).unwrap()
+    }
+
+    fn pool(
+        config: &ser::Texture,
+        modified: Option<SystemTime>,
+        handle: &Rc<u32>,
+    ) -> TexturePool<Rc<u32>> {
+        let mut pool = TexturePool::default();
+        pool.0.insert(config.clone(), (modified, handle.clone()));
+        pool
+    }
+
+    #[test]
+    fn reuse_unchanged_texture() {
+        let config = texture(r#"(name: "noise", kind: File("noise.png"))"#);
+        let modified = Some(SystemTime::UNIX_EPOCH);
+        let handle = Rc::new(1);
+        let pool = pool(&config, modified, &handle);
+
+        let reused = pool.get(
+            &texture(r#"(name: "noise", kind: File("noise.png"))"#),
+            modified,
+        );
+        assert!(reused.is_some_and(|x| Rc::ptr_eq(&x, &handle)));
+    }
+
+    #[test]
+    fn reload_changed_config() {
+        let config = texture(r#"(name: "noise", kind: File("noise.png"))"#);
+        let pool = pool(&config, None, &Rc::new(1));
+
+        let changed = texture(r#"(name: "noise", kind: File("noise.png"), srgb: false)"#);
+        assert!(pool.get(&changed, None).is_none());
+        let renamed = texture(r#"(name: "other", kind: File("noise.png"))"#);
+        assert!(pool.get(&renamed, None).is_none());
+    }
+
+    #[test]
+    fn reload_modified_file() {
+        let config = texture(r#"(name: "noise", kind: File("noise.png"))"#);
+        let modified = SystemTime::UNIX_EPOCH;
+        let pool = pool(&config, Some(modified), &Rc::new(1));
+
+        let later = Some(modified + Duration::from_secs(1));
+        assert!(pool.get(&config, later).is_none());
+        assert!(pool.get(&config, None).is_none());
+    }
+}
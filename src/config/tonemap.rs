@@ -0,0 +1,177 @@
+//! Tone mapping of an HDR frame to the displayable range.
+use super::ser::Tonemap;
+use crate::{geom::Geometry, render::Vertex};
+use anyhow::{Context, Result};
+use glium::{
+    framebuffer::SimpleFrameBuffer,
+    program::ProgramCreationInput,
+    texture::{DepthFormat, DepthTexture2d, MipmapsOption, Texture2d, UncompressedFloatFormat},
+    uniform,
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler},
+    Display, DrawParameters, IndexBuffer, Program, Surface, VertexBuffer,
+};
+
+const VERTEX_SHADER: &str = r#"
+#version 330
+
+in vec3 position;
+
+out vec2 uv;
+
+void main(){
+    uv = position.xy * 0.5 + 0.5;
+    gl_Position = vec4(position.xy, 0.0, 1.0);
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+#version 330
+
+in vec2 uv;
+
+uniform sampler2D frame;
+uniform int operator;
+uniform float exposure;
+
+out vec4 color;
+
+vec3 reinhard(vec3 x){
+    return x / (1.0 + x);
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve.
+vec3 aces(vec3 x){
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+// Jim Hejl and Richard Burgess-Dawson's curve, which includes the gamma encoding.
+vec3 filmic(vec3 x){
+    x = max(vec3(0.0), x - 0.004);
+    vec3 encoded = (x * (6.2 * x + 0.5)) / (x * (6.2 * x + 1.7) + 0.06);
+    return pow(encoded, vec3(2.2));
+}
+
+void main(){
+    vec4 source = texture(frame, uv);
+    vec3 hdr = max(source.rgb * exposure, 0.0);
+    vec3 mapped;
+    if (operator == 1) {
+        mapped = reinhard(hdr);
+    } else if (operator == 2) {
+        mapped = aces(hdr);
+    } else if (operator == 3) {
+        mapped = filmic(hdr);
+    } else {
+        mapped = hdr;
+    }
+    color = vec4(mapped, source.a);
+}
+"#;
+
+#[derive(Debug)]
+pub struct Tonemapping {
+    /// The operator applied to the frame, the frame is rendered directly when `None`.
+    pub operator: Tonemap,
+    /// Multiplier applied to the colors before tone mapping.
+    pub exposure: f32,
+    program: Program,
+    quad: (VertexBuffer<Vertex>, IndexBuffer<u32>),
+    color: Texture2d,
+    depth: DepthTexture2d,
+}
+
+impl Tonemapping {
+    pub fn load(operator: Tonemap, exposure: f32, display: &Display) -> Result<Self> {
+        let program = Program::new(
+            display,
+            ProgramCreationInput::SourceCode {
+                vertex_shader: VERTEX_SHADER,
+                tessellation_control_shader: None,
+                tessellation_evaluation_shader: None,
+                geometry_shader: None,
+                fragment_shader: FRAGMENT_SHADER,
+                transform_feedback_varyings: None,
+                outputs_srgb: false,
+                uses_point_size: false,
+            },
+        )
+        .context("failed to compile tone mapping program")?;
+        let quad = Geometry::ScreenQuad.to_buffers(display)?;
+        let (color, depth) = Self::create_textures(display, display.get_framebuffer_dimensions())?;
+
+        Ok(Tonemapping {
+            operator,
+            exposure,
+            program,
+            quad,
+            color,
+            depth,
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.operator != Tonemap::None
+    }
+
+    fn create_textures(
+        display: &Display,
+        (width, height): (u32, u32),
+    ) -> Result<(Texture2d, DepthTexture2d)> {
+        let color = Texture2d::empty_with_format(
+            display,
+            UncompressedFloatFormat::F16F16F16F16,
+            MipmapsOption::NoMipmap,
+            width.max(1),
+            height.max(1),
+        )
+        .context("failed to create tone mapping texture")?;
+        let depth = DepthTexture2d::empty_with_format(
+            display,
+            DepthFormat::I24,
+            MipmapsOption::NoMipmap,
+            width.max(1),
+            height.max(1),
+        )
+        .context("failed to create tone mapping depth texture")?;
+        Ok((color, depth))
+    }
+
+    pub fn resize(&mut self, dimensions: (u32, u32), display: &Display) -> Result<()> {
+        let (color, depth) = Self::create_textures(display, dimensions)?;
+        self.color = color;
+        self.depth = depth;
+        Ok(())
+    }
+
+    /// Returns the HDR surface passes should render to instead of the frame.
+    pub fn target<'a>(&'a self, display: &Display) -> Result<SimpleFrameBuffer<'a>> {
+        SimpleFrameBuffer::with_depth_buffer(display, &self.color, &self.depth)
+            .context("could not create tone mapping frame buffer")
+    }
+
+    /// Draw the tone mapped image to the given surface.
+    pub fn apply<S: Surface>(&self, surface: &mut S) -> Result<()> {
+        let operator = match self.operator {
+            Tonemap::None => 0i32,
+            Tonemap::Reinhard => 1,
+            Tonemap::Aces => 2,
+            Tonemap::Filmic => 3,
+        };
+        let uniforms = uniform! {
+            frame: Sampler::new(&self.color)
+                .minify_filter(MinifySamplerFilter::Nearest)
+                .magnify_filter(MagnifySamplerFilter::Nearest),
+            operator: operator,
+            exposure: self.exposure,
+        };
+        surface
+            .draw(
+                &self.quad.0,
+                &self.quad.1,
+                &self.program,
+                &uniforms,
+                &DrawParameters::default(),
+            )
+            .context("could not apply tone mapping")
+    }
+}
@@ -1,9 +1,212 @@
-use crate::render::Vertex;
+use crate::render::{Tangent, Vertex};
 use anyhow::Result;
+use glam::f32::{Vec2, Vec3};
 use glium::{Display, IndexBuffer, VertexBuffer};
 use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    f32::consts::PI,
+};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The vertices and indices of a triangle list.
+pub type Mesh = (Vec<Vertex>, Vec<u32>);
+
+/// How the vertex buffer of a mesh is expected to be used.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BufferUsage {
+    /// The vertices never change after loading, which is the case for almost all meshes.
+    #[default]
+    #[serde(rename = "immutable")]
+    Immutable,
+    /// The vertices are replaced now and then, for example when a mesh is edited.
+    #[serde(rename = "dynamic")]
+    Dynamic,
+    /// The vertices are replaced every frame, for example by CPU driven animation. Requires
+    /// support for persistently mapped buffers.
+    #[serde(rename = "persistent")]
+    Persistent,
+}
+
+/// Upload a mesh to the GPU.
+pub fn upload(
+    mesh: &Mesh,
+    usage: BufferUsage,
+    display: &Display,
+) -> Result<(VertexBuffer<Vertex>, IndexBuffer<u32>)> {
+    let vertex_buffer = match usage {
+        BufferUsage::Immutable => VertexBuffer::immutable(display, &mesh.0)?,
+        BufferUsage::Dynamic => VertexBuffer::dynamic(display, &mesh.0)?,
+        BufferUsage::Persistent => VertexBuffer::persistent(display, &mesh.0)?,
+    };
+    let index_buffer = IndexBuffer::<u32>::immutable(
+        display,
+        glium::index::PrimitiveType::TrianglesList,
+        &mesh.1,
+    )?;
+    Ok((vertex_buffer, index_buffer))
+}
+
+/// Collect the unique edges of the triangles of a mesh as a line list.
+pub fn edges(mesh: &Mesh) -> Vec<u32> {
+    let mut seen = HashSet::new();
+    let mut res = Vec::new();
+    for triangle in mesh.1.chunks_exact(3) {
+        for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+            let (a, b) = (triangle[a], triangle[b]);
+            if seen.insert((a.min(b), a.max(b))) {
+                res.extend_from_slice(&[a, b]);
+            }
+        }
+    }
+    res
+}
+
+/// Calculate a tangent for every vertex of a mesh from its positions and texture coordinates.
+///
+/// The w component of the tangent contains the handedness of the tangent space, the bitangent
+/// can be calculated as `cross(normal, tangent.xyz) * tangent.w`.
+pub fn tangents(mesh: &Mesh) -> Vec<Tangent> {
+    let (verticies, index) = mesh;
+    let mut tangents = vec![Vec3::ZERO; verticies.len()];
+    let mut bitangents = vec![Vec3::ZERO; verticies.len()];
+
+    for triangle in index.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| &verticies[triangle[i] as usize]);
+        let edge_1 = Vec3::from(b.position) - Vec3::from(a.position);
+        let edge_2 = Vec3::from(c.position) - Vec3::from(a.position);
+        let uv_1 = Vec2::from(b.tex_coord) - Vec2::from(a.tex_coord);
+        let uv_2 = Vec2::from(c.tex_coord) - Vec2::from(a.tex_coord);
+
+        let det = uv_1.x * uv_2.y - uv_2.x * uv_1.y;
+        if det.abs() < f32::EPSILON {
+            continue;
+        }
+        let r = 1.0 / det;
+        let tangent = (edge_1 * uv_2.y - edge_2 * uv_1.y) * r;
+        let bitangent = (edge_2 * uv_1.x - edge_1 * uv_2.x) * r;
+        for i in triangle {
+            tangents[*i as usize] += tangent;
+            bitangents[*i as usize] += bitangent;
+        }
+    }
+
+    verticies
+        .iter()
+        .zip(tangents.iter().zip(bitangents.iter()))
+        .map(|(vertex, (tangent, bitangent))| {
+            let normal = Vec3::from(vertex.normal);
+            // Gram-Schmidt orthogonalize the tangent against the normal.
+            let mut t = (*tangent - normal * normal.dot(*tangent)).normalize_or_zero();
+            if t == Vec3::ZERO {
+                t = normal.any_orthonormal_vector();
+            }
+            let w = if normal.cross(t).dot(*bitangent) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            Tangent {
+                tangent: [t.x, t.y, t.z, w],
+            }
+        })
+        .collect()
+}
+
+/// Returns whether the winding of a triangle disagrees with the normals of its vertices.
+fn is_wrongly_wound(verticies: &[Vertex], triangle: &[u32]) -> bool {
+    let normal = triangle
+        .iter()
+        .map(|x| Vec3::from(verticies[*x as usize].normal))
+        .fold(Vec3::ZERO, |a, b| a + b);
+    face_normal(verticies, triangle).dot(normal) < 0.0
+}
+
+/// Count the triangles of a mesh which are wound opposite to their vertex normals.
+pub fn count_wrongly_wound(mesh: &Mesh) -> usize {
+    let (verticies, index) = mesh;
+    index
+        .chunks_exact(3)
+        .filter(|x| is_wrongly_wound(verticies, x))
+        .count()
+}
+
+/// Flip the triangles of a mesh which are wound opposite to their vertex normals, returns the
+/// amount of flipped triangles.
+pub fn fix_winding(mesh: &mut Mesh) -> usize {
+    let (verticies, index) = mesh;
+    let mut count = 0;
+    for triangle in index.chunks_exact_mut(3) {
+        if is_wrongly_wound(verticies, triangle) {
+            triangle.swap(1, 2);
+            count += 1;
+        }
+    }
+    count
+}
+
+/// How the normals of a mesh are shaded.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Shading {
+    /// Every triangle gets its own vertices with the normal of the face, for a faceted look.
+    #[serde(rename = "flat")]
+    Flat,
+    /// Vertices at the same position share the average normal of the faces around them.
+    #[serde(rename = "smooth")]
+    Smooth,
+}
+
+impl Shading {
+    pub fn apply(&self, mesh: Mesh) -> Mesh {
+        match *self {
+            Shading::Flat => flat_shaded(mesh),
+            Shading::Smooth => smooth_shaded(mesh),
+        }
+    }
+}
+
+fn face_normal(verticies: &[Vertex], triangle: &[u32]) -> Vec3 {
+    let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(verticies[triangle[i] as usize].position));
+    (b - a).cross(c - a)
+}
+
+/// Duplicate the vertices of every triangle and set their normal to the normal of the face.
+fn flat_shaded((verticies, index): Mesh) -> Mesh {
+    let mut res = Vec::with_capacity(index.len());
+    for triangle in index.chunks_exact(3) {
+        let normal = face_normal(&verticies, triangle).normalize_or_zero().into();
+        res.extend(triangle.iter().map(|x| Vertex {
+            normal,
+            ..verticies[*x as usize]
+        }));
+    }
+    let index = (0..res.len() as u32).collect();
+    (res, index)
+}
+
+/// Replace the normals of a mesh with the average normal of the faces around each position.
+///
+/// Vertices are not merged so seams in the texture coordinates are kept.
+fn smooth_shaded((mut verticies, index): Mesh) -> Mesh {
+    let key = |x: &Vertex| x.position.map(f32::to_bits);
+    let mut normals = HashMap::new();
+    for triangle in index.chunks_exact(3) {
+        // The cross product is weighted by the area of the face.
+        let normal = face_normal(&verticies, triangle);
+        for i in triangle {
+            *normals
+                .entry(key(&verticies[*i as usize]))
+                .or_insert(Vec3::ZERO) += normal;
+        }
+    }
+    for vertex in verticies.iter_mut() {
+        if let Some(normal) = normals.get(&key(vertex)) {
+            vertex.normal = normal.normalize_or_zero().into();
+        }
+    }
+    (verticies, index)
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Cube {
     #[serde(default = "one")]
     width: f32,
@@ -14,10 +217,7 @@ pub struct Cube {
 }
 
 impl Cube {
-    pub fn to_buffers(
-        &self,
-        display: &Display,
-    ) -> Result<(VertexBuffer<Vertex>, IndexBuffer<u32>)> {
+    pub fn mesh(&self) -> Result<Mesh> {
         let x = self.width / 2.0;
         let y = self.height / 2.0;
         let z = self.depth / 2.0;
@@ -176,14 +376,7 @@ impl Cube {
             })
         }
 
-        let vertex_buffer = VertexBuffer::immutable(display, verticies)?;
-        let index_buffer = IndexBuffer::<u32>::immutable(
-            display,
-            glium::index::PrimitiveType::TrianglesList,
-            &index,
-        )?;
-
-        Ok((vertex_buffer, index_buffer))
+        Ok((verticies.to_vec(), index))
     }
 }
 
@@ -191,6 +384,10 @@ fn one() -> f32 {
     1.0
 }
 
+fn half() -> f32 {
+    0.5
+}
+
 impl Default for Cube {
     fn default() -> Self {
         Cube {
@@ -201,12 +398,555 @@ impl Default for Cube {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Sphere {
+    #[serde(default = "one")]
+    radius: f32,
+    #[serde(default = "default_sphere_rings")]
+    rings: u32,
+    #[serde(default = "default_sphere_sectors")]
+    sectors: u32,
+}
+
+fn default_sphere_rings() -> u32 {
+    16
+}
+
+fn default_sphere_sectors() -> u32 {
+    32
+}
+
+impl Sphere {
+    pub fn mesh(&self) -> Result<Mesh> {
+        ensure!(
+            self.rings >= 3,
+            "Sphere must have at least 3 rings, found {}",
+            self.rings
+        );
+        ensure!(
+            self.sectors >= 3,
+            "Sphere must have at least 3 sectors, found {}",
+            self.sectors
+        );
+
+        let mut verticies = Vec::new();
+        for ring in 0..=self.rings {
+            let v = ring as f32 / self.rings as f32;
+            let theta = v * PI;
+            for sector in 0..=self.sectors {
+                let u = sector as f32 / self.sectors as f32;
+                let phi = u * 2.0 * PI;
+                let normal = [
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                ];
+                verticies.push(Vertex {
+                    position: [
+                        normal[0] * self.radius,
+                        normal[1] * self.radius,
+                        normal[2] * self.radius,
+                    ],
+                    normal,
+                    tex_coord: [u, v],
+                });
+            }
+        }
+
+        let stride = self.sectors + 1;
+        let mut index = Vec::new();
+        for ring in 0..self.rings {
+            for sector in 0..self.sectors {
+                let a = ring * stride + sector;
+                let b = a + stride;
+                let c = b + 1;
+                let d = a + 1;
+                // The triangles touching the poles would be degenerate.
+                if ring != 0 {
+                    index.extend_from_slice(&[a, d, b]);
+                }
+                if ring != self.rings - 1 {
+                    index.extend_from_slice(&[b, d, c]);
+                }
+            }
+        }
+
+        Ok((verticies, index))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Plane {
+    #[serde(default = "one")]
+    width: f32,
+    #[serde(default = "one")]
+    depth: f32,
+    #[serde(default = "default_plane_subdivisions")]
+    rows: u32,
+    #[serde(default = "default_plane_subdivisions")]
+    cols: u32,
+}
+
+fn default_plane_subdivisions() -> u32 {
+    16
+}
+
+impl Plane {
+    pub fn mesh(&self) -> Result<Mesh> {
+        ensure!(
+            self.rows >= 1,
+            "Plane must have at least 1 row, found {}",
+            self.rows
+        );
+        ensure!(
+            self.cols >= 1,
+            "Plane must have at least 1 column, found {}",
+            self.cols
+        );
+
+        let mut verticies = Vec::new();
+        for row in 0..=self.rows {
+            let v = row as f32 / self.rows as f32;
+            for col in 0..=self.cols {
+                let u = col as f32 / self.cols as f32;
+                verticies.push(Vertex {
+                    position: [(u - 0.5) * self.width, 0.0, (v - 0.5) * self.depth],
+                    normal: [0.0, 1.0, 0.0],
+                    tex_coord: [u, v],
+                });
+            }
+        }
+
+        let stride = self.cols + 1;
+        let mut index = Vec::new();
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                let a = row * stride + col;
+                let b = a + 1;
+                let d = a + stride;
+                let c = d + 1;
+                index.extend_from_slice(&[a, d, b, b, d, c]);
+            }
+        }
+
+        Ok((verticies, index))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Cylinder {
+    #[serde(default = "half")]
+    radius: f32,
+    #[serde(default = "one")]
+    height: f32,
+    #[serde(default = "default_segments")]
+    segments: u32,
+}
+
+impl Cylinder {
+    pub fn mesh(&self) -> Result<Mesh> {
+        ensure!(
+            self.segments >= 3,
+            "Cylinder must have at least 3 segments, found {}",
+            self.segments
+        );
+
+        let y = self.height / 2.0;
+        let mut verticies = Vec::new();
+        let mut index = Vec::new();
+
+        // The side wall, top and bottom ring vertices are interleaved.
+        for segment in 0..=self.segments {
+            let u = segment as f32 / self.segments as f32;
+            let (sin, cos) = (u * 2.0 * PI).sin_cos();
+            for (y, v) in [(y, 1.0), (-y, 0.0)] {
+                verticies.push(Vertex {
+                    position: [cos * self.radius, y, sin * self.radius],
+                    normal: [cos, 0.0, sin],
+                    tex_coord: [u, v],
+                });
+            }
+        }
+        for segment in 0..self.segments {
+            let a = segment * 2;
+            let b = a + 1;
+            let d = a + 2;
+            let c = a + 3;
+            index.extend_from_slice(&[a, d, b, b, d, c]);
+        }
+
+        push_cap(&mut verticies, &mut index, self.radius, y, self.segments);
+        push_cap(&mut verticies, &mut index, self.radius, -y, self.segments);
+
+        Ok((verticies, index))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Cone {
+    #[serde(default = "half")]
+    radius: f32,
+    #[serde(default = "one")]
+    height: f32,
+    #[serde(default = "default_segments")]
+    segments: u32,
+}
+
+impl Cone {
+    pub fn mesh(&self) -> Result<Mesh> {
+        ensure!(
+            self.segments >= 3,
+            "Cone must have at least 3 segments, found {}",
+            self.segments
+        );
+
+        let y = self.height / 2.0;
+        let mut verticies = Vec::new();
+        let mut index = Vec::new();
+
+        // The apex is duplicated for every segment so each side face gets its own normal.
+        let slope = (self.radius / self.height.abs().max(f32::EPSILON)).atan();
+        for segment in 0..=self.segments {
+            let u = segment as f32 / self.segments as f32;
+            let (sin, cos) = (u * 2.0 * PI).sin_cos();
+            let (apex_sin, apex_cos) = ((u + 0.5 / self.segments as f32) * 2.0 * PI).sin_cos();
+            verticies.push(Vertex {
+                position: [0.0, y, 0.0],
+                normal: [apex_cos * slope.cos(), slope.sin(), apex_sin * slope.cos()],
+                tex_coord: [u, 1.0],
+            });
+            verticies.push(Vertex {
+                position: [cos * self.radius, -y, sin * self.radius],
+                normal: [cos * slope.cos(), slope.sin(), sin * slope.cos()],
+                tex_coord: [u, 0.0],
+            });
+        }
+        for segment in 0..self.segments {
+            let apex = segment * 2;
+            let b = apex + 1;
+            let c = apex + 3;
+            index.extend_from_slice(&[b, apex, c]);
+        }
+
+        push_cap(&mut verticies, &mut index, self.radius, -y, self.segments);
+
+        Ok((verticies, index))
+    }
+}
+
+/// Add a flat disk at the given height, facing up if the height is positive and down otherwise.
+fn push_cap(verticies: &mut Vec<Vertex>, index: &mut Vec<u32>, radius: f32, y: f32, segments: u32) {
+    let up = y >= 0.0;
+    let normal = [0.0, if up { 1.0 } else { -1.0 }, 0.0];
+    let center = verticies.len() as u32;
+    verticies.push(Vertex {
+        position: [0.0, y, 0.0],
+        normal,
+        tex_coord: [0.5, 0.5],
+    });
+    for segment in 0..=segments {
+        let (sin, cos) = (segment as f32 / segments as f32 * 2.0 * PI).sin_cos();
+        verticies.push(Vertex {
+            position: [cos * radius, y, sin * radius],
+            normal,
+            tex_coord: [0.5 + cos * 0.5, 0.5 + sin * 0.5],
+        });
+    }
+    for segment in 0..segments {
+        let a = center + 1 + segment;
+        let b = a + 1;
+        if up {
+            index.extend_from_slice(&[center, b, a]);
+        } else {
+            index.extend_from_slice(&[center, a, b]);
+        }
+    }
+}
+
+fn default_segments() -> u32 {
+    32
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Capsule {
+    #[serde(default = "half")]
+    radius: f32,
+    /// Height of the cylinder between the two hemispheres.
+    #[serde(default = "one")]
+    height: f32,
+    /// Amount of segments around the capsule.
+    #[serde(default = "default_segments")]
+    segments: u32,
+    /// Amount of rings in each hemisphere.
+    #[serde(default = "default_capsule_rings")]
+    rings: u32,
+}
+
+fn default_capsule_rings() -> u32 {
+    8
+}
+
+impl Capsule {
+    pub fn mesh(&self) -> Result<Mesh> {
+        ensure!(
+            self.segments >= 3,
+            "Capsule must have at least 3 segments, found {}",
+            self.segments
+        );
+        ensure!(
+            self.rings >= 1,
+            "Capsule must have at least 1 ring, found {}",
+            self.rings
+        );
+
+        // The texture coordinates follow the length of the surface from the bottom to the top.
+        let y = self.height / 2.0;
+        let length = PI * self.radius + self.height;
+
+        // Rows go from the top pole to the bottom pole, the two rows at the equators of the
+        // hemispheres have the same normals which forms the wall of the cylinder.
+        let rows = self.rings * 2 + 1;
+        let mut verticies = Vec::new();
+        for row in 0..=rows {
+            let (theta, offset, distance) = if row <= self.rings {
+                let theta = row as f32 / self.rings as f32 * PI / 2.0;
+                (theta, y, theta * self.radius)
+            } else {
+                let theta = (row - 1) as f32 / self.rings as f32 * PI / 2.0;
+                (theta, -y, theta * self.radius + self.height)
+            };
+            let v = 1.0 - distance / length;
+            for sector in 0..=self.segments {
+                let u = sector as f32 / self.segments as f32;
+                let phi = u * 2.0 * PI;
+                let normal = [
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                ];
+                verticies.push(Vertex {
+                    position: [
+                        normal[0] * self.radius,
+                        normal[1] * self.radius + offset,
+                        normal[2] * self.radius,
+                    ],
+                    normal,
+                    tex_coord: [u, v],
+                });
+            }
+        }
+
+        let stride = self.segments + 1;
+        let mut index = Vec::new();
+        for row in 0..rows {
+            for sector in 0..self.segments {
+                let a = row * stride + sector;
+                let b = a + stride;
+                let c = b + 1;
+                let d = a + 1;
+                // The triangles touching the poles would be degenerate.
+                if row != 0 {
+                    index.extend_from_slice(&[a, d, b]);
+                }
+                if row != rows - 1 {
+                    index.extend_from_slice(&[b, d, c]);
+                }
+            }
+        }
+
+        Ok((verticies, index))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Torus {
+    /// Radius of the tube.
+    #[serde(default = "default_torus_inner_radius")]
+    inner_radius: f32,
+    /// Distance from the center of the torus to the center of the tube.
+    #[serde(default = "one")]
+    outer_radius: f32,
+    /// Amount of segments around the center of the torus.
+    #[serde(default = "default_torus_rings")]
+    rings: u32,
+    /// Amount of segments around the tube.
+    #[serde(default = "default_torus_sides")]
+    sides: u32,
+}
+
+fn default_torus_inner_radius() -> f32 {
+    0.25
+}
+
+fn default_torus_rings() -> u32 {
+    24
+}
+
+fn default_torus_sides() -> u32 {
+    16
+}
+
+impl Torus {
+    pub fn mesh(&self) -> Result<Mesh> {
+        ensure!(
+            self.rings >= 3,
+            "Torus must have at least 3 rings, found {}",
+            self.rings
+        );
+        ensure!(
+            self.sides >= 3,
+            "Torus must have at least 3 sides, found {}",
+            self.sides
+        );
+
+        let mut verticies = Vec::new();
+        for ring in 0..=self.rings {
+            let u = ring as f32 / self.rings as f32;
+            let (ring_sin, ring_cos) = (u * 2.0 * PI).sin_cos();
+            for side in 0..=self.sides {
+                let v = side as f32 / self.sides as f32;
+                let (side_sin, side_cos) = (v * 2.0 * PI).sin_cos();
+                let normal = [side_cos * ring_cos, side_sin, side_cos * ring_sin];
+                verticies.push(Vertex {
+                    position: [
+                        ring_cos * self.outer_radius + normal[0] * self.inner_radius,
+                        normal[1] * self.inner_radius,
+                        ring_sin * self.outer_radius + normal[2] * self.inner_radius,
+                    ],
+                    normal,
+                    tex_coord: [u, v],
+                });
+            }
+        }
+
+        let stride = self.sides + 1;
+        let mut index = Vec::new();
+        for ring in 0..self.rings {
+            for side in 0..self.sides {
+                let a = ring * stride + side;
+                let b = a + stride;
+                let c = b + 1;
+                let d = a + 1;
+                index.extend_from_slice(&[a, d, b, b, d, c]);
+            }
+        }
+
+        Ok((verticies, index))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Icosphere {
+    #[serde(default = "one")]
+    radius: f32,
+    #[serde(default = "default_icosphere_subdivisions")]
+    subdivisions: u32,
+}
+
+fn default_icosphere_subdivisions() -> u32 {
+    3
+}
+
+/// Subdividing more often than this creates an unreasonable amount of triangles.
+const MAX_ICOSPHERE_SUBDIVISIONS: u32 = 7;
+
+impl Icosphere {
+    pub fn mesh(&self) -> Result<Mesh> {
+        ensure!(
+            self.subdivisions <= MAX_ICOSPHERE_SUBDIVISIONS,
+            "Icosphere can have at most {} subdivisions, found {}",
+            MAX_ICOSPHERE_SUBDIVISIONS,
+            self.subdivisions
+        );
+
+        let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+        let mut positions: Vec<Vec3> = [
+            [-1.0, t, 0.0],
+            [1.0, t, 0.0],
+            [-1.0, -t, 0.0],
+            [1.0, -t, 0.0],
+            [0.0, -1.0, t],
+            [0.0, 1.0, t],
+            [0.0, -1.0, -t],
+            [0.0, 1.0, -t],
+            [t, 0.0, -1.0],
+            [t, 0.0, 1.0],
+            [-t, 0.0, -1.0],
+            [-t, 0.0, 1.0],
+        ]
+        .iter()
+        .map(|x| Vec3::from(*x).normalize())
+        .collect();
+
+        let mut index: Vec<u32> = vec![
+            0, 11, 5, 0, 5, 1, 0, 1, 7, 0, 7, 10, 0, 10, 11, 1, 5, 9, 5, 11, 4, 11, 10, 2, 10, 7,
+            6, 7, 1, 8, 3, 9, 4, 3, 4, 2, 3, 2, 6, 3, 6, 8, 3, 8, 9, 4, 9, 5, 2, 4, 11, 6, 2, 10,
+            8, 6, 7, 9, 8, 1,
+        ];
+
+        for _ in 0..self.subdivisions {
+            let mut midpoints = HashMap::new();
+            let mut midpoint = |a: u32, b: u32| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    let x = (positions[a as usize] + positions[b as usize]).normalize();
+                    positions.push(x);
+                    positions.len() as u32 - 1
+                })
+            };
+            index = index
+                .chunks_exact(3)
+                .flat_map(|x| {
+                    let (a, b, c) = (x[0], x[1], x[2]);
+                    let ab = midpoint(a, b);
+                    let bc = midpoint(b, c);
+                    let ca = midpoint(c, a);
+                    [a, ab, ca, b, bc, ab, c, ca, bc, ab, bc, ca]
+                })
+                .collect();
+        }
+
+        let verticies = positions
+            .iter()
+            .map(|x| Vertex {
+                position: (*x * self.radius).into(),
+                normal: (*x).into(),
+                tex_coord: [
+                    (x.z.atan2(x.x) / (2.0 * PI)).rem_euclid(1.0),
+                    x.y.clamp(-1.0, 1.0).acos() / PI,
+                ],
+            })
+            .collect();
+
+        Ok((verticies, index))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub enum Geometry {
     #[serde(rename = "screen_quad")]
     ScreenQuad,
     #[serde(rename = "cube")]
     Cube(Cube),
+    #[serde(rename = "sphere")]
+    Sphere(Sphere),
+    #[serde(rename = "plane")]
+    Plane(Plane),
+    #[serde(rename = "cylinder")]
+    Cylinder(Cylinder),
+    #[serde(rename = "cone")]
+    Cone(Cone),
+    #[serde(rename = "capsule")]
+    Capsule(Capsule),
+    #[serde(rename = "torus")]
+    Torus(Torus),
+    #[serde(rename = "icosphere")]
+    Icosphere(Icosphere),
+    /// Vertices and indices of a triangle list given directly in the config.
+    #[serde(rename = "raw")]
+    Raw {
+        vertices: Vec<Vertex>,
+        indices: Vec<u32>,
+    },
 }
 
 impl Geometry {
@@ -214,13 +954,51 @@ impl Geometry {
         &self,
         display: &Display,
     ) -> Result<(VertexBuffer<Vertex>, IndexBuffer<u32>)> {
+        upload(&self.mesh()?, BufferUsage::Immutable, display)
+    }
+
+    /// Generate the vertices and indices of the geometry.
+    pub fn mesh(&self) -> Result<Mesh> {
         match &self {
-            Geometry::Cube(ref x) => x.to_buffers(display),
-            Geometry::ScreenQuad => Ok(Self::screen_quad(display)),
+            Geometry::Cube(ref x) => x.mesh(),
+            Geometry::Sphere(ref x) => x.mesh(),
+            Geometry::Plane(ref x) => x.mesh(),
+            Geometry::Cylinder(ref x) => x.mesh(),
+            Geometry::Cone(ref x) => x.mesh(),
+            Geometry::Capsule(ref x) => x.mesh(),
+            Geometry::Torus(ref x) => x.mesh(),
+            Geometry::Icosphere(ref x) => x.mesh(),
+            Geometry::ScreenQuad => Ok(Self::screen_quad()),
+            Geometry::Raw {
+                ref vertices,
+                ref indices,
+            } => Self::raw(vertices, indices),
         }
     }
 
-    fn screen_quad(display: &Display) -> (VertexBuffer<Vertex>, IndexBuffer<u32>) {
+    fn raw(vertices: &[Vertex], indices: &[u32]) -> Result<Mesh> {
+        ensure!(
+            indices.len().is_multiple_of(3),
+            "Amount of indices must be a multiple of 3, found {}",
+            indices.len()
+        );
+        if let Some((idx, x)) = indices
+            .iter()
+            .enumerate()
+            .find(|(_, x)| **x as usize >= vertices.len())
+        {
+            bail!(
+                "Index `{}` at position {} is out of bounds for {} vertices",
+                x,
+                idx,
+                vertices.len()
+            );
+        }
+
+        Ok((vertices.to_vec(), indices.to_vec()))
+    }
+
+    fn screen_quad() -> Mesh {
         let verticies = vec![
             Vertex {
                 position: [-1.0, -1.0, 0.0],
@@ -246,13 +1024,51 @@ impl Geometry {
 
         let indicies: Vec<u32> = vec![0, 3, 2, 0, 2, 1];
 
-        let vertex = VertexBuffer::immutable(display, &verticies).unwrap();
-        let index = IndexBuffer::immutable(
-            display,
-            glium::index::PrimitiveType::TrianglesList,
-            &indicies,
-        )
-        .unwrap();
-        (vertex, index)
+        (verticies, indicies)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cube, Shading};
+    use glam::Vec3;
+
+    #[test]
+    fn flat_has_more_vertices_than_smooth() {
+        let (flat, flat_index) = Shading::Flat.apply(Cube::default().mesh().unwrap());
+        let (smooth, smooth_index) = Shading::Smooth.apply(Cube::default().mesh().unwrap());
+        assert_eq!(flat.len(), flat_index.len());
+        assert_eq!(flat_index.len(), smooth_index.len());
+        assert!(flat.len() > smooth.len());
+    }
+
+    #[test]
+    fn flat_normals_are_face_normals() {
+        let (verticies, index) = Shading::Flat.apply(Cube::default().mesh().unwrap());
+        for triangle in index.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| verticies[triangle[i] as usize]);
+            let normal = Vec3::from(a.normal);
+            assert!((normal.length() - 1.0).abs() < 1e-5);
+            // All axis aligned faces of a cube have a single non zero component.
+            assert_eq!(normal.abs().max_element(), 1.0);
+            assert_eq!(a.normal, b.normal);
+            assert_eq!(a.normal, c.normal);
+        }
+    }
+
+    #[test]
+    fn smooth_normals_are_shared_at_corners() {
+        let (verticies, _) = Shading::Smooth.apply(Cube::default().mesh().unwrap());
+        for vertex in verticies.iter() {
+            let normal = Vec3::from(vertex.normal);
+            assert!((normal.length() - 1.0).abs() < 1e-5);
+            // The faces around a corner all contribute, so the normal points out of the corner.
+            let position = Vec3::from(vertex.position);
+            assert_eq!(normal.signum(), position.signum());
+            assert!(normal.abs().min_element() > 0.0);
+            for other in verticies.iter().filter(|x| x.position == vertex.position) {
+                assert_eq!(other.normal, vertex.normal);
+            }
+        }
     }
 }
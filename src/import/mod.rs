@@ -0,0 +1,3 @@
+//! Importers which convert projects from other tools into a ShaderTool config.
+
+pub mod shadertoy;
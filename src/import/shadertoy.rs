@@ -0,0 +1,200 @@
+//! Importer for Shadertoy multi buffer projects.
+//!
+//! A project is a directory containing an `image.glsl` and optionally `buffer_a.glsl` through
+//! `buffer_d.glsl`. Channel bindings are read from a `shadertoy.json` in the same directory
+//! which maps each pass to its bound channels, for example:
+//!
+//! ```json
+//! {
+//!     "buffer_a": { "iChannel0": "buffer_a", "iChannel1": "noise.png" },
+//!     "image": { "iChannel0": "buffer_a" }
+//! }
+//! ```
+//!
+//! A channel either refers to one of the buffer passes or to an image file. A buffer reading
+//! itself sees its previous frame, so its target is not cleared between frames.
+//! The importer writes a `ShaderTool.json` together with a fragment shader for each pass which
+//! wraps the original source with the Shadertoy builtin uniforms.
+
+use anyhow::{Context, Result};
+use serde_json::json;
+use std::{collections::HashMap, fmt::Write as _, fs, path::Path};
+
+/// The passes of a Shadertoy project in the order they are rendered.
+const PASSES: [&str; 5] = ["buffer_a", "buffer_b", "buffer_c", "buffer_d", "image"];
+
+const CHANNELS: [&str; 4] = ["iChannel0", "iChannel1", "iChannel2", "iChannel3"];
+
+const MAPPING_FILE: &str = "shadertoy.json";
+
+const VERTEX_SHADER: &str = "shadertool_vertex.glsl";
+
+const VERTEX_SOURCE: &str = "#version 330
+in vec3 position;
+
+void main(){
+    gl_Position = vec4(position,1.0);
+}
+";
+
+type Mapping = HashMap<String, HashMap<String, String>>;
+
+/// Import the Shadertoy project in the given directory, writing the generated config and shaders
+/// into the same directory.
+pub fn import(dir: impl AsRef<Path>) -> Result<()> {
+    let dir = dir.as_ref();
+    let config_path = dir.join("ShaderTool.json");
+    if config_path.exists() {
+        bail!(
+            "Directory `{}` already contains a ShaderTool.json",
+            dir.display()
+        );
+    }
+
+    let mapping: Mapping = match fs::read_to_string(dir.join(MAPPING_FILE)) {
+        Ok(x) => serde_json::from_str(&x)
+            .with_context(|| format!("Failed to parse `{}`", MAPPING_FILE))?,
+        Err(_) => {
+            warn!("No `{}` found, all channels will be unbound", MAPPING_FILE);
+            Mapping::new()
+        }
+    };
+
+    let passes: Vec<&str> = PASSES
+        .iter()
+        .copied()
+        .filter(|x| dir.join(format!("{}.glsl", x)).exists())
+        .collect();
+    ensure!(
+        passes.contains(&"image"),
+        "Shadertoy project must contain an `image.glsl`"
+    );
+    for name in mapping.keys() {
+        ensure!(
+            passes.contains(&name.as_str()),
+            "Channel mapping references unknown pass `{}`",
+            name
+        );
+    }
+
+    let mut textures = Vec::new();
+    let mut file_textures = HashMap::new();
+    for pass in passes.iter().filter(|x| **x != "image") {
+        textures.push(json!({
+            "name": pass,
+            "kind": {"Empty": {"size": "view_port"}},
+            "wrap": "clamp",
+        }));
+    }
+
+    let mut config_passes = Vec::new();
+    for pass in passes.iter() {
+        let channels = mapping.get(*pass).cloned().unwrap_or_default();
+        let mut pass_textures = Vec::new();
+        for (channel, source) in channels.iter() {
+            ensure!(
+                CHANNELS.contains(&channel.as_str()),
+                "Invalid channel `{}` in pass `{}`, expected one of `iChannel0` to `iChannel3`",
+                channel,
+                pass
+            );
+            let texture = if passes.contains(&source.as_str()) && source != "image" {
+                source.clone()
+            } else {
+                ensure!(
+                    dir.join(source).exists(),
+                    "Channel `{}` of pass `{}` references `{}` which is neither a buffer nor an existing file",
+                    channel,
+                    pass,
+                    source
+                );
+                let len = file_textures.len();
+                file_textures
+                    .entry(source.clone())
+                    .or_insert_with(|| {
+                        let name = format!("file_{}", len);
+                        textures.push(json!({
+                            "name": name,
+                            "kind": {"File": source},
+                            "mipmaps": "generate",
+                        }));
+                        name
+                    })
+                    .clone()
+            };
+            pass_textures.push(json!({"name": texture, "as": channel}));
+        }
+
+        let source = fs::read_to_string(dir.join(format!("{}.glsl", pass)))
+            .with_context(|| format!("Failed to read shader for pass `{}`", pass))?;
+        let fragment_shader = format!("shadertool_{}.glsl", pass);
+        fs::write(dir.join(&fragment_shader), wrap_source(&source))
+            .with_context(|| format!("Failed to write shader for pass `{}`", pass))?;
+
+        let mut config_pass = json!({
+            "vertex_shader": VERTEX_SHADER,
+            "fragment_shader": fragment_shader,
+            "objects": ["screen_quad"],
+            "textures": pass_textures,
+            "settings": {"cull": "disabled", "depth": {"compare": "ignore", "write": false}},
+        });
+        if *pass != "image" {
+            // Later passes read the buffer of the current frame, only the buffer itself has to
+            // see the previous frame which the target keeps when not cleared.
+            let reads_itself = channels.values().any(|x| x == pass);
+            config_pass["target"] = json!({
+                "color": [{"name": pass, "as": "frag_color"}],
+                "clear": !reads_itself,
+            });
+        }
+        config_passes.push(config_pass);
+    }
+
+    fs::write(dir.join(VERTEX_SHADER), VERTEX_SOURCE).context("Failed to write vertex shader")?;
+
+    let config = json!({
+        "objects": [{"name": "screen_quad", "kind": {"geometry": "screen_quad"}}],
+        "textures": textures,
+        "passes": config_passes,
+    });
+    let file = fs::File::create(&config_path).context("Failed to create config file")?;
+    serde_json::to_writer_pretty(file, &config).context("Failed to write config file")?;
+
+    info!(
+        "Imported Shadertoy project into `{}`",
+        config_path.display()
+    );
+    Ok(())
+}
+
+/// Wraps a Shadertoy shader source with declarations for the Shadertoy builtin uniforms.
+fn wrap_source(source: &str) -> String {
+    let mut res = String::new();
+    writeln!(res, "#version 330").unwrap();
+    writeln!(res).unwrap();
+    writeln!(res, "uniform float time;").unwrap();
+    writeln!(res, "uniform vec2 window_size;").unwrap();
+    writeln!(res, "uniform vec2 mouse_pos;").unwrap();
+    for channel in CHANNELS.iter() {
+        writeln!(res, "uniform sampler2D texture_{};", channel).unwrap();
+    }
+    writeln!(res, "out vec4 frag_color;").unwrap();
+    writeln!(res).unwrap();
+    writeln!(res, "#define iResolution vec3(window_size, 1.0)").unwrap();
+    writeln!(res, "#define iTime time").unwrap();
+    writeln!(
+        res,
+        "#define iMouse vec4(mouse_pos.x, window_size.y - mouse_pos.y, 0.0, 0.0)"
+    )
+    .unwrap();
+    for channel in CHANNELS.iter() {
+        writeln!(res, "#define {} texture_{}", channel, channel).unwrap();
+    }
+    writeln!(res).unwrap();
+    res.push_str(source);
+    writeln!(res).unwrap();
+    writeln!(res, "void main(){{").unwrap();
+    writeln!(res, "    mainImage(frag_color, gl_FragCoord.xy);").unwrap();
+    writeln!(res, "}}").unwrap();
+    res
+}
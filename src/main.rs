@@ -8,17 +8,36 @@ use anyhow::Result;
 use glium::glutin::event_loop::EventLoop;
 
 mod app;
+mod args;
 //mod asset;
 mod config;
 mod geom;
+mod import;
 mod render;
 mod util;
 
 fn main() -> Result<()> {
     env_logger::init();
 
+    let args = args::Args::parse()?;
+    if let Some(ref dir) = args.import_shadertoy {
+        return import::shadertoy::import(dir);
+    }
+
     let event_loop = EventLoop::<app::UserEvent>::with_user_event();
-    let mut app = app::App::new(&event_loop)?;
+    if args.benchmark {
+        return app::benchmark::run(&event_loop, &args);
+    }
+    if args.reflect {
+        return app::reflect::run(&event_loop, &args);
+    }
+    if args.render_graph {
+        return app::graph::run(&event_loop, &args);
+    }
+    if let Some(ref path) = args.contact_sheet {
+        return app::contact_sheet::run(&event_loop, &args, path);
+    }
+    let mut app = app::App::new(&event_loop, &args)?;
 
     event_loop.run(move |event, _, control_flow| {
         trace!("EVENT: {:?}", event);
@@ -1,10 +1,21 @@
 use glium::implement_vertex;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Vertex {
     pub position: [f32; 3],
+    #[serde(default)]
     pub normal: [f32; 3],
+    #[serde(default)]
     pub tex_coord: [f32; 2],
 }
 
 implement_vertex!(Vertex, position, normal, tex_coord);
+
+/// A per vertex tangent, stored in a separate buffer as only some objects need it.
+#[derive(Clone, Copy, Debug)]
+pub struct Tangent {
+    pub tangent: [f32; 4],
+}
+
+implement_vertex!(Tangent, tangent);
@@ -21,7 +21,7 @@ impl<T: Clone> CellVec<T> {
     }
 
     pub fn get(&self, index: usize) -> T {
-        unsafe { (*self.0.get())[index].clone() }
+        unsafe { (&(*self.0.get()))[index].clone() }
     }
 
     pub fn clear(&self) {
@@ -0,0 +1,31 @@
+//! Conversion of the system time to a calendar date.
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Returns the year, month, day and seconds since midnight of a time in UTC.
+///
+/// The month starts at 0 and the day at 1, matching the `iDate` uniform of Shadertoy.
+pub fn date(time: SystemTime) -> [f32; 4] {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let days = (secs / 86400) as i64;
+    let seconds = (secs % 86400) as f32 + since_epoch.subsec_nanos() as f32 * 1e-9;
+
+    // Convert days since the epoch to a civil date, the year is shifted to start in March so
+    // leap days fall at the end of the year.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    [year as f32, (month - 1) as f32, day as f32, seconds]
+}
@@ -3,6 +3,9 @@ use std::{any::Any, rc::Rc};
 
 pub mod cell_vec;
 pub use cell_vec::CellVec;
+pub mod date;
+pub mod rng;
+pub use rng::Rng;
 pub mod slotmap;
 pub use slotmap::SlotMap;
 